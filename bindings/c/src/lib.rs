@@ -1,13 +1,47 @@
 use std::os::raw::c_char;
 use std::ptr;
 use std::ffi::CStr;
-use chemical_elements::{ChemicalComposition, ElementSpecification};
+use chemical_elements::{
+    ChemicalComposition, ElementSpecification, ElementSpecificationParsingErrorKind,
+    FormulaParserErrorKind,
+};
 
 
 #[derive(Default)]
 pub struct CChemicalComposition(ChemicalComposition<'static>);
 
 
+/// The error code returned for a formula that failed to parse. Unknown element symbols
+/// keep the code of `InvalidElement` they were reported as before the two were told apart,
+/// unknown isotopes share the code of malformed ones, and an invalid charge takes the next
+/// unused code.
+fn formula_error_code(kind: FormulaParserErrorKind) -> u32 {
+    match kind {
+        FormulaParserErrorKind::InvalidStart => 1,
+        FormulaParserErrorKind::ElementCountMalformed => 2,
+        FormulaParserErrorKind::IsotopeCountMalformed
+        | FormulaParserErrorKind::UnknownIsotope => 3,
+        FormulaParserErrorKind::GroupCountMalformed => 4,
+        FormulaParserErrorKind::IncompleteFormula => 5,
+        FormulaParserErrorKind::InvalidElement | FormulaParserErrorKind::UnknownElement => 6,
+        FormulaParserErrorKind::InvalidCharge => 7,
+    }
+}
+
+/// The error code returned for an element specification that failed to parse. A
+/// malformed or unknown isotope takes the next unused code, and trailing characters the
+/// one after it.
+fn element_specification_error_code(kind: ElementSpecificationParsingErrorKind) -> u32 {
+    match kind {
+        ElementSpecificationParsingErrorKind::UnclosedIsotope => 1,
+        ElementSpecificationParsingErrorKind::UnknownElement => 2,
+        ElementSpecificationParsingErrorKind::InvalidIsotope
+        | ElementSpecificationParsingErrorKind::UnknownIsotope => 3,
+        ElementSpecificationParsingErrorKind::TrailingCharacters => 4,
+    }
+}
+
+
 #[no_mangle]
 pub extern "C" fn parse_formula(formula: *mut c_char, out: *mut *mut CChemicalComposition) -> u32 {
     unsafe {
@@ -23,7 +57,7 @@ pub extern "C" fn parse_formula(formula: *mut c_char, out: *mut *mut CChemicalCo
                 0
             },
             Err(parse_err) => {
-                (parse_err as u32) + 1
+                formula_error_code(parse_err.kind)
             }
         }
     }
@@ -78,7 +112,7 @@ impl CChemicalComposition {
                     0
                 },
                 Err(e) => {
-                    e as u32 + 1
+                    element_specification_error_code(e.kind)
                 }
             }
         }
@@ -95,7 +129,7 @@ impl CChemicalComposition {
                     0
                 },
                 Err(e) => {
-                    e as u32 + 1
+                    element_specification_error_code(e.kind)
                 }
             }
         }
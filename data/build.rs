@@ -1,10 +1,32 @@
 /// A script to dynamically generate the periodic table constant in crate::table.
-/// Reads the isotopic distribution data from data/nist_mass.json.
+/// Reads the isotopic distribution data from data/nist_mass.json, and the atomic
+/// numbers and standard atomic weights from data/nist_comp.txt.
 use serde_json;
 use serde_json::{Map, Value};
+use std::collections::HashMap;
 use std::fs;
 use std::io::{Cursor, Read, Write};
 use std::path;
+use std::process;
+
+const ELEMENT_NAMES: [&str; 118] = [
+    "Hydrogen", "Helium", "Lithium", "Beryllium", "Boron", "Carbon", "Nitrogen", "Oxygen",
+    "Fluorine", "Neon", "Sodium", "Magnesium", "Aluminium", "Silicon", "Phosphorus", "Sulfur",
+    "Chlorine", "Argon", "Potassium", "Calcium", "Scandium", "Titanium", "Vanadium", "Chromium",
+    "Manganese", "Iron", "Cobalt", "Nickel", "Copper", "Zinc", "Gallium", "Germanium", "Arsenic",
+    "Selenium", "Bromine", "Krypton", "Rubidium", "Strontium", "Yttrium", "Zirconium", "Niobium",
+    "Molybdenum", "Technetium", "Ruthenium", "Rhodium", "Palladium", "Silver", "Cadmium",
+    "Indium", "Tin", "Antimony", "Tellurium", "Iodine", "Xenon", "Caesium", "Barium",
+    "Lanthanum", "Cerium", "Praseodymium", "Neodymium", "Promethium", "Samarium", "Europium",
+    "Gadolinium", "Terbium", "Dysprosium", "Holmium", "Erbium", "Thulium", "Ytterbium",
+    "Lutetium", "Hafnium", "Tantalum", "Tungsten", "Rhenium", "Osmium", "Iridium", "Platinum",
+    "Gold", "Mercury", "Thallium", "Lead", "Bismuth", "Polonium", "Astatine", "Radon",
+    "Francium", "Radium", "Actinium", "Thorium", "Protactinium", "Uranium", "Neptunium",
+    "Plutonium", "Americium", "Curium", "Berkelium", "Californium", "Einsteinium", "Fermium",
+    "Mendelevium", "Nobelium", "Lawrencium", "Rutherfordium", "Dubnium", "Seaborgium",
+    "Bohrium", "Hassium", "Meitnerium", "Darmstadtium", "Roentgenium", "Copernicium",
+    "Nihonium", "Flerovium", "Moscovium", "Livermorium", "Tennessine", "Oganesson",
+];
 
 fn load_from_file(path: &path::Path) -> Map<String, Value> {
     let mut reader = fs::File::open(path).unwrap();
@@ -15,6 +37,94 @@ fn load_from_file(path: &path::Path) -> Map<String, Value> {
     return m;
 }
 
+/// The per-element metadata from the NIST atomic weights and isotopic compositions listing
+#[derive(Default)]
+struct AtomicMetadata {
+    /// Maps symbols to atomic numbers
+    atomic_numbers: HashMap<String, u8>,
+    /// Maps atomic numbers to the Rust expression for the standard atomic weight
+    standard_atomic_weights: HashMap<u8, String>,
+}
+
+impl AtomicMetadata {
+    fn atomic_number(&self, symbol: &str) -> u8 {
+        if let Some(n) = self.atomic_numbers.get(symbol) {
+            return *n;
+        }
+        systematic_atomic_number(symbol).unwrap_or(0)
+    }
+}
+
+/// Decode the IUPAC systematic placeholder symbols like `Uuh` into their atomic number
+fn systematic_atomic_number(symbol: &str) -> Option<u8> {
+    if symbol.len() != 3 || !symbol.starts_with('U') {
+        return None;
+    }
+    let mut n: u32 = 0;
+    for c in symbol.to_lowercase().chars() {
+        let digit = "nubtqphsoe".find(c)? as u32;
+        n = n * 10 + digit;
+    }
+    Some(n as u8)
+}
+
+fn strip_uncertainty(value: &str) -> &str {
+    match value.find('(') {
+        Some(i) => &value[..i],
+        None => value,
+    }
+}
+
+fn parse_standard_atomic_weight(value: &str) -> String {
+    let value = value.trim();
+    if value.is_empty() {
+        return "StandardAtomicWeight::Unknown".to_string();
+    }
+    if let Some(inner) = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+        return match inner.split_once(',') {
+            Some((lo, hi)) => format!(
+                "StandardAtomicWeight::Interval({}, {})",
+                lo.trim().parse::<f64>().unwrap(),
+                hi.trim().parse::<f64>().unwrap()
+            ),
+            None => format!(
+                "StandardAtomicWeight::MassNumber({})",
+                inner.trim().parse::<u16>().unwrap()
+            ),
+        };
+    }
+    format!(
+        "StandardAtomicWeight::Value({:?})",
+        strip_uncertainty(value).parse::<f64>().unwrap()
+    )
+}
+
+fn load_atomic_metadata(path: &path::Path) -> AtomicMetadata {
+    let buf = fs::read_to_string(path).unwrap();
+    let mut metadata = AtomicMetadata::default();
+    for block in buf.split("\n\n") {
+        let mut fields = HashMap::new();
+        for line in block.lines() {
+            if line.starts_with('#') {
+                continue;
+            }
+            if let Some((k, v)) = line.split_once(" =") {
+                fields.insert(k.trim(), v.trim());
+            }
+        }
+        let (Some(number), Some(symbol)) = (fields.get("Atomic Number"), fields.get("Atomic Symbol")) else {
+            continue;
+        };
+        let number: u8 = number.parse().unwrap();
+        metadata.atomic_numbers.insert(symbol.to_string(), number);
+        metadata
+            .standard_atomic_weights
+            .entry(number)
+            .or_insert_with(|| parse_standard_atomic_weight(fields.get("Standard Atomic Weight").unwrap_or(&"")));
+    }
+    metadata
+}
+
 //' A Dummy copy of the implementation for convenience.
 #[derive(Default)]
 struct Isotope {
@@ -27,8 +137,8 @@ struct Isotope {
 
 fn write_prelude(buffer: &mut Cursor<Vec<u8>>) {
     write!(buffer, r#"
-use lazy_static::lazy_static;
-use crate::element::{{Element, Isotope, PeriodicTable}};
+use crate::element::{{Element, Isotope, PeriodicTable, StandardAtomicWeight}};
+use std::sync::LazyLock;
 
 
 pub fn populate_periodic_table(table: &mut PeriodicTable) {{
@@ -36,7 +146,29 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {{
 }
 
 
-fn prepare_element(buffer: &mut Cursor<Vec<u8>>, symbol: &String, isotopes: &Value) {
+fn element_metadata(symbol: &str, metadata: &AtomicMetadata) -> String {
+    let (number, name) = match symbol {
+        "e*" => (0, "Electron"),
+        "H+" => (0, "Proton"),
+        _ => {
+            let number = metadata.atomic_number(symbol);
+            (number, if number > 0 { ELEMENT_NAMES[number as usize - 1] } else { "" })
+        }
+    };
+    let weight = metadata
+        .standard_atomic_weights
+        .get(&number)
+        .filter(|_| number > 0)
+        .cloned()
+        .unwrap_or_else(|| "StandardAtomicWeight::Unknown".to_string());
+    format!(
+        "element_number: {}, name: String::from(\"{}\"), standard_atomic_weight: {},",
+        number, name, weight
+    )
+}
+
+
+fn prepare_element(buffer: &mut Cursor<Vec<u8>>, symbol: &String, isotopes: &Value, metadata: &AtomicMetadata) {
     let mut isos: Vec<Isotope> = Vec::new();
     let iso = isotopes.as_object().unwrap();
     let mut reference_entry = Isotope {
@@ -62,25 +194,22 @@ fn prepare_element(buffer: &mut Cursor<Vec<u8>>, symbol: &String, isotopes: &Val
     }
     isos.sort_by_key(|i| (i.abundance * 100.0).round() as i32);
     let n = isos.len();
+    let element_metadata = element_metadata(symbol, metadata);
     if n > 0 {
         let most_abundant_neutron_count = isos[n - 1].neutrons;
         let most_abundant_mass = isos[n - 1].mass;
-        let mut element_number = 0;
         for y in &mut isos {
             y.neutron_shift = ((y.neutrons as i32) - (most_abundant_neutron_count as i32)) as i8;
-            if y.neutron_shift == 0 {
-                element_number = y.neutrons;
-            }
         }
-        writeln!(buffer, "\n\tlet mut elt = Element {{ symbol: String::from(\"{}\"), most_abundant_isotope: {}, most_abundant_mass: {:.6}, element_number: {},..Default::default() }};",
-                 symbol, most_abundant_neutron_count, most_abundant_mass, element_number).unwrap();
+        writeln!(buffer, "\n\tlet mut elt = Element {{ symbol: String::from(\"{}\"), most_abundant_isotope: {}, most_abundant_mass: {:.6}, {} ..Default::default() }};",
+                 symbol, most_abundant_neutron_count, most_abundant_mass, element_metadata).unwrap();
         for y in &isos {
             writeln!(buffer, "\telt.isotopes.insert({}, Isotope {{ mass: {:.6}, abundance: {:.6}, neutrons: {}, neutron_shift: {} }});",
                         y.neutrons, y.mass, y.abundance, y.neutrons, y.neutron_shift).unwrap();
         }
     } else {
-        writeln!(buffer, "\tlet mut elt = Element {{ symbol: String::from(\"{}\"), most_abundant_isotope: {}, most_abundant_mass: {:.6}, ..Default::default() }};",
-                 symbol, 0, reference_entry.mass).unwrap();
+        writeln!(buffer, "\tlet mut elt = Element {{ symbol: String::from(\"{}\"), most_abundant_isotope: {}, most_abundant_mass: {:.6}, {} ..Default::default() }};",
+                 symbol, 0, reference_entry.mass, element_metadata).unwrap();
         writeln!(buffer, "\telt.isotopes.insert({}, Isotope {{ mass: {:.6}, abundance: {:.6}, neutrons: {}, neutron_shift: {} }});",
                     reference_entry.neutrons, reference_entry.mass, reference_entry.abundance, reference_entry.neutrons,
                     reference_entry.neutron_shift).unwrap();
@@ -91,24 +220,24 @@ fn prepare_element(buffer: &mut Cursor<Vec<u8>>, symbol: &String, isotopes: &Val
 fn main() {
     println!("cargo:rerun-if-changed=src/table.rs");
     let elements = load_from_file(path::Path::new("data/nist_mass.json"));
+    let metadata = load_atomic_metadata(path::Path::new("data/nist_comp.txt"));
     let mut buffer = Cursor::new(Vec::new());
     write_prelude(&mut buffer);
     for (key, val) in elements.iter() {
-        prepare_element(&mut buffer, key, val);
+        prepare_element(&mut buffer, key, val, &metadata);
     }
     write!(&mut buffer, r#"}}
 
-lazy_static! {{
-    pub static ref PERIODIC_TABLE: PeriodicTable = {{
-        let mut t = PeriodicTable::new();
-        populate_periodic_table(&mut t);
-        t
-    }};
-}}"#).unwrap();
+pub static PERIODIC_TABLE: LazyLock<PeriodicTable> = LazyLock::new(|| {{
+    let mut t = PeriodicTable::new();
+    populate_periodic_table(&mut t);
+    t
+}});
+"#).unwrap();
     buffer.set_position(0);
     let mut out = String::new();
     buffer.read_to_string(&mut out).unwrap();
     let mut destination = fs::File::create("src/table.rs").unwrap();
-    destination.write(out.as_bytes()).unwrap();
+    destination.write_all(out.as_bytes()).unwrap();
     process::Command::new("rustfmt").arg("src/table.rs").status().unwrap();
 }
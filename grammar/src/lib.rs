@@ -0,0 +1,397 @@
+/*!
+The formula grammar shared by `chemical_elements`'s `FormulaParser` and the `formula!` macro
+of `chemical_elements_macros`, so a formula accepted at compile time is exactly one accepted
+at runtime.
+
+[`parse`] resolves a formula to the elements it contains without knowing anything about
+them beyond which symbols exist. It also provides the symbol suggestions and the caret
+rendering both crates use to report errors.
+
+A formula is a sequence of elements and groups, like `K4[Fe(CN)6]`:
+
+- An element is a symbol, an optional isotope like `C[13]` and an optional count.
+  Pseudo-elements like `H+` and `e*` extend their symbol with the following `+` or `*`
+  when the extended symbol exists.
+- A group is a formula in `()` or `[]`, closed with the bracket that opened it, with an
+  optional count.
+- Components like those of the hydrate `CuSO4·5H2O` are separated by `·`, `.` or `*`, and
+  each may start with a multiplier.
+*/
+use std::fmt::{self, Display};
+use std::ops::Range;
+
+/// The number of nearby symbols suggested for an unknown element
+pub const SUGGESTION_LIMIT: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// A component or group started with something other than an element or group
+    InvalidStart,
+    ElementCountMalformed,
+    IsotopeCountMalformed,
+    GroupCountMalformed,
+    /// The formula, a component or a group was empty or unclosed, or a group was closed
+    /// with the wrong bracket
+    IncompleteFormula,
+    /// A character was found where an element symbol or group was expected
+    InvalidElement,
+    /// An element symbol was not one of the known symbols
+    UnknownElement,
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::InvalidStart => "invalid start of formula",
+            Self::ElementCountMalformed => "malformed element count",
+            Self::IsotopeCountMalformed => "malformed isotope",
+            Self::GroupCountMalformed => "malformed group count",
+            Self::IncompleteFormula => "incomplete formula",
+            Self::InvalidElement => "unexpected character",
+            Self::UnknownElement => "unknown element",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// An error parsing a formula
+pub struct Error {
+    pub kind: ErrorKind,
+    /// The byte range of the offending token in the formula
+    pub span: Range<usize>,
+}
+
+impl Error {
+    fn new(kind: ErrorKind, span: Range<usize>) -> Self {
+        Self { kind, span }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// An occurrence of an element in a formula
+pub struct Atom {
+    /// The byte range of the element's symbol in the formula
+    pub symbol: Range<usize>,
+    /// The isotope's mass number, or 0 for the element's natural abundance
+    pub isotope: u16,
+    /// The byte range of the isotope's mass number, which is empty without an isotope
+    pub isotope_span: Range<usize>,
+    /// The count, multiplied through any enclosing groups and component multipliers
+    pub count: i32,
+}
+
+/**
+Resolve `formula` to the elements it contains, in the order they are written, using
+`is_symbol` to recognize element symbols.
+
+An element written more than once appears once per occurrence.
+*/
+pub fn parse(formula: &str, is_symbol: impl Fn(&str) -> bool) -> Result<Vec<Atom>, Error> {
+    let mut parser = Parser {
+        formula,
+        is_symbol,
+        position: 0,
+        atoms: Vec::new(),
+    };
+    parser.components(false)?;
+    Ok(parser.atoms)
+}
+
+struct Parser<'a, F> {
+    formula: &'a str,
+    is_symbol: F,
+    position: usize,
+    atoms: Vec<Atom>,
+}
+
+impl<F: Fn(&str) -> bool> Parser<'_, F> {
+    fn peek(&self) -> Option<char> {
+        self.formula[self.position..].chars().next()
+    }
+
+    fn peek_second(&self) -> Option<char> {
+        self.formula[self.position..].chars().nth(1)
+    }
+
+    fn advance(&mut self) {
+        if let Some(c) = self.peek() {
+            self.position += c.len_utf8();
+        }
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> Range<usize> {
+        let start = self.position;
+        while self.peek().is_some_and(&predicate) {
+            self.advance();
+        }
+        start..self.position
+    }
+
+    fn count(&mut self, kind: ErrorKind) -> Result<Option<(i32, Range<usize>)>, Error> {
+        let digits = self.take_while(|c| c.is_ascii_digit());
+        if digits.is_empty() {
+            return Ok(None);
+        }
+        self.formula[digits.clone()]
+            .parse::<i32>()
+            .map(|count| Some((count, digits.clone())))
+            .map_err(|_| Error::new(kind, digits))
+    }
+
+    /// Multiply the counts of the atoms from `start` on by a group count or multiplier
+    fn multiply(&mut self, start: usize, count: i32, span: Range<usize>) -> Result<(), Error> {
+        for atom in &mut self.atoms[start..] {
+            atom.count = atom
+                .count
+                .checked_mul(count)
+                .ok_or_else(|| Error::new(ErrorKind::GroupCountMalformed, span.clone()))?;
+        }
+        Ok(())
+    }
+
+    /// Parse separated components until the end of the formula or, when `nested`, a closing
+    /// bracket
+    fn components(&mut self, nested: bool) -> Result<(), Error> {
+        loop {
+            let start = self.atoms.len();
+            let multiplier = self.count(ErrorKind::GroupCountMalformed)?;
+            self.sequence(nested)?;
+            if let Some((multiplier, span)) = multiplier {
+                self.multiply(start, multiplier, span)?;
+            }
+            match self.peek() {
+                Some('·' | '.' | '*') => self.advance(),
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    /// Parse elements and groups until the end of the component
+    fn sequence(&mut self, nested: bool) -> Result<(), Error> {
+        let start = self.position;
+        loop {
+            match self.peek() {
+                Some(c) if c.is_ascii_uppercase() => self.element()?,
+                Some(c @ ('(' | '[')) => self.group(c)?,
+                Some(')' | ']') if nested => break,
+                Some('·' | '.' | '*') | None => break,
+                Some(c) => {
+                    let kind = if self.position == start {
+                        ErrorKind::InvalidStart
+                    } else {
+                        ErrorKind::InvalidElement
+                    };
+                    return Err(Error::new(kind, self.position..self.position + c.len_utf8()));
+                }
+            }
+        }
+        if self.position == start {
+            return Err(Error::new(ErrorKind::IncompleteFormula, start..start));
+        }
+        Ok(())
+    }
+
+    fn group(&mut self, opener: char) -> Result<(), Error> {
+        let open_at = self.position;
+        let closer = if opener == '[' { ']' } else { ')' };
+        self.advance();
+        let start = self.atoms.len();
+        self.components(true)?;
+        match self.peek() {
+            Some(c) if c == closer => self.advance(),
+            // A closer of the other kind
+            Some(_) => {
+                return Err(Error::new(
+                    ErrorKind::IncompleteFormula,
+                    self.position..self.position + 1,
+                ))
+            }
+            None => return Err(Error::new(ErrorKind::IncompleteFormula, open_at..open_at + 1)),
+        }
+        if let Some((count, span)) = self.count(ErrorKind::GroupCountMalformed)? {
+            self.multiply(start, count, span)?;
+        }
+        Ok(())
+    }
+
+    fn element(&mut self) -> Result<(), Error> {
+        let start = self.position;
+        self.advance();
+        self.take_while(|c| c.is_ascii_lowercase());
+        if let Some(c @ ('+' | '*')) = self.peek() {
+            let extended = format!("{}{}", &self.formula[start..self.position], c);
+            if (self.is_symbol)(&extended) {
+                self.advance();
+            }
+        }
+        let symbol = start..self.position;
+        if !(self.is_symbol)(&self.formula[symbol.clone()]) {
+            return Err(Error::new(ErrorKind::UnknownElement, symbol));
+        }
+
+        let mut isotope = 0;
+        let mut isotope_span = self.position..self.position;
+        if self.peek() == Some('[') && self.peek_second().is_some_and(|c| c.is_ascii_digit()) {
+            let open_at = self.position;
+            self.advance();
+            isotope_span = self.take_while(|c| c.is_ascii_digit());
+            match self.peek() {
+                Some(']') => self.advance(),
+                Some(c) => {
+                    return Err(Error::new(
+                        ErrorKind::IsotopeCountMalformed,
+                        self.position..self.position + c.len_utf8(),
+                    ))
+                }
+                None => {
+                    return Err(Error::new(ErrorKind::IncompleteFormula, open_at..open_at + 1))
+                }
+            }
+            isotope = self.formula[isotope_span.clone()]
+                .parse::<u16>()
+                .map_err(|_| Error::new(ErrorKind::IsotopeCountMalformed, isotope_span.clone()))?;
+        }
+        let count = self
+            .count(ErrorKind::ElementCountMalformed)?
+            .map_or(1, |(count, _)| count);
+        self.atoms.push(Atom {
+            symbol,
+            isotope,
+            isotope_span,
+            count,
+        });
+        Ok(())
+    }
+}
+
+/**
+Find up to `limit` of `symbols` which are close to `symbol`, for suggesting corrections to
+a misspelled symbol.
+
+Symbols are compared by case-insensitive edit distance, preferring symbols which share
+the first letter of `symbol`.
+*/
+pub fn nearest_symbols<'a>(
+    symbols: impl IntoIterator<Item = &'a str>,
+    symbol: &str,
+    limit: usize,
+) -> Vec<&'a str> {
+    let query: Vec<char> = symbol.to_lowercase().chars().collect();
+    let max_distance = if query.len() > 2 { 2 } else { 1 };
+    let mut candidates: Vec<(usize, bool, &str)> = symbols
+        .into_iter()
+        .filter_map(|candidate| {
+            let lowered: Vec<char> = candidate.to_lowercase().chars().collect();
+            let distance = edit_distance(&query, &lowered);
+            (distance <= max_distance && candidate != symbol)
+                .then(|| (distance, query.first() != lowered.first(), candidate))
+        })
+        .collect();
+    candidates.sort();
+    candidates.into_iter().take(limit).map(|(_, _, s)| s).collect()
+}
+
+/// The Levenshtein distance between two strings
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/**
+Write a parse error message naming the offending token and any suggested replacements,
+followed by the parsed string with a caret under the token, like
+
+```text
+unknown element `Xx` at byte 7, did you mean `Xe`?
+C6H12O6Xx
+       ^^
+```
+*/
+pub fn write_diagnostic(
+    f: &mut dyn fmt::Write,
+    message: &dyn Display,
+    source: &str,
+    span: &Range<usize>,
+    suggestions: &[String],
+) -> fmt::Result {
+    let token = source.get(span.clone()).unwrap_or_default();
+    if token.is_empty() {
+        write!(f, "{} at byte {}", message, span.start)?;
+    } else {
+        write!(f, "{} `{}` at byte {}", message, token, span.start)?;
+    }
+    if !suggestions.is_empty() {
+        let suggestions: Vec<String> = suggestions.iter().map(|s| format!("`{s}`")).collect();
+        write!(f, ", did you mean {}?", suggestions.join(" or "))?;
+    }
+    let column = source
+        .get(..span.start)
+        .map(|s| s.chars().count())
+        .unwrap_or_default();
+    let width = token.chars().count().max(1);
+    write!(f, "\n{}\n{}{}", source, " ".repeat(column), "^".repeat(width))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SYMBOLS: &[&str] = &["C", "H", "N", "O", "S", "K", "Fe", "Cu", "Xe", "H+", "e*"];
+
+    fn resolve(formula: &str) -> Result<Vec<(&str, u16, i32)>, Error> {
+        let atoms = parse(formula, |s| SYMBOLS.contains(&s))?;
+        Ok(atoms
+            .into_iter()
+            .map(|atom| (&formula[atom.symbol], atom.isotope, atom.count))
+            .collect())
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(resolve("H2O").unwrap(), vec![("H", 0, 2), ("O", 0, 1)]);
+        assert_eq!(
+            resolve("K4[Fe(CN)6]·3H2O").unwrap(),
+            vec![("K", 0, 4), ("Fe", 0, 1), ("C", 0, 6), ("N", 0, 6), ("H", 0, 6), ("O", 0, 3)]
+        );
+        assert_eq!(resolve("[C[13]H3]2").unwrap(), vec![("C", 13, 2), ("H", 0, 6)]);
+        assert_eq!(resolve("2(H2O·2H+)").unwrap(), vec![("H", 0, 4), ("O", 0, 2), ("H+", 0, 4)]);
+    }
+
+    #[test]
+    fn test_errors() {
+        for (formula, kind, span) in [
+            ("", ErrorKind::IncompleteFormula, 0..0),
+            ("c6", ErrorKind::InvalidStart, 0..1),
+            ("C6Xx", ErrorKind::UnknownElement, 2..4),
+            ("C6 H12", ErrorKind::InvalidElement, 2..3),
+            ("C6H12O6)", ErrorKind::InvalidElement, 7..8),
+            ("C6H12(O6", ErrorKind::IncompleteFormula, 5..6),
+            ("(C6H12O6]", ErrorKind::IncompleteFormula, 8..9),
+            ("C(H2]3", ErrorKind::IncompleteFormula, 4..5),
+            ("()", ErrorKind::IncompleteFormula, 1..1),
+            ("CuSO4·", ErrorKind::IncompleteFormula, 7..7),
+            ("C[1x]", ErrorKind::IsotopeCountMalformed, 3..4),
+            ("C[13", ErrorKind::IncompleteFormula, 1..2),
+            ("C[99999]", ErrorKind::IsotopeCountMalformed, 2..7),
+            ("C99999999999", ErrorKind::ElementCountMalformed, 1..12),
+            ("(C100000)100000", ErrorKind::GroupCountMalformed, 9..15),
+        ] {
+            assert_eq!(resolve(formula).unwrap_err(), Error::new(kind, span), "{formula}");
+        }
+    }
+
+    #[test]
+    fn test_nearest_symbols() {
+        assert_eq!(nearest_symbols(SYMBOLS.iter().copied(), "Xx", 3), vec!["Xe"]);
+        assert_eq!(nearest_symbols(SYMBOLS.iter().copied(), "Fee", 3), vec!["Fe", "Xe", "e*"]);
+        assert!(nearest_symbols(SYMBOLS.iter().copied(), "Zz", 3).is_empty());
+    }
+}
@@ -0,0 +1,33 @@
+/// Generate the element table compiled into the macros from data/elements.txt, a listing of the
+/// symbols and isotopes of `chemical_elements`'s `PERIODIC_TABLE`.
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+fn main() {
+    println!("cargo:rerun-if-changed=data/elements.txt");
+    let listing =
+        fs::read_to_string("data/elements.txt").expect("Failed to read data/elements.txt");
+    let mut source = String::from("pub static ELEMENTS: &[(&str, &[u16])] = &[\n");
+    for line in listing.lines() {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (symbol, isotopes) = line
+            .split_once('\t')
+            .unwrap_or_else(|| panic!("Malformed element listing {line:?}"));
+        let isotopes: Vec<u16> = isotopes
+            .split_whitespace()
+            .map(|isotope| {
+                isotope
+                    .parse()
+                    .unwrap_or_else(|_| panic!("Malformed isotope in {line:?}"))
+            })
+            .collect();
+        writeln!(source, "    ({symbol:?}, &{isotopes:?}),").unwrap();
+    }
+    source.push_str("];\n");
+    let out_dir = env::var("OUT_DIR").unwrap();
+    fs::write(Path::new(&out_dir).join("elements.rs"), source).unwrap();
+}
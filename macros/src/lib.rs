@@ -0,0 +1,84 @@
+/*!
+Procedural macros for `chemical_elements`, re-exported by that crate when its `macros`
+feature is enabled.
+
+The symbols and isotopes of `chemical_elements`'s `PERIODIC_TABLE` are compiled into this
+crate from `data/elements.txt`, which the tests check against the runtime table, so formulas
+are validated against exactly the elements and isotopes available at runtime. The grammar
+itself comes from `chemical_elements_grammar`, which `FormulaParser` also uses.
+*/
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, LitStr};
+
+mod parser;
+mod table;
+
+use crate::parser::{parse, ElementCount};
+
+/**
+Build a `ChemicalComposition` from a formula validated at compile time against the
+built-in `PERIODIC_TABLE`.
+
+The formula uses the same notation as `ChemicalComposition::parse`, including isotopes
+like `C[13]`, groups in `()` or `[]` and hydrates like `CuSO4·5H2O`. Unknown elements,
+unknown isotopes and malformed counts are compile errors.
+
+The expansion looks up each element directly rather than parsing the formula, so it is
+cheap enough for hot paths and can initialize a `static` through `LazyLock`:
+
+```rust,ignore
+use std::sync::LazyLock;
+use chemical_elements::{formula, ChemicalComposition};
+
+static HEXOSE: LazyLock<ChemicalComposition<'static>> = LazyLock::new(|| formula!("C6H12O6"));
+```
+*/
+#[proc_macro]
+pub fn formula(input: TokenStream) -> TokenStream {
+    let literal = parse_macro_input!(input as LitStr);
+    let formula = literal.value();
+    match parse(&formula, &table::PERIODIC_TABLE) {
+        Ok(counts) => expand(&counts).into(),
+        Err(err) => {
+            // Point at the offending token where the compiler supports it, which requires
+            // that the literal's bytes match its value
+            let token = literal.token();
+            let span = if token.to_string() == format!("\"{formula}\"") {
+                token.subspan(err.span.start + 1..err.span.end + 1)
+            } else {
+                None
+            };
+            syn::Error::new(span.unwrap_or_else(|| literal.span()), err)
+                .to_compile_error()
+                .into()
+        }
+    }
+}
+
+fn expand(counts: &[ElementCount]) -> TokenStream2 {
+    let increments = counts.iter().map(|element| {
+        let ElementCount {
+            symbol,
+            isotope,
+            count,
+        } = element;
+        quote! {
+            composition.inc(
+                ::chemical_elements::ElementSpecification::new(
+                    &::chemical_elements::PERIODIC_TABLE[#symbol],
+                    #isotope,
+                ),
+                #count,
+            );
+        }
+    });
+    quote! {
+        {
+            let mut composition = ::chemical_elements::ChemicalComposition::new();
+            #(#increments)*
+            composition
+        }
+    }
+}
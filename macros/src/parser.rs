@@ -0,0 +1,217 @@
+//! Resolve a formula to element counts without building a composition, using the grammar
+//! `chemical_elements`'s `FormulaParser` shares through `chemical_elements_grammar`.
+use std::fmt;
+use std::ops::Range;
+
+use chemical_elements_grammar::{self as grammar, write_diagnostic, SUGGESTION_LIMIT};
+
+use crate::table::PeriodicTable;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ElementCount {
+    pub symbol: String,
+    pub isotope: u16,
+    pub count: i32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    pub formula: String,
+    /// The byte range of the offending token in `formula`
+    pub span: Range<usize>,
+    /// Element symbols close to an unknown element's symbol
+    pub suggestions: Vec<String>,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_diagnostic(f, &self.message, &self.formula, &self.span, &self.suggestions)
+    }
+}
+
+/// Resolve `formula` to the counts of each element and isotope, in order of first appearance
+pub fn parse(
+    formula: &str,
+    periodic_table: &PeriodicTable,
+) -> Result<Vec<ElementCount>, ParseError> {
+    let error = |message: &dyn fmt::Display, span: Range<usize>| ParseError {
+        message: message.to_string(),
+        formula: formula.to_string(),
+        span,
+        suggestions: Vec::new(),
+    };
+    let atoms = grammar::parse(formula, |symbol| periodic_table.get(symbol).is_some())
+        .map_err(|err| {
+            let mut parse_error = error(&err.kind, err.span);
+            if err.kind == grammar::ErrorKind::UnknownElement {
+                let symbol = &formula[parse_error.span.clone()];
+                parse_error.suggestions = periodic_table
+                    .nearest_symbols(symbol, SUGGESTION_LIMIT)
+                    .into_iter()
+                    .map(String::from)
+                    .collect();
+            }
+            parse_error
+        })?;
+
+    let mut counts: Vec<ElementCount> = Vec::new();
+    for atom in atoms {
+        let symbol = &formula[atom.symbol];
+        // As `FormulaParser` does, reject isotopes the table has no mass for
+        if !atom.isotope_span.is_empty() {
+            let element = periodic_table.get(symbol).expect("symbol was recognized");
+            if atom.isotope == 0 || !element.isotopes.contains(&atom.isotope) {
+                return Err(error(&"unknown isotope", atom.isotope_span));
+            }
+        }
+        match counts
+            .iter_mut()
+            .find(|element| element.symbol == symbol && element.isotope == atom.isotope)
+        {
+            Some(element) => element.count += atom.count,
+            None => counts.push(ElementCount {
+                symbol: symbol.to_string(),
+                isotope: atom.isotope,
+                count: atom.count,
+            }),
+        }
+    }
+    counts.retain(|element| element.count != 0);
+    Ok(counts)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::table::PERIODIC_TABLE;
+
+    fn count(counts: &[ElementCount], symbol: &str, isotope: u16) -> i32 {
+        counts
+            .iter()
+            .find(|e| e.symbol == symbol && e.isotope == isotope)
+            .map(|e| e.count)
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn test_parse() {
+        let counts = parse("C6H12O6", &PERIODIC_TABLE).unwrap();
+        assert_eq!(counts.len(), 3);
+        assert_eq!(count(&counts, "H", 0), 12);
+
+        let counts = parse("K4[Fe(CN)6]·3H2O", &PERIODIC_TABLE).unwrap();
+        assert_eq!(count(&counts, "N", 6), 0);
+        assert_eq!(count(&counts, "N", 0), 6);
+        assert_eq!(count(&counts, "O", 0), 3);
+        assert_eq!(count(&counts, "H", 0), 6);
+
+        let counts = parse("[C[13]H3]2O", &PERIODIC_TABLE).unwrap();
+        assert_eq!(count(&counts, "C", 13), 2);
+        assert_eq!(count(&counts, "H", 0), 6);
+        let counts = parse("H+", &PERIODIC_TABLE).unwrap();
+        assert_eq!(count(&counts, "H+", 0), 1);
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = parse("C6H12O6Xx", &PERIODIC_TABLE).unwrap_err();
+        assert_eq!(err.span, 7..9);
+        assert_eq!(
+            err.to_string(),
+            "unknown element `Xx` at byte 7, did you mean `Xe`?\nC6H12O6Xx\n       ^^"
+        );
+        let err = parse("C[99]H4", &PERIODIC_TABLE).unwrap_err();
+        assert_eq!(err.span, 2..4);
+        for formula in ["", "C6(H2O", "CuSO4·", "c6", "C6H12O6)"] {
+            assert!(parse(formula, &PERIODIC_TABLE).is_err(), "{formula}");
+        }
+    }
+
+    /// Both crates parse with `chemical_elements_grammar`, so `formula!` must accept and
+    /// reject exactly what `ChemicalComposition::parse` does
+    #[test]
+    fn test_agrees_with_runtime_parser() {
+        use chemical_elements::ChemicalComposition;
+
+        let good = [
+            "H2O",
+            "C6H12O6",
+            "C34H53O15N7",
+            "CH3N[15]",
+            "[C[13]H3]2O",
+            "C[13]6H12O6",
+            "(CH3)3C(CH2)2",
+            "K4[Fe(CN)6]·3H2O",
+            "CuSO4·5H2O",
+            "CaCl2*2H2O",
+            "CaCl2.2H2O",
+            "2H2O",
+            "C0H2",
+            "H+",
+            "H+2",
+            "((CH2)2O)3",
+            "NaCl·H2O·2NH3",
+            "[CuSO4·5H2O]2",
+            "[C(H2)]3",
+        ];
+        let bad = [
+            "",
+            "c6",
+            "6",
+            "C6H12O6Xx",
+            "K4[Fe(CX)6]·3H2O",
+            "CuSO4·5H2Q",
+            "CuSO4·",
+            "C6H12(O6",
+            "C6H12O6)",
+            "C6H12O6]",
+            "(C6H12O6]",
+            "[C6H12O6)",
+            "C(H2]3",
+            "C[1x]H4",
+            "C[13H4",
+            "C[99999]H4",
+            "C[99]",
+            "C[99]H4",
+            "C[0]H4",
+            "e*",
+            "H-2O-1",
+            "C-",
+            "C--2",
+            "C6 H12",
+            "()",
+            "C()",
+            "·H2O",
+        ];
+        for formula in good {
+            let counts = parse(formula, &PERIODIC_TABLE)
+                .unwrap_or_else(|err| panic!("{formula} should parse: {err}"));
+            let composition = ChemicalComposition::parse(formula)
+                .unwrap_or_else(|err| panic!("{formula} should parse: {err}"));
+            let mut expected: Vec<(String, u16, i32)> = composition
+                .iter()
+                .filter(|(_, count)| **count != 0)
+                .map(|(spec, count)| (spec.element.symbol.clone(), spec.isotope, *count))
+                .collect();
+            let mut observed: Vec<(String, u16, i32)> = counts
+                .into_iter()
+                .filter(|e| e.count != 0)
+                .map(|e| (e.symbol, e.isotope, e.count))
+                .collect();
+            expected.sort();
+            observed.sort();
+            assert_eq!(observed, expected, "{formula}");
+        }
+        for formula in bad {
+            assert!(
+                parse(formula, &PERIODIC_TABLE).is_err(),
+                "{formula} should be rejected"
+            );
+            assert!(
+                ChemicalComposition::parse(formula).is_err(),
+                "{formula} should be rejected by FormulaParser"
+            );
+        }
+    }
+}
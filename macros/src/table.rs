@@ -0,0 +1,96 @@
+//! The symbols and isotopes of `chemical_elements`'s `PERIODIC_TABLE`, generated by `build.rs`
+//! from `data/elements.txt`, which is all the formula grammar needs to validate a formula.
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+include!(concat!(env!("OUT_DIR"), "/elements.rs"));
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Element {
+    pub symbol: &'static str,
+    pub isotopes: &'static [u16],
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PeriodicTable {
+    elements: HashMap<&'static str, Element>,
+}
+
+impl PeriodicTable {
+    pub fn get(&self, symbol: &str) -> Option<&Element> {
+        self.elements.get(symbol)
+    }
+
+    /// The known symbols within a small edit distance of `symbol`, as suggested by
+    /// `chemical_elements`'s `PeriodicTable::nearest_symbols`
+    pub fn nearest_symbols(&self, symbol: &str, limit: usize) -> Vec<&str> {
+        chemical_elements_grammar::nearest_symbols(self.elements.keys().copied(), symbol, limit)
+    }
+}
+
+pub static PERIODIC_TABLE: LazyLock<PeriodicTable> = LazyLock::new(|| PeriodicTable {
+    elements: ELEMENTS
+        .iter()
+        .map(|(symbol, isotopes)| (*symbol, Element { symbol, isotopes }))
+        .collect(),
+});
+
+#[cfg(test)]
+mod test {
+    use std::env;
+    use std::fs;
+
+    use super::*;
+
+    /// Render `chemical_elements`'s `PERIODIC_TABLE` in the format of `data/elements.txt`
+    fn render_listing() -> String {
+        let mut elements: Vec<_> = chemical_elements::PERIODIC_TABLE
+            .elements
+            .values()
+            .collect();
+        elements.sort_by(|a, b| (a.element_number, &a.symbol).cmp(&(b.element_number, &b.symbol)));
+        let mut listing: String = LISTING_HEADER.into();
+        for element in elements {
+            let mut isotopes: Vec<u16> = element.isotopes.keys().copied().collect();
+            isotopes.sort();
+            let isotopes: Vec<String> = isotopes.iter().map(|i| i.to_string()).collect();
+            listing.push_str(&format!("{}\t{}\n", element.symbol, isotopes.join(" ")));
+        }
+        listing
+    }
+
+    const LISTING_HEADER: &str = "\
+# The symbol and isotopes of each element in chemical_elements's PERIODIC_TABLE, separated by a tab.
+# Regenerate with `CHEMICAL_ELEMENTS_BLESS=1 cargo test -p chemical_elements_macros` when src/table.rs changes.
+";
+
+    #[test]
+    fn test_listing_matches_runtime_table() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/data/elements.txt");
+        let expected = render_listing();
+        if env::var_os("CHEMICAL_ELEMENTS_BLESS").is_some() {
+            fs::write(path, &expected).unwrap();
+        }
+        let listing = fs::read_to_string(path).unwrap();
+        assert!(
+            listing == expected,
+            "data/elements.txt is out of date with PERIODIC_TABLE, regenerate it with \
+             CHEMICAL_ELEMENTS_BLESS=1"
+        );
+        assert_eq!(
+            PERIODIC_TABLE.elements.len(),
+            chemical_elements::PERIODIC_TABLE.elements.len()
+        );
+    }
+
+    #[test]
+    fn test_nearest_symbols() {
+        for symbol in ["Xx", "Cll", "Q", "h", "Fee", "Zz"] {
+            assert_eq!(
+                PERIODIC_TABLE.nearest_symbols(symbol, 3),
+                chemical_elements::PERIODIC_TABLE.nearest_symbols(symbol, 3),
+                "{symbol}"
+            );
+        }
+    }
+}
@@ -144,6 +144,120 @@ impl<'transient, 'inner: 'transient, 'lifespan: 'inner> ChemicalComposition<'lif
         }
     }
 
+    #[inline]
+    /// Calculate the monoisotopic mass from each element's lightest isotope.
+    pub fn monoisotopic_mass(&self) -> f64 {
+        match self {
+            ChemicalComposition::Vec(v) => v.monoisotopic_mass(),
+            ChemicalComposition::Map(m) => m.monoisotopic_mass(),
+        }
+    }
+
+    #[inline]
+    /// Get the mass from each element's most abundant isotope, an alias for `mass`.
+    pub fn most_abundant_mass(&self) -> f64 {
+        self.mass()
+    }
+
+    #[inline]
+    /// Explicitly calculate the abundance-weighted average mass, ignoring any caching.
+    pub fn calc_average_mass(&self) -> f64 {
+        match self {
+            ChemicalComposition::Vec(v) => v.calc_average_mass(),
+            ChemicalComposition::Map(m) => m.calc_average_mass(),
+        }
+    }
+
+    #[inline]
+    /// Get the average mass, using the cached value if available.
+    pub fn average_mass(&self) -> f64 {
+        match self {
+            ChemicalComposition::Vec(v) => v.average_mass(),
+            ChemicalComposition::Map(m) => m.average_mass(),
+        }
+    }
+
+    #[inline]
+    /// Get the average mass, and cache it, or reuse the cached value.
+    pub fn faverage_mass(&mut self) -> f64 {
+        match self {
+            ChemicalComposition::Vec(v) => v.faverage_mass(),
+            ChemicalComposition::Map(m) => m.faverage_mass(),
+        }
+    }
+
+    #[inline]
+    /// Explicitly calculate the molar mass from standard atomic weights, ignoring any caching.
+    pub fn calc_molar_mass(&self) -> f64 {
+        match self {
+            ChemicalComposition::Vec(v) => v.calc_molar_mass(),
+            ChemicalComposition::Map(m) => m.calc_molar_mass(),
+        }
+    }
+
+    #[inline]
+    /// Get the molar mass from standard atomic weights, using the cached value if available.
+    pub fn molar_mass(&self) -> f64 {
+        match self {
+            ChemicalComposition::Vec(v) => v.molar_mass(),
+            ChemicalComposition::Map(m) => m.molar_mass(),
+        }
+    }
+
+    #[inline]
+    /// Get the molar mass, and cache it, or reuse the cached value.
+    pub fn fmolar_mass(&mut self) -> f64 {
+        match self {
+            ChemicalComposition::Vec(v) => v.fmolar_mass(),
+            ChemicalComposition::Map(m) => m.fmolar_mass(),
+        }
+    }
+
+    #[inline]
+    /// Explicitly calculate the nominal mass, ignoring any caching.
+    pub fn calc_nominal_mass(&self) -> i64 {
+        match self {
+            ChemicalComposition::Vec(v) => v.calc_nominal_mass(),
+            ChemicalComposition::Map(m) => m.calc_nominal_mass(),
+        }
+    }
+
+    #[inline]
+    /// Get the nominal mass, using the cached value if available.
+    pub fn nominal_mass(&self) -> i64 {
+        match self {
+            ChemicalComposition::Vec(v) => v.nominal_mass(),
+            ChemicalComposition::Map(m) => m.nominal_mass(),
+        }
+    }
+
+    #[inline]
+    /// Get the nominal mass, and cache it, or reuse the cached value.
+    pub fn fnominal_mass(&mut self) -> i64 {
+        match self {
+            ChemicalComposition::Vec(v) => v.fnominal_mass(),
+            ChemicalComposition::Map(m) => m.fnominal_mass(),
+        }
+    }
+
+    #[inline]
+    /// Get the mass defect, the difference between the mass and the nominal mass.
+    pub fn mass_defect(&self) -> f64 {
+        match self {
+            ChemicalComposition::Vec(v) => v.mass_defect(),
+            ChemicalComposition::Map(m) => m.mass_defect(),
+        }
+    }
+
+    #[inline]
+    /// Get the mass defect, populating the mass and nominal mass caches.
+    pub fn fmass_defect(&mut self) -> f64 {
+        match self {
+            ChemicalComposition::Vec(v) => v.fmass_defect(),
+            ChemicalComposition::Map(m) => m.fmass_defect(),
+        }
+    }
+
     #[inline]
     pub(crate) fn _add_from(&mut self, other: &'transient ChemicalCompositionVec<'lifespan>) {
         for (key, val) in other.iter() {
@@ -217,7 +331,9 @@ impl<'transient, 'inner: 'transient, 'lifespan: 'inner> ChemicalComposition<'lif
     Parse a text formula into a [`ChemicalComposition`] using the
     global [`PeriodicTable`].
 
-    If the formula fails to parse, a [`FormulaParserError`] is returned.
+    If the formula fails to parse, a [`FormulaParserError`] is returned. A composition has
+    no charge, so a formula with a charge suffix like `C6H13O6+` is rejected as an
+    `InvalidCharge`; read it with [`ChemicalComposition::parse_charged`] instead.
 
     ```rust
     # use chemical_elements::ChemicalComposition;
@@ -240,8 +356,7 @@ impl<'transient, 'inner: 'transient, 'lifespan: 'inner> ChemicalComposition<'lif
         string: &str,
         periodic_table: &'lifespan PeriodicTable,
     ) -> Result<ChemicalComposition<'lifespan>, FormulaParserError> {
-        let mut parser = FormulaParser::default();
-        parser.parse_formula_with_table_generic(string, periodic_table)
+        FormulaParser::parse_formula_with_table_generic(string, periodic_table)
     }
 }
 
@@ -408,8 +523,7 @@ impl<'a> FromStr for ChemicalComposition<'a> {
     type Err = FormulaParserError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut parser = FormulaParser::default();
-        parser.parse_formula_with_table_generic(s, &PERIODIC_TABLE)
+        FormulaParser::parse_formula_with_table_generic(s, &PERIODIC_TABLE)
     }
 }
 
@@ -469,6 +583,75 @@ impl<'transient, 'inner: 'transient, 'lifespan: 'inner> ChemicalCompositionRef<'
         }
     }
 
+    #[inline]
+    /// Calculate the monoisotopic mass from each element's lightest isotope.
+    pub fn monoisotopic_mass(&self) -> f64 {
+        match self {
+            ChemicalCompositionRef::Vec(v) => v.monoisotopic_mass(),
+            ChemicalCompositionRef::Map(m) => m.monoisotopic_mass(),
+        }
+    }
+
+    #[inline]
+    /// Get the mass from each element's most abundant isotope, an alias for `mass`.
+    pub fn most_abundant_mass(&self) -> f64 {
+        self.mass()
+    }
+
+    #[inline]
+    /// Explicitly calculate the abundance-weighted average mass, ignoring any caching.
+    pub fn calc_average_mass(&self) -> f64 {
+        match self {
+            ChemicalCompositionRef::Vec(v) => v.calc_average_mass(),
+            ChemicalCompositionRef::Map(m) => m.calc_average_mass(),
+        }
+    }
+
+    #[inline]
+    /// Get the average mass, using the cached value if available.
+    pub fn average_mass(&self) -> f64 {
+        match self {
+            ChemicalCompositionRef::Vec(v) => v.average_mass(),
+            ChemicalCompositionRef::Map(m) => m.average_mass(),
+        }
+    }
+
+    #[inline]
+    /// Calculate the molar mass from standard atomic weights.
+    pub fn molar_mass(&self) -> f64 {
+        match self {
+            ChemicalCompositionRef::Vec(v) => v.molar_mass(),
+            ChemicalCompositionRef::Map(m) => m.molar_mass(),
+        }
+    }
+
+    #[inline]
+    /// Explicitly calculate the nominal mass, ignoring any caching.
+    pub fn calc_nominal_mass(&self) -> i64 {
+        match self {
+            ChemicalCompositionRef::Vec(v) => v.calc_nominal_mass(),
+            ChemicalCompositionRef::Map(m) => m.calc_nominal_mass(),
+        }
+    }
+
+    #[inline]
+    /// Get the nominal mass, using the cached value if available.
+    pub fn nominal_mass(&self) -> i64 {
+        match self {
+            ChemicalCompositionRef::Vec(v) => v.nominal_mass(),
+            ChemicalCompositionRef::Map(m) => m.nominal_mass(),
+        }
+    }
+
+    #[inline]
+    /// Get the mass defect, the difference between the mass and the nominal mass.
+    pub fn mass_defect(&self) -> f64 {
+        match self {
+            ChemicalCompositionRef::Vec(v) => v.mass_defect(),
+            ChemicalCompositionRef::Map(m) => m.mass_defect(),
+        }
+    }
+
     pub fn is_empty(&self) -> bool {
         match self {
             ChemicalCompositionRef::Vec(i) => i.is_empty(),
@@ -634,6 +817,21 @@ mod test {
         assert!((mass - calc).abs() < 1e-6);
     }
 
+    #[test]
+    fn test_average_and_nominal_mass() {
+        let mut case = ChemicalComposition::from(vec![("O", 1), ("H", 2)]);
+        assert!((case.average_mass() - 18.01528).abs() < 1e-3);
+        assert!((case.molar_mass() - 18.01528).abs() < 1e-3);
+        assert_eq!(case.nominal_mass(), 18);
+        assert!((case.mass_defect() - 0.0105646837).abs() < 1e-6);
+
+        let average = case.faverage_mass();
+        assert_eq!(case.fnominal_mass(), 18);
+        case.inc_str("O", 1);
+        assert_eq!(case.nominal_mass(), 34);
+        assert!(case.average_mass() > average + 15.9);
+    }
+
     #[test]
     fn test_add() {
         let case = ChemicalComposition::from(vec![("O", 1), ("H", 2)]);
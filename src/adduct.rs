@@ -0,0 +1,377 @@
+//! Describe how a neutral molecule is ionized with adducts, written in the
+//! common bracket notation like `[M+Na]+`, `[2M+H]+` or `[M-H2O+H]+`.
+//!
+//! ```rust
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use chemical_elements::{ChemicalComposition, IonType};
+//!
+//! let ion_type: IonType = "[M+Na]+".parse()?;
+//! let glucose = ChemicalComposition::parse("C6H12O6")?;
+//! let ion = ion_type.ion_composition(&glucose);
+//! assert_eq!(ion["Na"], 1);
+//! let mz = ion_type.ion_mz(&glucose);
+//! assert!((mz - 203.05261).abs() < 1e-4);
+//! assert!((ion_type.neutral_mass(mz) - glucose.mass()).abs() < 1e-6);
+//! # Ok(())
+//! # }
+//! ```
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use crate::formula::FormulaParserError;
+use crate::isotopic_pattern::{isotopic_variants, PeakList};
+use crate::mz::ELECTRON;
+use crate::table::PERIODIC_TABLE;
+use crate::{ChemicalComposition, PeriodicTable};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum IonTypeParserError {
+    /// The ion type was not enclosed in `[]`
+    MissingBrackets,
+    /// The molecule placeholder `M` was not found inside the brackets
+    MissingMolecule,
+    /// The multiplier in front of `M` was not a positive integer
+    InvalidMultimer,
+    /// An added or removed group did not specify a formula
+    EmptyAdduct,
+    /// An added or removed group's count was malformed or zero
+    AdductCountMalformed,
+    /// The charge following the brackets was missing, zero or malformed
+    InvalidCharge,
+    /// An added or removed group's formula could not be parsed, located within the ion type
+    InvalidFormula(FormulaParserError),
+}
+
+impl Display for IonTypeParserError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingBrackets => f.write_str("the ion type is not enclosed in `[]`"),
+            Self::MissingMolecule => f.write_str("the ion type has no molecule `M`"),
+            Self::InvalidMultimer => f.write_str("the multimer is not a positive integer"),
+            Self::EmptyAdduct => f.write_str("an adduct has no formula"),
+            Self::AdductCountMalformed => f.write_str("malformed adduct count"),
+            Self::InvalidCharge => f.write_str("the charge is missing, zero or malformed"),
+            Self::InvalidFormula(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for IonTypeParserError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::InvalidFormula(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<FormulaParserError> for IonTypeParserError {
+    fn from(value: FormulaParserError) -> Self {
+        Self::InvalidFormula(value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+/// A group of atoms added to (positive `count`) or removed from (negative `count`)
+/// the molecule when forming an ion
+pub struct Adduct<'lifespan> {
+    pub count: i32,
+    pub composition: ChemicalComposition<'lifespan>,
+}
+
+impl<'lifespan> Adduct<'lifespan> {
+    pub fn new(count: i32, composition: ChemicalComposition<'lifespan>) -> Self {
+        Self { count, composition }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/**
+An ion type, describing how many copies of a neutral molecule are combined with
+which [`Adduct`]s, and the resulting charge.
+
+The charge is assumed to arise from the adducts' atoms themselves, so the ion's mass is
+the sum of its atoms' masses, corrected for the electrons lost or gained.
+
+The m/z is only defined for a non-zero `charge` and `multimer`, which [`IonType::parse`]
+enforces along with non-zero adduct counts, but [`IonType::new`] does not.
+*/
+pub struct IonType<'lifespan> {
+    pub multimer: u32,
+    pub adducts: Vec<Adduct<'lifespan>>,
+    pub charge: i32,
+}
+
+impl<'lifespan> IonType<'lifespan> {
+    pub fn new(multimer: u32, adducts: Vec<Adduct<'lifespan>>, charge: i32) -> Self {
+        Self {
+            multimer,
+            adducts,
+            charge,
+        }
+    }
+
+    /// The composition added to the multimer to form the ion
+    pub fn delta(&self) -> ChemicalComposition<'lifespan> {
+        let mut delta = ChemicalComposition::new();
+        for adduct in self.adducts.iter() {
+            delta += &(&adduct.composition * adduct.count);
+        }
+        delta
+    }
+
+    /// The elemental composition of the ion formed from `composition`
+    pub fn ion_composition(
+        &self,
+        composition: &ChemicalComposition<'lifespan>,
+    ) -> ChemicalComposition<'lifespan> {
+        let multimer = composition * self.multimer as i32;
+        multimer + &self.delta()
+    }
+
+    /// The m/z of the ion formed from a molecule with the specified neutral mass
+    pub fn mass_charge_ratio(&self, neutral_mass: f64) -> f64 {
+        let z = self.charge as f64;
+        (neutral_mass * self.multimer as f64 + self.delta().mass() - z * ELECTRON) / z.abs()
+    }
+
+    /// The m/z of the ion formed from `composition`
+    pub fn ion_mz(&self, composition: &ChemicalComposition<'lifespan>) -> f64 {
+        self.mass_charge_ratio(composition.mass())
+    }
+
+    /// Invert an observed m/z back to the neutral mass of a single molecule
+    pub fn neutral_mass(&self, mz: f64) -> f64 {
+        let z = self.charge as f64;
+        (mz * z.abs() + z * ELECTRON - self.delta().mass()) / self.multimer as f64
+    }
+
+    /**
+    Generate the coarse isotopic pattern of the ion formed from `composition`, so that the
+    adducts' atoms contribute to the pattern. See [`isotopic_variants`].
+    */
+    pub fn isotopic_variants(
+        &self,
+        composition: &ChemicalComposition<'lifespan>,
+        npeaks: i32,
+    ) -> PeakList {
+        isotopic_variants(
+            self.ion_composition(composition),
+            npeaks,
+            self.charge,
+            -ELECTRON,
+        )
+    }
+
+    /// Parse an ion type written like `[2M-H2O+Na]+` using the global [`PeriodicTable`]
+    pub fn parse(text: &str) -> Result<IonType<'static>, IonTypeParserError> {
+        IonType::parse_with(text, &PERIODIC_TABLE)
+    }
+
+    /// Parse an ion type using the specified [`PeriodicTable`]
+    pub fn parse_with(
+        text: &str,
+        periodic_table: &'lifespan PeriodicTable,
+    ) -> Result<IonType<'lifespan>, IonTypeParserError> {
+        let text = text.trim();
+        let body = text
+            .strip_prefix('[')
+            .ok_or(IonTypeParserError::MissingBrackets)?;
+        let (body, charge) = body
+            .rsplit_once(']')
+            .ok_or(IonTypeParserError::MissingBrackets)?;
+        let charge = parse_charge(charge)?;
+
+        let (multimer, adducts) = body
+            .split_once('M')
+            .ok_or(IonTypeParserError::MissingMolecule)?;
+        // The position of the adducts in `text`, past `[`, the multimer and `M`
+        let adducts_start = 1 + multimer.len() + 1;
+        let multimer = if multimer.is_empty() {
+            1
+        } else {
+            match multimer.parse::<u32>() {
+                Ok(n) if n > 0 => n,
+                _ => return Err(IonTypeParserError::InvalidMultimer),
+            }
+        };
+
+        let mut parsed = Vec::new();
+        let mut rest = adducts;
+        // The position of `rest` in `text`, to locate formula errors in the whole ion type
+        let mut position = adducts_start;
+        while !rest.is_empty() {
+            let sign = match rest.as_bytes()[0] {
+                b'+' => 1,
+                b'-' => -1,
+                _ => return Err(IonTypeParserError::AdductCountMalformed),
+            };
+            rest = &rest[1..];
+            let end = rest.find(['+', '-']).unwrap_or(rest.len());
+            let (token, tail) = rest.split_at(end);
+            rest = tail;
+            let token_start = position + 1;
+            position = token_start + token.len();
+
+            let digits = token.len() - token.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            let (count, formula) = token.split_at(digits);
+            let count = if count.is_empty() {
+                1
+            } else {
+                match count.parse::<i32>() {
+                    Ok(n) if n > 0 => n,
+                    _ => return Err(IonTypeParserError::AdductCountMalformed),
+                }
+            };
+            if formula.is_empty() {
+                return Err(IonTypeParserError::EmptyAdduct);
+            }
+            let composition = ChemicalComposition::parse_with(formula, periodic_table)
+                .map_err(|err| err.within(text, token_start + digits))?;
+            parsed.push(Adduct::new(sign * count, composition));
+        }
+        Ok(IonType::new(multimer, parsed, charge))
+    }
+}
+
+fn parse_charge(text: &str) -> Result<i32, IonTypeParserError> {
+    let (magnitude, sign) = if let Some(m) = text.strip_suffix('+') {
+        (m, 1)
+    } else if let Some(m) = text.strip_suffix('-') {
+        (m, -1)
+    } else if let Some(m) = text.strip_prefix('+') {
+        (m, 1)
+    } else if let Some(m) = text.strip_prefix('-') {
+        (m, -1)
+    } else {
+        return Err(IonTypeParserError::InvalidCharge);
+    };
+    let magnitude = if magnitude.is_empty() {
+        1
+    } else {
+        magnitude
+            .parse::<i32>()
+            .map_err(|_| IonTypeParserError::InvalidCharge)?
+    };
+    if magnitude <= 0 {
+        return Err(IonTypeParserError::InvalidCharge);
+    }
+    Ok(sign * magnitude)
+}
+
+impl FromStr for IonType<'static> {
+    type Err = IonTypeParserError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        IonType::parse(s)
+    }
+}
+
+impl<'lifespan> Display for IonType<'lifespan> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[")?;
+        if self.multimer != 1 {
+            write!(f, "{}", self.multimer)?;
+        }
+        f.write_str("M")?;
+        for adduct in self.adducts.iter() {
+            f.write_str(if adduct.count < 0 { "-" } else { "+" })?;
+            if adduct.count.abs() != 1 {
+                write!(f, "{}", adduct.count.abs())?;
+            }
+            f.write_str(&adduct.composition.to_string())?;
+        }
+        f.write_str("]")?;
+        if self.charge.abs() != 1 {
+            write!(f, "{}", self.charge.abs())?;
+        }
+        f.write_str(if self.charge < 0 { "-" } else { "+" })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::formula::FormulaParserErrorKind;
+    use crate::{mass_charge_ratio, PROTON};
+
+    #[test]
+    fn test_parse() {
+        let ion_type = IonType::parse("[2M-H2O+2H]2+").unwrap();
+        assert_eq!(ion_type.multimer, 2);
+        assert_eq!(ion_type.charge, 2);
+        assert_eq!(ion_type.adducts.len(), 2);
+        assert_eq!(ion_type.adducts[0].count, -1);
+        assert_eq!(ion_type.adducts[1].count, 2);
+        assert_eq!(IonType::parse(&ion_type.to_string()).unwrap(), ion_type);
+
+        let delta = ion_type.delta();
+        assert_eq!(delta["H"], 0);
+        assert_eq!(delta["O"], -1);
+
+        assert_eq!(IonType::parse("[M-H]-").unwrap().charge, -1);
+        assert!(IonType::parse("M+H").is_err());
+        assert!(IonType::parse("[M+H]").is_err());
+        assert!(IonType::parse("[M+2]+").is_err());
+
+        // Zero charges, multimers and adduct counts would make the m/z undefined
+        assert_eq!(IonType::parse("[M]0"), Err(IonTypeParserError::InvalidCharge));
+        assert_eq!(IonType::parse("[M+H]0+"), Err(IonTypeParserError::InvalidCharge));
+        assert_eq!(IonType::parse("[0M+H]+"), Err(IonTypeParserError::InvalidMultimer));
+        assert_eq!(
+            IonTypeParserError::InvalidCharge.to_string(),
+            "the charge is missing, zero or malformed"
+        );
+        assert_eq!(
+            IonType::parse("[M+0H]+"),
+            Err(IonTypeParserError::AdductCountMalformed)
+        );
+        assert_eq!(
+            IonType::parse("[M+H-0H2O]+"),
+            Err(IonTypeParserError::AdductCountMalformed)
+        );
+
+        let err = IonType::parse("[M+2Xx-H]+").unwrap_err();
+        let IonTypeParserError::InvalidFormula(inner) = &err else {
+            panic!("{err:?}")
+        };
+        assert_eq!(inner.kind, FormulaParserErrorKind::UnknownElement);
+        assert_eq!(inner.span, 4..6);
+        assert_eq!(inner.token(), "Xx");
+        assert!(!inner.suggestions.is_empty());
+        assert_eq!(err.to_string(), inner.to_string());
+        assert!(err.to_string().contains("[M+2Xx-H]+"));
+    }
+
+    #[test]
+    fn test_mz() {
+        let comp = ChemicalComposition::parse("C34H53O15N7").unwrap();
+        let mass = comp.mass();
+        for (ion, z) in [("[M+H]+", 1), ("[M+2H]2+", 2), ("[M-H]-", -1)] {
+            let ion_type = IonType::parse(ion).unwrap();
+            let expected = mass_charge_ratio(mass, z, PROTON);
+            assert!((ion_type.ion_mz(&comp) - expected).abs() < 1e-5);
+            assert!((ion_type.neutral_mass(expected) - mass).abs() < 1e-5);
+        }
+
+        let dimer = IonType::parse("[2M+NH4]+").unwrap();
+        let ion = dimer.ion_composition(&comp);
+        assert_eq!(ion["C"], 68);
+        assert_eq!(ion["N"], 15);
+        assert!((dimer.neutral_mass(dimer.ion_mz(&comp)) - mass).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_isotopic_variants() {
+        let comp = ChemicalComposition::parse("C6H12O6").unwrap();
+        let sodium = IonType::parse("[M+Na]+").unwrap();
+        let potassium = IonType::parse("[M+K]+").unwrap();
+        let na_peaks = sodium.isotopic_variants(&comp, 3);
+        let k_peaks = potassium.isotopic_variants(&comp, 3);
+        assert!((na_peaks[0].mz - sodium.ion_mz(&comp)).abs() < 1e-4);
+        assert!((k_peaks[0].mz - potassium.ion_mz(&comp)).abs() < 1e-4);
+        assert_eq!(k_peaks[0].charge, 1);
+        // 41K raises the M+2 peak, while sodium is monoisotopic
+        assert!(k_peaks[2].intensity > na_peaks[2].intensity + 0.04);
+    }
+}
@@ -0,0 +1,341 @@
+//! Formulas with a net charge, like `C6H13O6+` or `[Fe(CN)6]4-`.
+//!
+//! A charge is carried by [`ChargedComposition`] rather than [`ChemicalComposition`], which
+//! has no charge of its own, so charged formulas are read with [`ChargedComposition::parse`]
+//! or [`ChemicalComposition::parse_charged`]. [`ChemicalComposition::parse`] rejects a charge
+//! suffix with [`FormulaParserErrorKind::InvalidCharge`] instead of dropping it.
+//!
+//! Likewise, the charge is applied to an isotopic pattern by
+//! [`ChargedComposition::isotopic_variants`], while the free [`isotopic_variants`] function
+//! still takes the charge and charge carrier explicitly. A [`ChargedComposition`] does not
+//! convert into a [`ChemicalComposition`], so its charge can't be lost on the way; use its
+//! `composition` field to opt out of the charge.
+use std::fmt;
+use std::ops::{Deref, DerefMut, Range};
+use std::str::FromStr;
+
+#[cfg(feature = "serde")]
+use serde_with::{DeserializeFromStr, SerializeDisplay};
+
+use crate::abstract_composition::ChemicalComposition;
+use crate::formula::{
+    parse_formula_with_table, to_formula, FormulaParserError, FormulaParserErrorKind,
+};
+use crate::isotopic_pattern::{isotopic_variants, PeakList};
+use crate::mz::ELECTRON;
+use crate::table::PERIODIC_TABLE;
+use crate::PeriodicTable;
+
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(DeserializeFromStr, SerializeDisplay))]
+/**
+A [`ChemicalComposition`] carrying a net charge, written with a charge suffix like
+`C6H13O6+`, `SO4-2`, `NH4^+` or `[Fe(CN)6]4-`.
+
+The charge arises from the composition's own atoms, so the ion's mass is the sum of its
+atoms' masses corrected for the electrons lost or gained.
+
+```rust
+# fn main() -> Result<(), Box<dyn std::error::Error>> {
+use chemical_elements::{ChargedComposition, ChemicalComposition};
+
+let ion = ChemicalComposition::parse_charged("[Fe(CN)6]4-")?;
+assert_eq!(ion.charge, -4);
+assert_eq!(ion["C"], 6);
+let round_trip: ChargedComposition = ion.to_string().parse()?;
+assert_eq!(round_trip, ion);
+# Ok(())
+# }
+```
+*/
+pub struct ChargedComposition<'lifespan> {
+    pub composition: ChemicalComposition<'lifespan>,
+    pub charge: i32,
+}
+
+impl<'lifespan> ChargedComposition<'lifespan> {
+    pub fn new(composition: ChemicalComposition<'lifespan>, charge: i32) -> Self {
+        Self {
+            composition,
+            charge,
+        }
+    }
+
+    /// Parse a charged formula using the global [`PeriodicTable`]. A formula without a
+    /// charge suffix is neutral.
+    pub fn parse(string: &str) -> Result<ChargedComposition<'lifespan>, FormulaParserError> {
+        ChargedComposition::parse_with(string, &PERIODIC_TABLE)
+    }
+
+    /// Parse a charged formula using the specified [`PeriodicTable`]
+    pub fn parse_with(
+        string: &str,
+        periodic_table: &'lifespan PeriodicTable,
+    ) -> Result<ChargedComposition<'lifespan>, FormulaParserError> {
+        let offset = string.len() - string.trim_start().len();
+        let trimmed = string.trim();
+        let (body, charge) =
+            split_charge(trimmed).map_err(|err| err.within(string, offset))?;
+        let composition = parse_formula_with_table(&trimmed[body.clone()], periodic_table)
+            .map_err(|err| err.within(string, offset + body.start))?;
+        Ok(ChargedComposition::new(composition, charge))
+    }
+
+    /// The mass of the ion, with the electrons lost or gained accounted for
+    pub fn mass(&self) -> f64 {
+        self.composition.mass() - self.charge as f64 * ELECTRON
+    }
+
+    /// The m/z of the ion, or its mass if it is neutral
+    pub fn mz(&self) -> f64 {
+        if self.charge == 0 {
+            self.mass()
+        } else {
+            self.mass() / self.charge.abs() as f64
+        }
+    }
+
+    /**
+    Generate the coarse isotopic pattern of the ion at its own charge. See
+    [`isotopic_variants`].
+    */
+    pub fn isotopic_variants(&self, npeaks: i32) -> PeakList {
+        isotopic_variants(self.composition.clone(), npeaks, self.charge, -ELECTRON)
+    }
+
+    /// Write the formula followed by its charge suffix, as read by [`ChargedComposition::parse`]
+    pub fn to_formula(&self) -> String {
+        let mut formula = to_formula(&self.composition);
+        if self.charge != 0 {
+            formula.push(if self.charge > 0 { '+' } else { '-' });
+            if self.charge.abs() != 1 {
+                formula.push_str(&self.charge.abs().to_string());
+            }
+        }
+        formula
+    }
+}
+
+/**
+Split a charge suffix from a formula, accepting `+`, `++`, `+2` and their negative
+counterparts directly after the formula, and additionally `2+` after a `^` or a formula
+enclosed in `[]`. Brackets not followed by a charge are left to the formula parser as a group.
+*/
+fn split_charge(string: &str) -> Result<(Range<usize>, i32), FormulaParserError> {
+    let invalid = |suffix: Range<usize>| {
+        FormulaParserError::new(FormulaParserErrorKind::InvalidCharge, string, suffix)
+    };
+    if let Some(caret) = string.find('^') {
+        let suffix = caret + 1..string.len();
+        let charge = parse_charge(&string[suffix.clone()], true).ok_or_else(|| invalid(suffix))?;
+        let body = match closing_bracket(string) {
+            Some(end) if end + 1 == caret => 1..end,
+            _ => 0..caret,
+        };
+        return Ok((body, charge));
+    }
+    if let Some(end) = closing_bracket(string) {
+        let suffix = &string[end + 1..];
+        if suffix.contains(['+', '-'])
+            && suffix.chars().all(|c| c.is_ascii_digit() || c == '+' || c == '-')
+        {
+            let charge = parse_charge(suffix, true).ok_or_else(|| invalid(end + 1..string.len()))?;
+            return Ok((1..end, charge));
+        }
+    }
+    match string.find(['+', '-']) {
+        Some(i) => {
+            let charge = parse_charge(&string[i..], false).ok_or_else(|| invalid(i..string.len()))?;
+            Ok((0..i, charge))
+        }
+        None => Ok((0..string.len(), 0)),
+    }
+}
+
+/**
+Locate the charge suffix of a formula which would parse as a neutral formula without it,
+so [`ChemicalComposition::parse`] can reject the charge rather than report the suffix as
+unexpected characters.
+*/
+pub(crate) fn charge_suffix(string: &str, periodic_table: &PeriodicTable) -> Option<Range<usize>> {
+    let (body, charge) = split_charge(string).ok()?;
+    if charge == 0 {
+        return None;
+    }
+    parse_formula_with_table(&string[body.clone()], periodic_table).ok()?;
+    let end = if string[body.end..].starts_with(']') {
+        body.end + 1
+    } else {
+        body.end
+    };
+    Some(end..string.len())
+}
+
+/// Find the `]` closing a `[` which opens `string`, so `[A][B]2-` is not mistaken for one group
+fn closing_bracket(string: &str) -> Option<usize> {
+    if !string.starts_with('[') {
+        return None;
+    }
+    let mut depth = 0;
+    for (i, c) in string.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn parse_charge(suffix: &str, allow_leading_magnitude: bool) -> Option<i32> {
+    let sign_of = |c: char| match c {
+        '+' => Some(1),
+        '-' => Some(-1),
+        _ => None,
+    };
+    let first = suffix.chars().next()?;
+    let (sign, magnitude) = if let Some(sign) = sign_of(first) {
+        let rest = &suffix[1..];
+        if rest.is_empty() {
+            (sign, 1)
+        } else if rest.chars().all(|c| sign_of(c) == Some(sign)) {
+            (sign, rest.len() as i32 + 1)
+        } else {
+            (sign, rest.parse::<i32>().ok()?)
+        }
+    } else if allow_leading_magnitude {
+        let last = suffix.chars().last().unwrap();
+        let sign = sign_of(last)?;
+        let magnitude = &suffix[..suffix.len() - 1];
+        (sign, magnitude.parse::<i32>().ok()?)
+    } else {
+        return None;
+    };
+    if magnitude <= 0 {
+        return None;
+    }
+    Some(sign * magnitude)
+}
+
+impl<'lifespan> Deref for ChargedComposition<'lifespan> {
+    type Target = ChemicalComposition<'lifespan>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.composition
+    }
+}
+
+impl<'lifespan> DerefMut for ChargedComposition<'lifespan> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.composition
+    }
+}
+
+impl<'lifespan> From<ChemicalComposition<'lifespan>> for ChargedComposition<'lifespan> {
+    fn from(value: ChemicalComposition<'lifespan>) -> Self {
+        Self::new(value, 0)
+    }
+}
+
+impl<'a> FromStr for ChargedComposition<'a> {
+    type Err = FormulaParserError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ChargedComposition::parse(s)
+    }
+}
+
+impl<'lifespan> fmt::Display for ChargedComposition<'lifespan> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_formula())
+    }
+}
+
+impl<'lifespan> ChemicalComposition<'lifespan> {
+    /// Parse a formula with an optional charge suffix into a [`ChargedComposition`] using
+    /// the global [`PeriodicTable`]
+    pub fn parse_charged(
+        string: &str,
+    ) -> Result<ChargedComposition<'lifespan>, FormulaParserError> {
+        ChargedComposition::parse(string)
+    }
+
+    /// Parse a formula with an optional charge suffix into a [`ChargedComposition`] using
+    /// the specified [`PeriodicTable`]
+    pub fn parse_charged_with(
+        string: &str,
+        periodic_table: &'lifespan PeriodicTable,
+    ) -> Result<ChargedComposition<'lifespan>, FormulaParserError> {
+        ChargedComposition::parse_with(string, periodic_table)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_charge() {
+        for (formula, charge) in [
+            ("C6H13O6+", 1),
+            ("SO4-2", -2),
+            ("SO4--", -2),
+            ("NH4^+", 1),
+            ("SO4^2-", -2),
+            ("[Fe(CN)6]4-", -4),
+            ("[Fe(CN)6]", 0),
+            ("K4[Fe(CN)6]", 0),
+            ("[Fe(CN)6]2", 0),
+            ("[C[13]H3]+", 1),
+            ("[Cu(NH3)4][PtCl4]^2-", -2),
+            ("C6H12O6", 0),
+        ] {
+            let ion = ChargedComposition::parse(formula).unwrap();
+            assert_eq!(ion.charge, charge, "{formula}");
+            assert_eq!(ChargedComposition::parse(&ion.to_string()).unwrap(), ion);
+        }
+        assert_eq!(ChargedComposition::parse("SO4-2").unwrap()["O"], 4);
+        assert_eq!(ChargedComposition::parse("[Fe(CN)6]4-").unwrap()["N"], 6);
+
+        // Only the group enclosing the whole formula takes a leading magnitude, so the `2` here
+        // counts the second group
+        let salt = ChargedComposition::parse("[Cu(NH3)4][PtCl4]2-").unwrap();
+        assert_eq!(salt.charge, -1);
+        assert_eq!(salt["Pt"], 2);
+
+        for formula in ["SO4+-", "SO4^", "[Fe(CN)6-", "NH4+0"] {
+            assert!(ChargedComposition::parse(formula).is_err(), "{formula}");
+        }
+        let err = ChargedComposition::parse(" [Fe(CN)6]4+-").unwrap_err();
+        assert_eq!(err.kind, FormulaParserErrorKind::InvalidCharge);
+        assert_eq!(err.token(), "4+-");
+        for (formula, suffix) in [("C6H13O6+", "+"), ("[Fe(CN)6]4-", "4-"), ("NH4^+", "^+")] {
+            let err = ChemicalComposition::parse(formula).unwrap_err();
+            assert_eq!(err.kind, FormulaParserErrorKind::InvalidCharge, "{formula}");
+            assert_eq!(err.token(), suffix, "{formula}");
+        }
+        let err = ChargedComposition::parse("[Fe(CX)6]4-").unwrap_err();
+        assert_eq!(err.kind, FormulaParserErrorKind::UnknownElement);
+        assert_eq!(err.span, 5..6);
+    }
+
+    #[test]
+    fn test_isotopic_variants() {
+        let ion = ChemicalComposition::parse_charged("C6H13O6+").unwrap();
+        let glucose = ChemicalComposition::parse("C6H12O6").unwrap();
+        let expected = crate::mass_charge_ratio(glucose.mass(), 1, crate::PROTON);
+        assert!((ion.mz() - expected).abs() < 1e-6);
+
+        let peaks = ion.isotopic_variants(3);
+        assert_eq!(peaks[0].charge, 1);
+        assert!((peaks[0].mz - expected).abs() < 1e-5);
+
+        let sulfate = ChargedComposition::parse("SO4^2-").unwrap();
+        assert!((sulfate.mz() - (sulfate.composition.mass() + 2.0 * ELECTRON) / 2.0).abs() < 1e-9);
+    }
+}
@@ -21,6 +21,9 @@ and multiplication by integers.
 pub struct ChemicalCompositionVec<'a> {
     pub composition: Vec<(ElementSpecification<'a>, i32)>,
     mass_cache: Option<f64>,
+    average_mass_cache: Option<f64>,
+    molar_mass_cache: Option<f64>,
+    nominal_mass_cache: Option<i64>,
 }
 
 /**
@@ -74,7 +77,7 @@ impl<'transient, 'lifespan: 'transient> ChemicalCompositionVec<'lifespan> {
         } else {
             self.composition.push((elt_spec, count));
         }
-        self.mass_cache = None;
+        self.clear_caches();
     }
 
     #[inline]
@@ -171,6 +174,171 @@ impl<'transient, 'lifespan: 'transient> ChemicalCompositionVec<'lifespan> {
     pub fn has_mass_cached(&self) -> bool {
         self.mass_cache.is_some()
     }
+
+    #[inline]
+    /**
+    Calculate the monoisotopic mass of the chemical composition from the mass of each
+    element's lightest isotope. This differs from [`Self::mass`], which uses the most
+    abundant isotope, for elements like Fe, Se or Sn.
+    */
+    pub fn monoisotopic_mass(&self) -> f64 {
+        let mut total = 0.0;
+        for (elt_spec, count) in &self.composition {
+            total = elt_spec.monoisotopic_mass().mul_add(*count as f64, total);
+        }
+        total
+    }
+
+    #[inline]
+    /// Get the mass of the chemical composition from each element's most abundant isotope,
+    /// an alias for [`Self::mass`]
+    pub fn most_abundant_mass(&self) -> f64 {
+        self.mass()
+    }
+
+    #[inline]
+    /**
+    Explicitly calculate the average mass of the chemical composition from the
+    abundance-weighted masses of each element's isotopes, ignoring any caching.
+    */
+    pub fn calc_average_mass(&self) -> f64 {
+        let mut total = 0.0;
+        for (elt_spec, count) in &self.composition {
+            total = elt_spec.average_mass().mul_add(*count as f64, total);
+        }
+        total
+    }
+
+    #[inline]
+    /**
+    Get the average mass of this chemical composition. If the average mass cache
+    has been populated, return that instead of repeating the calculation.
+    */
+    pub fn average_mass(&self) -> f64 {
+        match self.average_mass_cache {
+            None => self.calc_average_mass(),
+            Some(val) => val,
+        }
+    }
+
+    #[inline]
+    /**
+    Get the average mass of this chemical composition, and cache it,
+    or reuse the cached value.
+    */
+    pub fn faverage_mass(&mut self) -> f64 {
+        match self.average_mass_cache {
+            None => {
+                let total = self.calc_average_mass();
+                self.average_mass_cache = Some(total);
+                total
+            }
+            Some(val) => val,
+        }
+    }
+
+    #[inline]
+    /**
+    Explicitly calculate the molar mass of the chemical composition from each element's
+    IUPAC standard atomic weight, falling back to the abundance-weighted average
+    mass when no standard atomic weight is known, ignoring any caching.
+    */
+    pub fn calc_molar_mass(&self) -> f64 {
+        let mut total = 0.0;
+        for (elt_spec, count) in &self.composition {
+            total = elt_spec.molar_mass().mul_add(*count as f64, total);
+        }
+        total
+    }
+
+    #[inline]
+    /**
+    Get the molar mass of this chemical composition. If the molar mass cache
+    has been populated, return that instead of repeating the calculation.
+    */
+    pub fn molar_mass(&self) -> f64 {
+        match self.molar_mass_cache {
+            None => self.calc_molar_mass(),
+            Some(val) => val,
+        }
+    }
+
+    #[inline]
+    /**
+    Get the molar mass of this chemical composition, and cache it,
+    or reuse the cached value.
+    */
+    pub fn fmolar_mass(&mut self) -> f64 {
+        match self.molar_mass_cache {
+            None => {
+                let total = self.calc_molar_mass();
+                self.molar_mass_cache = Some(total);
+                total
+            }
+            Some(val) => val,
+        }
+    }
+
+    #[inline]
+    /**
+    Explicitly calculate the nominal mass of the chemical composition, the sum of
+    the mass numbers of each element's most abundant isotope, ignoring any caching.
+    */
+    pub fn calc_nominal_mass(&self) -> i64 {
+        self.composition
+            .iter()
+            .map(|(elt_spec, count)| elt_spec.nominal_mass() as i64 * *count as i64)
+            .sum()
+    }
+
+    #[inline]
+    /**
+    Get the nominal mass of this chemical composition. If the nominal mass cache
+    has been populated, return that instead of repeating the calculation.
+    */
+    pub fn nominal_mass(&self) -> i64 {
+        match self.nominal_mass_cache {
+            None => self.calc_nominal_mass(),
+            Some(val) => val,
+        }
+    }
+
+    #[inline]
+    /**
+    Get the nominal mass of this chemical composition, and cache it,
+    or reuse the cached value.
+    */
+    pub fn fnominal_mass(&mut self) -> i64 {
+        match self.nominal_mass_cache {
+            None => {
+                let total = self.calc_nominal_mass();
+                self.nominal_mass_cache = Some(total);
+                total
+            }
+            Some(val) => val,
+        }
+    }
+
+    #[inline]
+    /// Get the mass defect, the difference between the mass and the nominal mass
+    pub fn mass_defect(&self) -> f64 {
+        self.mass() - self.nominal_mass() as f64
+    }
+
+    #[inline]
+    /// Get the mass defect, populating the mass and nominal mass caches along the way
+    pub fn fmass_defect(&mut self) -> f64 {
+        self.fmass() - self.fnominal_mass() as f64
+    }
+
+    #[inline]
+    /// Invalidate all of the cached masses
+    fn clear_caches(&mut self) {
+        self.mass_cache = None;
+        self.average_mass_cache = None;
+        self.molar_mass_cache = None;
+        self.nominal_mass_cache = None;
+    }
 }
 
 const ZERO: i32 = 0;
@@ -192,7 +360,7 @@ impl<'lifespan> Index<&ElementSpecification<'lifespan>> for ChemicalCompositionV
 impl<'lifespan> IndexMut<&ElementSpecification<'lifespan>> for ChemicalCompositionVec<'lifespan> {
     #[inline]
     fn index_mut(&mut self, key: &ElementSpecification<'lifespan>) -> &mut Self::Output {
-        self.mass_cache = None;
+        self.clear_caches();
         if let Some(i) = self.find(key) {
             let (_, out) = self.composition.get_mut(i).unwrap();
             out
@@ -236,7 +404,7 @@ impl<'lifespan> IndexMut<&str> for ChemicalCompositionVec<'lifespan> {
     */
     #[inline]
     fn index_mut(&mut self, key: &str) -> &mut Self::Output {
-        self.mass_cache = None;
+        self.clear_caches();
         let key = key.parse::<ElementSpecification>().unwrap();
         let entry = self.index_mut(&key);
         entry
@@ -260,6 +428,7 @@ impl<'lifespan, 'transient, 'outer: 'transient> ChemicalCompositionVec<'lifespan
 
     #[inline]
     pub(crate) fn _mul_by(&mut self, scaler: i32) {
+        self.clear_caches();
         self.composition.iter_mut().for_each(|(_, v)| {
             *v *= scaler;
         })
@@ -336,6 +505,9 @@ impl<'lifespan> From<Vec<(ElementSpecification<'lifespan>, i32)>>
         let composition = ChemicalCompositionVec {
             composition: elements,
             mass_cache: None,
+            average_mass_cache: None,
+            molar_mass_cache: None,
+            nominal_mass_cache: None,
         };
         return composition;
     }
@@ -345,8 +517,7 @@ impl<'a> FromStr for ChemicalCompositionVec<'a> {
     type Err = FormulaParserError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut parser = FormulaParser::default();
-        parser.parse_formula_with_table_generic(s, &PERIODIC_TABLE)
+        FormulaParser::parse_formula_with_table_generic(s, &PERIODIC_TABLE)
     }
 }
 
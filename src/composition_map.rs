@@ -23,6 +23,9 @@ and multiplication by integers.
 pub struct ChemicalCompositionMap<'a> {
     pub composition: HashMap<ElementSpecification<'a>, i32, FnvBuildHasher>,
     mass_cache: Option<f64>,
+    average_mass_cache: Option<f64>,
+    molar_mass_cache: Option<f64>,
+    nominal_mass_cache: Option<i64>,
 }
 
 /**
@@ -50,7 +53,7 @@ impl<'transient, 'lifespan: 'transient> ChemicalCompositionMap<'lifespan> {
     /// Set the count for a specific element. This will invalidate the mass cache.
     pub fn set(&mut self, elt_spec: ElementSpecification<'lifespan>, count: i32) {
         self.composition.insert(elt_spec, count);
-        self.mass_cache = None;
+        self.clear_caches();
     }
 
     #[inline]
@@ -139,6 +142,171 @@ impl<'transient, 'lifespan: 'transient> ChemicalCompositionMap<'lifespan> {
     pub fn has_mass_cached(&self) -> bool {
         self.mass_cache.is_some()
     }
+
+    #[inline]
+    /**
+    Calculate the monoisotopic mass of the chemical composition from the mass of each
+    element's lightest isotope. This differs from [`Self::mass`], which uses the most
+    abundant isotope, for elements like Fe, Se or Sn.
+    */
+    pub fn monoisotopic_mass(&self) -> f64 {
+        let mut total = 0.0;
+        for (elt_spec, count) in &self.composition {
+            total = elt_spec.monoisotopic_mass().mul_add(*count as f64, total);
+        }
+        total
+    }
+
+    #[inline]
+    /// Get the mass of the chemical composition from each element's most abundant isotope,
+    /// an alias for [`Self::mass`]
+    pub fn most_abundant_mass(&self) -> f64 {
+        self.mass()
+    }
+
+    #[inline]
+    /**
+    Explicitly calculate the average mass of the chemical composition from the
+    abundance-weighted masses of each element's isotopes, ignoring any caching.
+    */
+    pub fn calc_average_mass(&self) -> f64 {
+        let mut total = 0.0;
+        for (elt_spec, count) in &self.composition {
+            total = elt_spec.average_mass().mul_add(*count as f64, total);
+        }
+        total
+    }
+
+    #[inline]
+    /**
+    Get the average mass of this chemical composition. If the average mass cache
+    has been populated, return that instead of repeating the calculation.
+    */
+    pub fn average_mass(&self) -> f64 {
+        match self.average_mass_cache {
+            None => self.calc_average_mass(),
+            Some(val) => val,
+        }
+    }
+
+    #[inline]
+    /**
+    Get the average mass of this chemical composition, and cache it,
+    or reuse the cached value.
+    */
+    pub fn faverage_mass(&mut self) -> f64 {
+        match self.average_mass_cache {
+            None => {
+                let total = self.calc_average_mass();
+                self.average_mass_cache = Some(total);
+                total
+            }
+            Some(val) => val,
+        }
+    }
+
+    #[inline]
+    /**
+    Explicitly calculate the molar mass of the chemical composition from each element's
+    IUPAC standard atomic weight, falling back to the abundance-weighted average
+    mass when no standard atomic weight is known, ignoring any caching.
+    */
+    pub fn calc_molar_mass(&self) -> f64 {
+        let mut total = 0.0;
+        for (elt_spec, count) in &self.composition {
+            total = elt_spec.molar_mass().mul_add(*count as f64, total);
+        }
+        total
+    }
+
+    #[inline]
+    /**
+    Get the molar mass of this chemical composition. If the molar mass cache
+    has been populated, return that instead of repeating the calculation.
+    */
+    pub fn molar_mass(&self) -> f64 {
+        match self.molar_mass_cache {
+            None => self.calc_molar_mass(),
+            Some(val) => val,
+        }
+    }
+
+    #[inline]
+    /**
+    Get the molar mass of this chemical composition, and cache it,
+    or reuse the cached value.
+    */
+    pub fn fmolar_mass(&mut self) -> f64 {
+        match self.molar_mass_cache {
+            None => {
+                let total = self.calc_molar_mass();
+                self.molar_mass_cache = Some(total);
+                total
+            }
+            Some(val) => val,
+        }
+    }
+
+    #[inline]
+    /**
+    Explicitly calculate the nominal mass of the chemical composition, the sum of
+    the mass numbers of each element's most abundant isotope, ignoring any caching.
+    */
+    pub fn calc_nominal_mass(&self) -> i64 {
+        self.composition
+            .iter()
+            .map(|(elt_spec, count)| elt_spec.nominal_mass() as i64 * *count as i64)
+            .sum()
+    }
+
+    #[inline]
+    /**
+    Get the nominal mass of this chemical composition. If the nominal mass cache
+    has been populated, return that instead of repeating the calculation.
+    */
+    pub fn nominal_mass(&self) -> i64 {
+        match self.nominal_mass_cache {
+            None => self.calc_nominal_mass(),
+            Some(val) => val,
+        }
+    }
+
+    #[inline]
+    /**
+    Get the nominal mass of this chemical composition, and cache it,
+    or reuse the cached value.
+    */
+    pub fn fnominal_mass(&mut self) -> i64 {
+        match self.nominal_mass_cache {
+            None => {
+                let total = self.calc_nominal_mass();
+                self.nominal_mass_cache = Some(total);
+                total
+            }
+            Some(val) => val,
+        }
+    }
+
+    #[inline]
+    /// Get the mass defect, the difference between the mass and the nominal mass
+    pub fn mass_defect(&self) -> f64 {
+        self.mass() - self.nominal_mass() as f64
+    }
+
+    #[inline]
+    /// Get the mass defect, populating the mass and nominal mass caches along the way
+    pub fn fmass_defect(&mut self) -> f64 {
+        self.fmass() - self.fnominal_mass() as f64
+    }
+
+    #[inline]
+    /// Invalidate all of the cached masses
+    fn clear_caches(&mut self) {
+        self.mass_cache = None;
+        self.average_mass_cache = None;
+        self.molar_mass_cache = None;
+        self.nominal_mass_cache = None;
+    }
 }
 
 /**
@@ -160,6 +328,7 @@ impl<'lifespan, 'transient, 'outer: 'transient> ChemicalCompositionMap<'lifespan
 
     #[inline]
     pub(crate) fn _mul_by(&mut self, scaler: i32) {
+        self.clear_caches();
         self.iter_mut().for_each(|(_, v)| {
             *v *= scaler
         });
@@ -189,7 +358,7 @@ impl<'lifespan> Index<&ElementSpecification<'lifespan>> for ChemicalCompositionM
 impl<'lifespan> IndexMut<&ElementSpecification<'lifespan>> for ChemicalCompositionMap<'lifespan> {
     #[inline]
     fn index_mut(&mut self, key: &ElementSpecification<'lifespan>) -> &mut Self::Output {
-        self.mass_cache = None;
+        self.clear_caches();
         let entry = self.composition.entry(key.clone());
         entry.or_insert(0)
     }
@@ -233,7 +402,7 @@ impl<'a> ChemicalCompositionMap<'a> {
     to element counts.
     */
     pub fn get_str_mut(&mut self, elt: &str) -> Option<&mut i32> {
-        self.mass_cache = None;
+        self.clear_caches();
         self.composition.get_mut(elt)
     }
 
@@ -250,7 +419,7 @@ impl<'a> ChemicalCompositionMap<'a> {
     /// If a new [`ElementSpecification`] needs to be created and fails,
     /// this method will panic.
     pub fn inc_str(&mut self, elt: &str, count: i32) {
-        self.mass_cache = None;
+        self.clear_caches();
         if let Some(val) = self.get_str_mut(elt) {
             *val += count;
         } else {
@@ -297,7 +466,7 @@ impl<'lifespan> IndexMut<&str> for ChemicalCompositionMap<'lifespan> {
     */
     #[inline]
     fn index_mut(&mut self, key: &str) -> &mut Self::Output {
-        self.mass_cache = None;
+        self.clear_caches();
         let key = key.parse::<ElementSpecification>().unwrap();
         let entry = self.composition.entry(key);
         entry.or_insert(0)
@@ -431,6 +600,24 @@ mod test {
         assert!((mass - calc).abs() < 1e-6);
     }
 
+    #[test]
+    fn test_average_and_nominal_mass() {
+        let mut case = ChemicalCompositionMap::from(vec![("O", 1), ("H", 2)]);
+        assert!((case.average_mass() - 18.01528).abs() < 1e-3);
+        assert!((case.molar_mass() - 18.01528).abs() < 1e-3);
+        assert_eq!(case.nominal_mass(), 18);
+        assert!((case.mass_defect() - 0.0105646837).abs() < 1e-6);
+
+        let average = case.faverage_mass();
+        let molar = case.fmolar_mass();
+        assert_eq!(case.molar_mass(), molar);
+        assert_eq!(case.fnominal_mass(), 18);
+        case.inc_str("O", 1);
+        assert_eq!(case.nominal_mass(), 34);
+        assert!(case.average_mass() > average + 15.9);
+        assert!(case.molar_mass() > molar + 15.9);
+    }
+
     #[test]
     fn test_add() {
         let case = ChemicalCompositionMap::from(vec![("O", 1), ("H", 2)]);
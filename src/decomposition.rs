@@ -0,0 +1,549 @@
+//! Enumerate every elemental composition whose mass falls within a tolerance window
+//! of a query mass, subject to per-element count bounds.
+//!
+//! The search uses the round-robin construction of an extended residue table over
+//! integer-scaled element masses and the accompanying backtracking enumeration described
+//! in [Böcker & Lipták, 2007](https://doi.org/10.1007/s00453-007-0162-8) and
+//! [Böcker et al., 2008](https://doi.org/10.1093/bioinformatics/btn603). Rounding errors
+//! introduced by the integer scaling are bounded so that every candidate can be verified
+//! against the real masses, meaning the precision only governs speed, not correctness.
+//!
+//! ```rust
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use chemical_elements::ChemicalComposition;
+//! use chemical_elements::decomposition::{MassDecomposer, Tolerance, parse_element_bounds};
+//!
+//! let bounds = parse_element_bounds("C0-100 H0-200 N0-10 O0-20 S0-3")?;
+//! let decomposer = MassDecomposer::new(bounds)?;
+//! let hits = decomposer.decompose(180.06339, Tolerance::PPM(5.0));
+//! assert!(hits.contains(&ChemicalComposition::parse("C6H12O6")?));
+//! # Ok(())
+//! # }
+//! ```
+use std::fmt::{self, Display};
+
+use crate::table::PERIODIC_TABLE;
+use crate::{ChemicalComposition, ElementSpecification, PeriodicTable};
+
+/// The default width of an integer mass unit, in Daltons
+pub const DEFAULT_PRECISION: f64 = 1e-4;
+
+const UNREACHABLE: u64 = u64::MAX;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// A mass error tolerance, either relative in parts-per-million or absolute in Daltons
+pub enum Tolerance {
+    PPM(f64),
+    Da(f64),
+}
+
+impl Tolerance {
+    /// Compute the lower and upper mass bounds accepted around `mass`
+    pub fn bounds(&self, mass: f64) -> (f64, f64) {
+        let width = match self {
+            Tolerance::PPM(ppm) => mass * ppm / 1e6,
+            Tolerance::Da(da) => *da,
+        };
+        (mass - width, mass + width)
+    }
+
+    /// Test whether `observed` is within tolerance of `reference`
+    pub fn test(&self, reference: f64, observed: f64) -> bool {
+        let (lo, hi) = self.bounds(reference);
+        observed >= lo && observed <= hi
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecompositionError {
+    /// A bound specification could not be parsed
+    MalformedBound(String),
+    /// The element in a bound specification is not in the [`PeriodicTable`]
+    UnknownElement(String),
+    /// A bound was negative or had a minimum greater than its maximum
+    InvalidRange(String),
+    /// The same element was bounded more than once
+    DuplicateElement(String),
+    /// No elements were provided to decompose over
+    EmptyAlphabet,
+    /// The precision was not a positive, finite number
+    InvalidPrecision(f64),
+}
+
+impl Display for DecompositionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MalformedBound(bound) => {
+                write!(f, "malformed bound `{bound}`, expected an element and `min-max`")
+            }
+            Self::UnknownElement(symbol) => write!(f, "unknown element `{symbol}`"),
+            Self::InvalidRange(bound) => write!(
+                f,
+                "invalid range in `{bound}`, bounds must be non-negative and ordered"
+            ),
+            Self::DuplicateElement(element) => {
+                write!(f, "element `{element}` is bounded more than once")
+            }
+            Self::EmptyAlphabet => f.write_str("no elements were given to decompose over"),
+            Self::InvalidPrecision(precision) => {
+                write!(f, "precision {precision} is not a positive, finite number")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecompositionError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// The inclusive range of counts permitted for a single element in a decomposition
+pub struct ElementBound<'lifespan> {
+    pub element: ElementSpecification<'lifespan>,
+    pub min: i32,
+    pub max: i32,
+}
+
+impl<'lifespan> ElementBound<'lifespan> {
+    pub fn new(element: ElementSpecification<'lifespan>, min: i32, max: i32) -> Self {
+        Self { element, min, max }
+    }
+
+    /// The mass of a single instance of this element, consistent with
+    /// [`ChemicalComposition::mass`]
+    pub fn mass(&self) -> f64 {
+        if self.element.isotope == 0 {
+            self.element.element.most_abundant_mass
+        } else {
+            self.element.element.isotopes[&self.element.isotope].mass
+        }
+    }
+
+    /**
+    Parse a single bound like `C0-100`, `N10` (meaning `N0-10`) or `C[13]0-6`
+    using the specified [`PeriodicTable`]
+    */
+    pub fn parse_with(
+        text: &str,
+        periodic_table: &'lifespan PeriodicTable,
+    ) -> Result<ElementBound<'lifespan>, DecompositionError> {
+        let mut depth = 0;
+        let mut split = None;
+        for (i, c) in text.char_indices() {
+            match c {
+                '[' => depth += 1,
+                ']' => depth -= 1,
+                c if c.is_ascii_digit() && depth == 0 => {
+                    split = Some(i);
+                    break;
+                }
+                _ => {}
+            }
+        }
+        let split = split.ok_or_else(|| DecompositionError::MalformedBound(text.to_string()))?;
+        let (symbol, range) = text.split_at(split);
+        let element = ElementSpecification::parse_with(symbol, periodic_table)
+            .map_err(|_| DecompositionError::UnknownElement(symbol.to_string()))?;
+        let parse_count = |s: &str| {
+            s.parse::<i32>()
+                .map_err(|_| DecompositionError::MalformedBound(text.to_string()))
+        };
+        let (min, max) = match range.split_once('-') {
+            Some((lo, hi)) => (parse_count(lo)?, parse_count(hi)?),
+            None => (0, parse_count(range)?),
+        };
+        if min < 0 || max < min {
+            return Err(DecompositionError::InvalidRange(text.to_string()));
+        }
+        Ok(ElementBound::new(element, min, max))
+    }
+}
+
+/**
+Parse a whitespace or comma separated list of element bounds like
+`C0-100 H0-200 N0-10 O0-20 S0-3` using the global [`PeriodicTable`].
+An element may only appear once.
+*/
+pub fn parse_element_bounds(
+    text: &str,
+) -> Result<Vec<ElementBound<'static>>, DecompositionError> {
+    parse_element_bounds_with(text, &PERIODIC_TABLE)
+}
+
+/// As [`parse_element_bounds`], but using the specified [`PeriodicTable`]
+pub fn parse_element_bounds_with<'lifespan>(
+    text: &str,
+    periodic_table: &'lifespan PeriodicTable,
+) -> Result<Vec<ElementBound<'lifespan>>, DecompositionError> {
+    let bounds = text
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|tok| !tok.is_empty())
+        .map(|tok| ElementBound::parse_with(tok, periodic_table))
+        .collect::<Result<Vec<_>, _>>()?;
+    check_duplicates(&bounds)?;
+    Ok(bounds)
+}
+
+fn check_duplicates(bounds: &[ElementBound]) -> Result<(), DecompositionError> {
+    for (i, bound) in bounds.iter().enumerate() {
+        if bounds[..i].iter().any(|b| b.element == bound.element) {
+            return Err(DecompositionError::DuplicateElement(bound.element.to_string()));
+        }
+    }
+    Ok(())
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        let t = a % b;
+        a = b;
+        b = t;
+    }
+    a
+}
+
+#[derive(Debug, Clone)]
+/**
+Decomposes masses into elemental compositions over a fixed alphabet of
+[`ElementBound`]s.
+
+Building the extended residue table is proportional to the number of elements times
+the integer mass of the lightest element, so a [`MassDecomposer`] should be constructed
+once and re-used across many queries.
+*/
+pub struct MassDecomposer<'lifespan> {
+    bounds: Vec<ElementBound<'lifespan>>,
+    masses: Vec<f64>,
+    integer_masses: Vec<u64>,
+    precision: f64,
+    min_relative_error: f64,
+    max_relative_error: f64,
+    /// The minimum mass of the composition built from each element's lower bound
+    base_mass: f64,
+    /// The extended residue table, stored row-major by residue modulo the lightest integer mass
+    residue_table: Vec<u64>,
+}
+
+impl<'lifespan> MassDecomposer<'lifespan> {
+    /// Create a new [`MassDecomposer`] over `bounds` using [`DEFAULT_PRECISION`]
+    pub fn new(bounds: Vec<ElementBound<'lifespan>>) -> Result<Self, DecompositionError> {
+        Self::with_precision(bounds, DEFAULT_PRECISION)
+    }
+
+    /**
+    Create a new [`MassDecomposer`] over `bounds`, scaling masses to integers with
+    units of `precision` Daltons. Smaller values produce a larger table but fewer
+    false candidates to verify.
+
+    Each element may only be bounded once, otherwise the same composition would be
+    enumerated several times, so repeated elements are a [`DecompositionError::DuplicateElement`].
+    */
+    pub fn with_precision(
+        mut bounds: Vec<ElementBound<'lifespan>>,
+        precision: f64,
+    ) -> Result<Self, DecompositionError> {
+        if bounds.is_empty() {
+            return Err(DecompositionError::EmptyAlphabet);
+        }
+        if !(precision.is_finite() && precision > 0.0) {
+            return Err(DecompositionError::InvalidPrecision(precision));
+        }
+        check_duplicates(&bounds)?;
+        bounds.sort_by(|a, b| a.mass().total_cmp(&b.mass()));
+
+        let masses: Vec<f64> = bounds.iter().map(|b| b.mass()).collect();
+        let integer_masses: Vec<u64> = masses
+            .iter()
+            .map(|m| ((m / precision).round() as u64).max(1))
+            .collect();
+
+        let (min_relative_error, max_relative_error) = masses.iter().zip(integer_masses.iter()).fold(
+            (0.0f64, 0.0f64),
+            |(lo, hi), (m, a)| {
+                let err = (*a as f64 * precision - m) / m;
+                (lo.min(err), hi.max(err))
+            },
+        );
+
+        let base_mass = bounds
+            .iter()
+            .zip(masses.iter())
+            .map(|(b, m)| b.min as f64 * m)
+            .sum();
+
+        let mut inst = Self {
+            bounds,
+            masses,
+            integer_masses,
+            precision,
+            min_relative_error,
+            max_relative_error,
+            base_mass,
+            residue_table: Vec::new(),
+        };
+        inst.build_residue_table();
+        Ok(inst)
+    }
+
+    /// The element bounds, sorted by ascending mass
+    pub fn bounds(&self) -> &[ElementBound<'lifespan>] {
+        &self.bounds
+    }
+
+    pub fn precision(&self) -> f64 {
+        self.precision
+    }
+
+    #[inline]
+    fn table_at(&self, residue: u64, column: usize) -> u64 {
+        self.residue_table[residue as usize * self.bounds.len() + column]
+    }
+
+    /// Fill the extended residue table using the round-robin algorithm
+    fn build_residue_table(&mut self) {
+        let k = self.bounds.len();
+        let a0 = self.integer_masses[0];
+        let mut table = vec![UNREACHABLE; a0 as usize * k];
+        table[0] = 0;
+
+        for i in 1..k {
+            for r in 0..a0 as usize {
+                table[r * k + i] = table[r * k + i - 1];
+            }
+            let ai = self.integer_masses[i];
+            let d = gcd(a0, ai);
+            for p in 0..d {
+                let mut n = (p..a0)
+                    .step_by(d as usize)
+                    .map(|q| table[q as usize * k + i])
+                    .min()
+                    .unwrap_or(UNREACHABLE);
+                if n == UNREACHABLE {
+                    continue;
+                }
+                for _ in 0..(a0 / d) {
+                    n += ai;
+                    let r = (n % a0) as usize;
+                    n = n.min(table[r * k + i]);
+                    table[r * k + i] = n;
+                }
+            }
+        }
+        self.residue_table = table;
+    }
+
+    /// Recursively enumerate the element counts whose integer masses sum to `mass`
+    fn find_all(&self, mass: u64, i: usize, counts: &mut [i32], acc: &mut Vec<Vec<i32>>) {
+        let a0 = self.integer_masses[0];
+        if i == 0 {
+            // `u64::is_multiple_of` is too recent for the versions of Rust this crate supports
+            #[allow(clippy::manual_is_multiple_of)]
+            if mass % a0 != 0 {
+                return;
+            }
+            let c = mass / a0;
+            if c <= (self.bounds[0].max - self.bounds[0].min) as u64 {
+                counts[0] = c as i32;
+                acc.push(counts.to_vec());
+            }
+            return;
+        }
+        let ai = self.integer_masses[i];
+        let limit = (self.bounds[i].max - self.bounds[i].min) as i64;
+        let lcm = a0 / gcd(a0, ai) * ai;
+        let step = (lcm / ai) as i64;
+        for j in 0..step {
+            if j > limit || (j as u64) * ai > mass {
+                break;
+            }
+            counts[i] = j as i32;
+            let mut remainder = mass - j as u64 * ai;
+            let lower = self.table_at(remainder % a0, i - 1);
+            while remainder >= lower && (counts[i] as i64) <= limit {
+                self.find_all(remainder, i - 1, counts, acc);
+                if remainder < lcm {
+                    break;
+                }
+                remainder -= lcm;
+                counts[i] += step as i32;
+            }
+        }
+        counts[i] = 0;
+    }
+
+    fn build_composition(&self, counts: &[i32]) -> ChemicalComposition<'lifespan> {
+        let mut composition = ChemicalComposition::new();
+        for (bound, count) in self.bounds.iter().zip(counts.iter()) {
+            let total = bound.min + count;
+            if total != 0 {
+                composition.inc(bound.element, total);
+            }
+        }
+        composition
+    }
+
+    /**
+    Find all compositions within the element bounds whose mass is within `tolerance`
+    of `mass`, sorted by ascending mass.
+    */
+    pub fn decompose(&self, mass: f64, tolerance: Tolerance) -> Vec<ChemicalComposition<'lifespan>> {
+        let (lo, hi) = tolerance.bounds(mass);
+        self.decompose_range(lo, hi)
+    }
+
+    /// Find all compositions within the element bounds whose mass is in `[lo, hi]`
+    pub fn decompose_range(&self, lo: f64, hi: f64) -> Vec<ChemicalComposition<'lifespan>> {
+        let residual_lo = (lo - self.base_mass).max(0.0);
+        let residual_hi = hi - self.base_mass;
+        if residual_hi < 0.0 {
+            return Vec::new();
+        }
+
+        let start = (residual_lo * (1.0 + self.min_relative_error) / self.precision).floor() as u64;
+        let end = (residual_hi * (1.0 + self.max_relative_error) / self.precision).ceil() as u64;
+
+        let mut candidates = Vec::new();
+        let mut counts = vec![0; self.bounds.len()];
+        let k = self.bounds.len() - 1;
+        for integer_mass in start..=end {
+            self.find_all(integer_mass, k, &mut counts, &mut candidates);
+        }
+
+        let mut hits: Vec<(f64, Vec<i32>)> = candidates
+            .into_iter()
+            .filter_map(|counts| {
+                let total: f64 = counts
+                    .iter()
+                    .zip(self.masses.iter())
+                    .map(|(c, m)| *c as f64 * m)
+                    .sum::<f64>()
+                    + self.base_mass;
+                if total >= lo && total <= hi {
+                    Some((total, counts))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        hits.sort_by(|a, b| a.0.total_cmp(&b.0));
+        hits.into_iter()
+            .map(|(_, counts)| self.build_composition(&counts))
+            .collect()
+    }
+}
+
+/**
+Find all compositions within `bounds` whose mass is within `tolerance` of `mass`.
+
+This builds a new [`MassDecomposer`] for each call. When decomposing many masses over
+the same bounds, construct a [`MassDecomposer`] directly instead.
+*/
+pub fn decompose_mass<'lifespan>(
+    mass: f64,
+    tolerance: Tolerance,
+    bounds: Vec<ElementBound<'lifespan>>,
+) -> Result<Vec<ChemicalComposition<'lifespan>>, DecompositionError> {
+    let decomposer = MassDecomposer::new(bounds)?;
+    Ok(decomposer.decompose(mass, tolerance))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn brute_force(mass: f64, tolerance: Tolerance, bounds: &[ElementBound<'static>]) -> usize {
+        let mut hits = 0;
+        let mut counts: Vec<i32> = bounds.iter().map(|b| b.min).collect();
+        loop {
+            let total: f64 = counts
+                .iter()
+                .zip(bounds.iter())
+                .map(|(c, b)| *c as f64 * b.mass())
+                .sum();
+            if tolerance.test(mass, total) {
+                hits += 1;
+            }
+            let mut i = 0;
+            loop {
+                if i == bounds.len() {
+                    return hits;
+                }
+                counts[i] += 1;
+                if counts[i] > bounds[i].max {
+                    counts[i] = bounds[i].min;
+                    i += 1;
+                } else {
+                    break;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_bounds() {
+        let bounds = parse_element_bounds("C0-100 H0-200,N10 C[13]1-6").unwrap();
+        assert_eq!(bounds.len(), 4);
+        assert_eq!(bounds[2].min, 0);
+        assert_eq!(bounds[2].max, 10);
+        assert_eq!(bounds[3].element.isotope, 13);
+        assert_eq!(bounds[3].min, 1);
+
+        assert!(matches!(
+            parse_element_bounds("Xx0-3"),
+            Err(DecompositionError::UnknownElement(_))
+        ));
+        assert!(matches!(
+            parse_element_bounds("C5-3"),
+            Err(DecompositionError::InvalidRange(_))
+        ));
+        assert_eq!(
+            parse_element_bounds("C5-3").unwrap_err().to_string(),
+            "invalid range in `C5-3`, bounds must be non-negative and ordered"
+        );
+
+        assert_eq!(
+            parse_element_bounds("C0-10 H0-20 C0-5").unwrap_err(),
+            DecompositionError::DuplicateElement("C".to_string())
+        );
+        let mut bounds = parse_element_bounds("C0-10 H0-20").unwrap();
+        bounds.push(bounds[0]);
+        assert_eq!(
+            MassDecomposer::new(bounds).unwrap_err(),
+            DecompositionError::DuplicateElement("C".to_string())
+        );
+        // Distinct isotopes of the same element are separate alphabet entries
+        let bounds = parse_element_bounds("C0-10 C[13]0-5").unwrap();
+        assert!(MassDecomposer::new(bounds).is_ok());
+    }
+
+    #[test]
+    fn test_decompose_glucose() {
+        let bounds = parse_element_bounds("C0-20 H0-40 N0-5 O0-20").unwrap();
+        let decomposer = MassDecomposer::new(bounds.clone()).unwrap();
+        let hits = decomposer.decompose(180.06339, Tolerance::PPM(10.0));
+        let glucose = ChemicalComposition::parse("C6H12O6").unwrap();
+        assert!(hits.contains(&glucose));
+        assert!(hits
+            .iter()
+            .all(|c| Tolerance::PPM(10.0).test(180.06339, c.mass())));
+        assert_eq!(hits.len(), brute_force(180.06339, Tolerance::PPM(10.0), &bounds));
+    }
+
+    #[test]
+    fn test_decompose_lower_bounds() {
+        let bounds = parse_element_bounds("C5-20 H0-40 N0-5 O1-20 S1-2").unwrap();
+        let decomposer = MassDecomposer::with_precision(bounds.clone(), 1e-3).unwrap();
+        let target = ChemicalComposition::parse("C10H17N3O6S").unwrap().mass();
+        let hits = decomposer.decompose(target, Tolerance::Da(0.01));
+        assert!(hits.iter().all(|c| c["S"] >= 1 && c["C"] >= 5 && c["O"] >= 1));
+        assert_eq!(hits.len(), brute_force(target, Tolerance::Da(0.01), &bounds));
+    }
+
+    #[test]
+    fn test_decompose_single_element() {
+        let bounds = parse_element_bounds("C0-10").unwrap();
+        let decomposer = MassDecomposer::new(bounds.clone()).unwrap();
+        let hits = decomposer.decompose(120.0, Tolerance::Da(0.01));
+        assert_eq!(hits, vec![ChemicalComposition::parse("C10").unwrap()]);
+        assert_eq!(hits.len(), brute_force(120.0, Tolerance::Da(0.01), &bounds));
+        assert!(decomposer.decompose(125.0, Tolerance::Da(0.01)).is_empty());
+    }
+}
@@ -67,6 +67,67 @@ impl cmp::PartialOrd<Isotope> for Isotope {
     }
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature="serde", derive(Serialize, Deserialize))]
+/** The IUPAC standard atomic weight of an element, which may be a single value,
+an interval spanning the natural variation in isotopic composition, or only the mass
+number of the most stable isotope for elements without a stable isotope.
+*/
+pub enum StandardAtomicWeight {
+    #[default]
+    Unknown,
+    Value(f64),
+    Interval(f64, f64),
+    MassNumber(u16),
+}
+
+impl StandardAtomicWeight {
+    /// A single representative value, using the midpoint of an interval
+    pub fn value(&self) -> Option<f64> {
+        match self {
+            StandardAtomicWeight::Unknown => None,
+            StandardAtomicWeight::Value(v) => Some(*v),
+            StandardAtomicWeight::Interval(lo, hi) => Some((lo + hi) / 2.0),
+            StandardAtomicWeight::MassNumber(n) => Some(*n as f64),
+        }
+    }
+}
+
+/// Serialize isotope tables with mass numbers as string keys, so that formats which
+/// only allow string keys, like TOML, can represent an [`Element`]
+#[cfg(feature = "serde")]
+mod isotope_keys {
+    use super::{HashMap, Isotope, RandomState};
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        isotopes: &HashMap<u16, Isotope, RandomState>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(isotopes.iter().map(|(k, v)| (k.to_string(), v)))
+    }
+
+    /// Tables saved before mass numbers were written as strings use integer keys
+    #[derive(Deserialize, PartialEq, Eq, Hash)]
+    #[serde(untagged)]
+    enum Key {
+        Number(u16),
+        Text(String),
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<HashMap<u16, Isotope, RandomState>, D::Error> {
+        let raw: HashMap<Key, Isotope, RandomState> = HashMap::deserialize(deserializer)?;
+        raw.into_iter()
+            .map(|(k, v)| match k {
+                Key::Number(k) => Ok((k, v)),
+                Key::Text(k) => k.parse().map(|k| (k, v)).map_err(D::Error::custom),
+            })
+            .collect()
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 #[cfg_attr(feature="serde", derive(Serialize, Deserialize))]
 /** A chemical element with known masses and isotopic frequency.
@@ -76,19 +137,90 @@ treated like a singleton in a [`PeriodicTable`].
 */
 pub struct Element {
     pub symbol: String,
+    #[cfg_attr(feature="serde", serde(with = "isotope_keys"))]
     pub isotopes: HashMap<u16, Isotope, RandomState>,
     pub most_abundant_isotope: u16,
     pub most_abundant_mass: f64,
     pub min_neutron_shift: NeutronShiftType,
     pub max_neutron_shift: NeutronShiftType,
+    /// The atomic number, the number of protons in the nucleus. Pseudo-elements like
+    /// `e*` and `H+` use 0.
     pub element_number: ElementNumberType,
+    #[cfg_attr(feature="serde", serde(default))]
+    pub name: String,
+    #[cfg_attr(feature="serde", serde(default))]
+    pub standard_atomic_weight: StandardAtomicWeight,
 }
 
 impl Element {
+    /// The mass of the most abundant isotope. See [`Element::monoisotopic_mass`] for
+    /// the mass of the lightest isotope.
     pub fn mass(&self) -> f64 {
         return self.isotopes[&self.most_abundant_isotope].mass;
     }
 
+    /// The mass of the most abundant isotope
+    #[inline]
+    pub fn most_abundant_mass(&self) -> f64 {
+        self.most_abundant_mass
+    }
+
+    /// The mass number of the lightest naturally occurring isotope, which for most
+    /// light elements is also the most abundant, but not for elements like Fe, Se, Sn or Mo
+    pub fn monoisotopic_isotope(&self) -> u16 {
+        self.isotopes
+            .values()
+            .filter(|iso| iso.abundance > 0.0)
+            .min_by(|a, b| a.mass.total_cmp(&b.mass))
+            .map(|iso| iso.neutrons)
+            .unwrap_or(self.most_abundant_isotope)
+    }
+
+    /// The mass of the lightest naturally occurring isotope
+    pub fn monoisotopic_mass(&self) -> f64 {
+        self.isotopes
+            .get(&self.monoisotopic_isotope())
+            .map(|iso| iso.mass)
+            .unwrap_or(self.most_abundant_mass)
+    }
+
+    #[inline]
+    pub fn atomic_number(&self) -> ElementNumberType {
+        self.element_number
+    }
+
+    /// The abundance-weighted mean of the masses of this element's isotopes
+    pub fn average_mass(&self) -> f64 {
+        let (total, weight) = self
+            .isotopes
+            .values()
+            .fold((0.0, 0.0), |(total, weight), iso| {
+                (iso.mass.mul_add(iso.abundance, total), weight + iso.abundance)
+            });
+        if weight > 0.0 {
+            total / weight
+        } else {
+            self.most_abundant_mass
+        }
+    }
+
+    /// The standard atomic weight of this element if it is known, otherwise
+    /// [`Element::average_mass`]
+    pub fn molar_mass(&self) -> f64 {
+        self.standard_atomic_weight
+            .value()
+            .unwrap_or_else(|| self.average_mass())
+    }
+
+    /// The integer mass of the most abundant isotope
+    pub fn nominal_mass(&self) -> u16 {
+        if self.most_abundant_isotope != 0 {
+            self.most_abundant_isotope
+        } else {
+            self.most_abundant_mass.round() as u16
+        }
+    }
+
     pub fn calc_min_neutron_shift(&self) -> NeutronShiftType {
         if self.min_neutron_shift != 0 {
             return self.min_neutron_shift;
@@ -109,6 +241,11 @@ impl Element {
         }
     }
 
+    /// Whether `mass_number` is one of this element's isotopes
+    pub fn has_isotope(&self, mass_number: u16) -> bool {
+        mass_number != 0 && self.isotopes.contains_key(&mass_number)
+    }
+
     pub fn isotope_by_shift(&self, shift: NeutronShiftType) -> Option<&Isotope> {
         let num = self.most_abundant_isotope as i16 + shift as i16;
         self.isotopes.get(&(num as u16))
@@ -184,6 +321,32 @@ impl PeriodicTable {
     pub fn get(&self, symbol: &str) -> Option<&Element> {
         self.elements.get(symbol)
     }
+
+    /// Find the element with the specified atomic number, ignoring pseudo-elements
+    pub fn get_by_atomic_number(&self, atomic_number: ElementNumberType) -> Option<&Element> {
+        if atomic_number == 0 {
+            return None;
+        }
+        self.elements
+            .values()
+            .filter(|e| e.element_number == atomic_number)
+            .min_by_key(|e| e.symbol.len())
+    }
+
+    /**
+    Find up to `limit` symbols in the table which are close to `symbol`, for suggesting
+    corrections to a misspelled symbol.
+
+    Symbols are compared by case-insensitive edit distance, preferring symbols which share
+    the first letter of `symbol`.
+    */
+    pub fn nearest_symbols(&self, symbol: &str, limit: usize) -> Vec<&str> {
+        chemical_elements_grammar::nearest_symbols(
+            self.elements.keys().map(String::as_str),
+            symbol,
+            limit,
+        )
+    }
 }
 
 impl ops::Index<&str> for PeriodicTable {
@@ -194,3 +357,38 @@ impl ops::Index<&str> for PeriodicTable {
         return &self.elements[i];
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::table::PERIODIC_TABLE;
+
+    #[test]
+    fn test_atomic_metadata() {
+        let silver = &PERIODIC_TABLE["Ag"];
+        assert_eq!(silver.atomic_number(), 47);
+        assert_eq!(silver.name, "Silver");
+        assert_eq!(silver.standard_atomic_weight, StandardAtomicWeight::Value(107.8682));
+
+        let carbon = PERIODIC_TABLE.get_by_atomic_number(6).unwrap();
+        assert_eq!(carbon.symbol, "C");
+        assert_eq!(carbon.standard_atomic_weight, StandardAtomicWeight::Interval(12.0096, 12.0116));
+        assert!((carbon.standard_atomic_weight.value().unwrap() - 12.0106).abs() < 1e-9);
+
+        assert_eq!(PERIODIC_TABLE.get_by_atomic_number(1).unwrap().symbol, "H");
+        assert!(PERIODIC_TABLE.get_by_atomic_number(0).is_none());
+        assert_eq!(PERIODIC_TABLE["Tc"].standard_atomic_weight, StandardAtomicWeight::MassNumber(98));
+    }
+
+    #[test]
+    fn test_monoisotopic() {
+        let selenium = &PERIODIC_TABLE["Se"];
+        assert_eq!(selenium.most_abundant_isotope, 80);
+        assert_eq!(selenium.monoisotopic_isotope(), 74);
+        assert!((selenium.monoisotopic_mass() - 73.922476).abs() < 1e-6);
+
+        let carbon = &PERIODIC_TABLE["C"];
+        assert_eq!(carbon.monoisotopic_isotope(), 12);
+        assert_eq!(carbon.monoisotopic_mass(), carbon.mass());
+    }
+}
@@ -2,20 +2,75 @@ use std::borrow::Borrow;
 use std::cmp;
 use std::fmt::{self, Display};
 use std::hash;
+use std::ops::Range;
 use std::str::FromStr;
 
+use chemical_elements_grammar::{write_diagnostic, SUGGESTION_LIMIT};
+
 use crate::element::{Element, PeriodicTable};
 use crate::table::PERIODIC_TABLE;
 
-#[derive(Debug, Clone, Copy)]
-pub enum ElementSpecificationParsingError {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ElementSpecificationParsingErrorKind {
+    /// An isotope was opened with `[` but never closed
     UnclosedIsotope,
+    /// The symbol was not found in the periodic table
     UnknownElement,
+    /// The isotope between `[]` was not a mass number
+    InvalidIsotope,
+    /// The mass number between `[]` is not one of the element's isotopes
+    UnknownIsotope,
+    /// Characters followed the `]` closing the isotope
+    TrailingCharacters,
+}
+
+impl Display for ElementSpecificationParsingErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::UnclosedIsotope => "unclosed isotope",
+            Self::UnknownElement => "unknown element",
+            Self::InvalidIsotope => "invalid isotope",
+            Self::UnknownIsotope => "unknown isotope",
+            Self::TrailingCharacters => "unexpected characters after the isotope",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// An error parsing an [`ElementSpecification`], locating the problem in the parsed string
+pub struct ElementSpecificationParsingError {
+    pub kind: ElementSpecificationParsingErrorKind,
+    /// The string being parsed
+    pub specification: String,
+    /// The byte range of the offending token in `specification`
+    pub span: Range<usize>,
+    /// Symbols close to an unknown element's symbol
+    pub suggestions: Vec<String>,
+}
+
+impl ElementSpecificationParsingError {
+    pub fn new(
+        kind: ElementSpecificationParsingErrorKind,
+        specification: &str,
+        span: Range<usize>,
+    ) -> Self {
+        Self {
+            kind,
+            specification: specification.to_string(),
+            span,
+            suggestions: Vec::new(),
+        }
+    }
+
+    /// The offending token
+    pub fn token(&self) -> &str {
+        self.specification.get(self.span.clone()).unwrap_or_default()
+    }
 }
 
 impl Display for ElementSpecificationParsingError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self)
+        write_diagnostic(f, &self.kind, &self.specification, &self.span, &self.suggestions)
     }
 }
 
@@ -100,6 +155,56 @@ impl<'transient, 'lifespan: 'transient, 'element> ElementSpecification<'element>
         return ElementSpecification { element, isotope };
     }
 
+    #[inline]
+    /// The mass of the fixed isotope, or of the element's most abundant isotope
+    pub fn mass(&self) -> f64 {
+        if self.isotope == 0 {
+            self.element.most_abundant_mass
+        } else {
+            self.element.isotopes[&self.isotope].mass
+        }
+    }
+
+    #[inline]
+    /// The mass of the fixed isotope, or of the element's lightest isotope
+    pub fn monoisotopic_mass(&self) -> f64 {
+        if self.isotope == 0 {
+            self.element.monoisotopic_mass()
+        } else {
+            self.element.isotopes[&self.isotope].mass
+        }
+    }
+
+    #[inline]
+    /// The mass of the fixed isotope, or the element's abundance-weighted average mass
+    pub fn average_mass(&self) -> f64 {
+        if self.isotope == 0 {
+            self.element.average_mass()
+        } else {
+            self.element.isotopes[&self.isotope].mass
+        }
+    }
+
+    #[inline]
+    /// The mass of the fixed isotope, or the element's standard atomic weight
+    pub fn molar_mass(&self) -> f64 {
+        if self.isotope == 0 {
+            self.element.molar_mass()
+        } else {
+            self.element.isotopes[&self.isotope].mass
+        }
+    }
+
+    #[inline]
+    /// The mass number of the fixed isotope, or of the element's most abundant isotope
+    pub fn nominal_mass(&self) -> u16 {
+        if self.isotope == 0 {
+            self.element.nominal_mass()
+        } else {
+            self.isotope
+        }
+    }
+
     #[inline]
     pub fn to_string(&self) -> String {
         if self.isotope == 0 {
@@ -152,38 +257,69 @@ impl<'transient, 'lifespan: 'transient, 'element> ElementSpecification<'element>
         string: &'transient str,
         periodic_table: &'lifespan PeriodicTable,
     ) -> Result<ElementSpecification<'lifespan>, ElementSpecificationParsingError> {
-        let n = string.len();
-        let elt_start = 0;
-        let mut elt_end = n;
-        let mut iso_start = n;
-        let mut iso_end = n;
-        for (i, c) in string.chars().enumerate() {
-            if c == '[' {
-                elt_end = i;
-                if n > i {
-                    iso_start = i + 1;
-                } else {
-                    return Err(ElementSpecificationParsingError::UnclosedIsotope);
+        let error = ElementSpecificationParsingError::new;
+        let (elt_sym, isotope, isotope_span) = match string.find('[') {
+            Some(open) => {
+                let close = match string[open..].find(']') {
+                    Some(offset) => open + offset,
+                    None => {
+                        return Err(error(
+                            ElementSpecificationParsingErrorKind::UnclosedIsotope,
+                            string,
+                            open..string.len(),
+                        ))
+                    }
+                };
+                if close + 1 < string.len() {
+                    return Err(error(
+                        ElementSpecificationParsingErrorKind::TrailingCharacters,
+                        string,
+                        close + 1..string.len(),
+                    ));
                 }
-            } else if c == ']' {
-                iso_end = i;
+                let isotope = &string[open + 1..close];
+                let isotope = if isotope.is_empty() {
+                    0
+                } else {
+                    isotope.parse::<u16>().map_err(|_| {
+                        error(
+                            ElementSpecificationParsingErrorKind::InvalidIsotope,
+                            string,
+                            open + 1..close,
+                        )
+                    })?
+                };
+                (&string[..open], isotope, open + 1..close)
             }
-        }
-        let elt_sym = &string[elt_start..elt_end];
+            None => (string, 0, 0..0),
+        };
         if let Some(element) = periodic_table.get(elt_sym) {
-            let isotope = if iso_start != iso_end {
-                string[iso_start..iso_end].parse::<u16>().unwrap()
-            } else {
-                0
-            };
+            // Empty brackets are the element's natural abundance, but any mass number given
+            // must be an isotope of the element so its mass can be looked up
+            if !isotope_span.is_empty() && !element.has_isotope(isotope) {
+                return Err(error(
+                    ElementSpecificationParsingErrorKind::UnknownIsotope,
+                    string,
+                    isotope_span,
+                ));
+            }
             Ok(ElementSpecification::new(element, isotope))
         } else {
-            Err(ElementSpecificationParsingError::UnknownElement)
+            let mut err = error(
+                ElementSpecificationParsingErrorKind::UnknownElement,
+                string,
+                0..elt_sym.len(),
+            );
+            err.suggestions = periodic_table
+                .nearest_symbols(elt_sym, SUGGESTION_LIMIT)
+                .into_iter()
+                .map(String::from)
+                .collect();
+            Err(err)
         }
     }
 }
 
-
 impl<'a> FromStr for ElementSpecification<'a> {
     type Err = ElementSpecificationParsingError;
 
@@ -206,4 +342,34 @@ mod test {
         assert_eq!(spec.isotope, 13);
         assert_eq!(spec.element.symbol, "C");
     }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = ElementSpecification::parse("C[abc]").unwrap_err();
+        assert_eq!(err.kind, ElementSpecificationParsingErrorKind::InvalidIsotope);
+        assert_eq!(err.token(), "abc");
+        let err = "C[13]xyz".parse::<ElementSpecification>().unwrap_err();
+        assert_eq!(err.kind, ElementSpecificationParsingErrorKind::TrailingCharacters);
+        assert_eq!(err.token(), "xyz");
+        assert_eq!(
+            err.to_string(),
+            "unexpected characters after the isotope `xyz` at byte 5\nC[13]xyz\n     ^^^"
+        );
+        let err = ElementSpecification::parse("C[99]").unwrap_err();
+        assert_eq!(err.kind, ElementSpecificationParsingErrorKind::UnknownIsotope);
+        assert_eq!(err.token(), "99");
+        assert!(ElementSpecification::parse("C[0]").is_err());
+        assert_eq!(ElementSpecification::parse("C[]").unwrap().isotope, 0);
+        let err = ElementSpecification::parse("C[13").unwrap_err();
+        assert_eq!(err.kind, ElementSpecificationParsingErrorKind::UnclosedIsotope);
+
+        let err = ElementSpecification::parse("Xx[12]").unwrap_err();
+        assert_eq!(err.kind, ElementSpecificationParsingErrorKind::UnknownElement);
+        assert_eq!(err.token(), "Xx");
+        assert_eq!(err.suggestions, vec!["Xe".to_string()]);
+        assert_eq!(
+            err.to_string(),
+            "unknown element `Xx` at byte 0, did you mean `Xe`?\nXx[12]\n^^"
+        );
+    }
 }
\ No newline at end of file
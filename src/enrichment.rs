@@ -0,0 +1,230 @@
+//! Derive isotopically enriched variants of an [`Element`] for labeling experiments,
+//! like 99% <sup>13</sup>C glucose or 98% <sup>15</sup>N media.
+//!
+//! A variant is an ordinary [`Element`] under a new symbol, conventionally the base
+//! symbol followed by `*`, so once it is added to a [`PeriodicTable`](crate::PeriodicTable)
+//! it can be used in formulas and isotopic pattern generation like any other element.
+//!
+//! ```rust
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use chemical_elements::{ChemicalComposition, PERIODIC_TABLE};
+//!
+//! let mut table = PERIODIC_TABLE.clone();
+//! table.add(PERIODIC_TABLE["C"].enriched("C*", 13, 0.99)?);
+//! let glucose = ChemicalComposition::parse_with("C*6H12O6", &table)?;
+//! let unlabeled = ChemicalComposition::parse("C6H12O6")?;
+//! assert!((glucose.mass() - unlabeled.mass() - 6.0 * 1.003355).abs() < 1e-5);
+//! # Ok(())
+//! # }
+//! ```
+use std::fmt::{self, Display};
+
+use crate::element::{Element, Isotope, StandardAtomicWeight};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EnrichmentError {
+    /// The requested isotope is not known for the base element
+    UnknownIsotope(u16),
+    /// An abundance or purity was not in `[0, 1]`
+    InvalidAbundance(f64),
+    /// The abundances do not sum to one
+    AbundanceSum(f64),
+}
+
+impl Display for EnrichmentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownIsotope(mass_number) => {
+                write!(
+                    f,
+                    "the element has no isotope with mass number {mass_number}"
+                )
+            }
+            Self::InvalidAbundance(abundance) => {
+                write!(f, "abundance {abundance} is not between 0 and 1")
+            }
+            Self::AbundanceSum(total) => write!(f, "abundances sum to {total} rather than 1"),
+        }
+    }
+}
+
+impl std::error::Error for EnrichmentError {}
+
+/// How far the abundances of a variant may sum from one
+const ABUNDANCE_SUM_TOLERANCE: f64 = 1e-6;
+
+impl Element {
+    /**
+    Derive a variant of this element named `symbol` whose isotopes have the specified
+    abundances, given as `(mass number, abundance)` pairs. Isotopes which are not listed
+    are omitted, and the listed abundances must sum to one.
+
+    The variant keeps the atomic number and name of this element, but its standard atomic
+    weight is unknown, so [`Element::molar_mass`] uses the abundance-weighted mass.
+    */
+    pub fn with_abundances(
+        &self,
+        symbol: &str,
+        abundances: &[(u16, f64)],
+    ) -> Result<Element, EnrichmentError> {
+        let mut total = 0.0;
+        for (mass_number, abundance) in abundances.iter().copied() {
+            if !self.isotopes.contains_key(&mass_number) || mass_number == 0 {
+                return Err(EnrichmentError::UnknownIsotope(mass_number));
+            }
+            if !(0.0..=1.0).contains(&abundance) {
+                return Err(EnrichmentError::InvalidAbundance(abundance));
+            }
+            total += abundance;
+        }
+        if (total - 1.0).abs() > ABUNDANCE_SUM_TOLERANCE {
+            return Err(EnrichmentError::AbundanceSum(total));
+        }
+
+        let (most_abundant_isotope, _) = abundances
+            .iter()
+            .copied()
+            .filter(|(_, abundance)| *abundance > 0.0)
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap();
+        let mut variant = Element {
+            symbol: symbol.to_string(),
+            most_abundant_isotope,
+            most_abundant_mass: self.isotopes[&most_abundant_isotope].mass,
+            element_number: self.element_number,
+            name: self.name.clone(),
+            standard_atomic_weight: StandardAtomicWeight::Unknown,
+            ..Default::default()
+        };
+        for (mass_number, abundance) in abundances.iter().copied() {
+            if abundance == 0.0 {
+                continue;
+            }
+            variant.isotopes.insert(
+                mass_number,
+                Isotope {
+                    mass: self.isotopes[&mass_number].mass,
+                    abundance,
+                    neutrons: mass_number,
+                    neutron_shift: (mass_number as i32 - most_abundant_isotope as i32) as i8,
+                },
+            );
+        }
+        variant.index_isotopes();
+        Ok(variant)
+    }
+
+    /**
+    Derive a variant of this element named `symbol` where `isotope` makes up `purity` of
+    the atoms, and the remainder is split between the other isotopes in proportion to their
+    natural abundances.
+    */
+    pub fn enriched(
+        &self,
+        symbol: &str,
+        isotope: u16,
+        purity: f64,
+    ) -> Result<Element, EnrichmentError> {
+        if !(0.0..=1.0).contains(&purity) {
+            return Err(EnrichmentError::InvalidAbundance(purity));
+        }
+        if !self.isotopes.contains_key(&isotope) || isotope == 0 {
+            return Err(EnrichmentError::UnknownIsotope(isotope));
+        }
+        let remainder: f64 = self
+            .isotopes
+            .values()
+            .filter(|iso| iso.neutrons != isotope && iso.neutrons != 0)
+            .map(|iso| iso.abundance)
+            .sum();
+        let mut abundances: Vec<(u16, f64)> = self
+            .isotopes
+            .values()
+            .filter(|iso| iso.neutrons != 0)
+            .map(|iso| {
+                if iso.neutrons == isotope {
+                    (iso.neutrons, purity)
+                } else if remainder > 0.0 {
+                    (iso.neutrons, (1.0 - purity) * iso.abundance / remainder)
+                } else {
+                    (iso.neutrons, 0.0)
+                }
+            })
+            .collect();
+        abundances.sort_by_key(|(mass_number, _)| *mass_number);
+        self.with_abundances(symbol, &abundances)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::isotopic_pattern::baffling::PhiConstants;
+    use crate::isotopic_pattern::isotopic_variants;
+    use crate::{ChemicalComposition, PERIODIC_TABLE, PROTON};
+
+    #[test]
+    fn test_enriched() {
+        let carbon = PERIODIC_TABLE["C"].enriched("C*", 13, 0.99).unwrap();
+        assert_eq!(carbon.most_abundant_isotope, 13);
+        assert_eq!(carbon.element_number, 6);
+        assert!((carbon.isotopes[&12].abundance - 0.01).abs() < 1e-12);
+        assert_eq!(carbon.min_neutron_shift, -1);
+        assert_eq!(carbon.max_neutron_shift, 0);
+
+        let nitrogen = PERIODIC_TABLE["N"].enriched("N*", 15, 0.98).unwrap();
+        assert!((nitrogen.isotopes[&14].abundance - 0.02).abs() < 1e-12);
+        assert!((nitrogen.average_mass() - 15.0).abs() < 0.02);
+
+        let oxygen = PERIODIC_TABLE["O"].enriched("O*", 17, 0.5).unwrap();
+        let total: f64 = oxygen.isotopes.values().map(|iso| iso.abundance).sum();
+        assert!((total - 1.0).abs() < 1e-12);
+        assert_eq!(oxygen.isotopes[&18].neutron_shift, 1);
+        assert_eq!(PhiConstants::from_element(&oxygen).order, 1);
+    }
+
+    #[test]
+    fn test_validation() {
+        let carbon = &PERIODIC_TABLE["C"];
+        assert_eq!(
+            carbon.with_abundances("C*", &[(12, 0.5), (13, 0.4)]),
+            Err(EnrichmentError::AbundanceSum(0.9))
+        );
+        assert_eq!(
+            carbon.with_abundances("C*", &[(14, 1.0)]),
+            Err(EnrichmentError::UnknownIsotope(14))
+        );
+        assert_eq!(
+            carbon.enriched("C*", 13, 1.5),
+            Err(EnrichmentError::InvalidAbundance(1.5))
+        );
+        assert_eq!(
+            carbon.enriched("C*", 13, 1.5).unwrap_err().to_string(),
+            "abundance 1.5 is not between 0 and 1"
+        );
+        let pure = carbon
+            .with_abundances("C*", &[(12, 0.0), (13, 1.0)])
+            .unwrap();
+        assert_eq!(pure.isotopes.len(), 1);
+    }
+
+    #[test]
+    fn test_labeled_pattern() {
+        let mut table = PERIODIC_TABLE.clone();
+        table.add(PERIODIC_TABLE["C"].enriched("C*", 13, 0.99).unwrap());
+        let labeled = ChemicalComposition::parse_with("C*6H12O6", &table).unwrap();
+        let unlabeled = ChemicalComposition::parse("C6H12O6").unwrap();
+        assert_eq!(
+            ChemicalComposition::parse_with(&labeled.to_string(), &table).unwrap(),
+            labeled
+        );
+
+        let labeled_peaks = isotopic_variants(labeled.clone(), 3, 1, PROTON);
+        let unlabeled_peaks = isotopic_variants(unlabeled.clone(), 3, 1, PROTON);
+        let shift = labeled_peaks[0].mz - unlabeled_peaks[0].mz;
+        assert!((shift - 6.0 * 1.0033548).abs() < 1e-5);
+        // Without 12C to contribute, the M+1 peak comes only from H and O
+        assert!(unlabeled_peaks[1].intensity / unlabeled_peaks[0].intensity > 0.06);
+        assert!(labeled_peaks[1].intensity / labeled_peaks[0].intensity < 0.01);
+    }
+}
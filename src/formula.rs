@@ -1,668 +1,179 @@
 use std::fmt::Display;
-use std::num::ParseIntError;
+use std::ops::Range;
 
+use chemical_elements_grammar::{self as grammar, write_diagnostic, SUGGESTION_LIMIT};
+
+use crate::charged_composition::charge_suffix;
 use crate::table::PERIODIC_TABLE;
 use crate::ElementSpecification;
 use crate::abstract_composition::{ChemicalComposition, ChemicalCompositionRef};
-use crate::{Element, PeriodicTable};
-
-#[derive(Debug)]
-pub enum FormulaParserState {
-    New,
-    Element,
-    Isotope,
-    IsotopeToCount,
-    Count,
-    Group,
-    GroupToGroupCount,
-    GroupCount,
-}
+use crate::PeriodicTable;
 
-impl Default for FormulaParserState {
-    fn default() -> FormulaParserState {
-        FormulaParserState::New
-    }
-}
-
-#[derive(Debug, Clone, Copy)]
-pub enum FormulaParserError {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormulaParserErrorKind {
     InvalidStart,
     ElementCountMalformed,
     IsotopeCountMalformed,
     GroupCountMalformed,
     IncompleteFormula,
+    /// A character was found where an element symbol or group was expected
     InvalidElement,
+    /// An element symbol was not found in the periodic table
+    UnknownElement,
+    InvalidCharge,
+    /// An isotope's mass number is not one of the element's isotopes in the periodic table
+    UnknownIsotope,
 }
 
-impl Display for FormulaParserError {
+impl Display for FormulaParserErrorKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self)
+        f.write_str(match self {
+            Self::InvalidStart => "invalid start of formula",
+            Self::ElementCountMalformed => "malformed element count",
+            Self::IsotopeCountMalformed => "malformed isotope",
+            Self::GroupCountMalformed => "malformed group count",
+            Self::IncompleteFormula => "incomplete formula",
+            Self::InvalidElement => "unexpected character",
+            Self::UnknownElement => "unknown element",
+            Self::InvalidCharge => "invalid charge",
+            Self::UnknownIsotope => "unknown isotope",
+        })
     }
 }
 
-impl std::error::Error for FormulaParserError {}
-
-#[derive(Default)]
-pub struct FormulaParser {
-    pub element_start: usize,
-    pub element_end: usize,
-    pub isotope_start: usize,
-    pub isotope_end: usize,
-    pub count_start: usize,
-    pub count_end: usize,
-    pub paren_stack: i32,
-    pub group_start: usize,
-    pub group_end: usize,
-    pub group_count_start: usize,
-    pub group_count_end: usize,
-    pub state: FormulaParserState,
+#[derive(Debug, Clone, PartialEq)]
+/**
+An error parsing a formula, locating the problem in the formula. Its [`Display`]
+implementation renders the formula with a caret under the offending token.
+*/
+pub struct FormulaParserError {
+    pub kind: FormulaParserErrorKind,
+    /// The formula being parsed
+    pub formula: String,
+    /// The byte range of the offending token in `formula`
+    pub span: Range<usize>,
+    /// Element symbols close to an unknown element's symbol
+    pub suggestions: Vec<String>,
 }
 
-impl<'transient, 'lifespan: 'transient, 'separate> FormulaParser {
-    pub fn parse(string: &str) -> Result<ChemicalComposition<'lifespan>, FormulaParserError> {
-        let mut parser = Self::default();
-        parser.parse_formula_with_table_generic(string, &PERIODIC_TABLE)
+impl FormulaParserError {
+    pub fn new(kind: FormulaParserErrorKind, formula: &str, span: Range<usize>) -> Self {
+        Self {
+            kind,
+            formula: formula.to_string(),
+            span,
+            suggestions: Vec::new(),
+        }
     }
 
-    pub fn parse_with_table(
-        string: &'transient str,
-        periodic_table: &'lifespan PeriodicTable,
-    ) -> Result<ChemicalComposition<'lifespan>, FormulaParserError> {
-        let mut parser = Self::default();
-        parser.parse_formula_with_table_generic(string, periodic_table)
+    /// The offending token, which is empty if the formula ended unexpectedly
+    pub fn token(&self) -> &str {
+        self.formula.get(self.span.clone()).unwrap_or_default()
     }
 
-    pub fn parse_element_from_string(
-        &mut self,
-        string: &str,
-        periodic_table: &'lifespan PeriodicTable,
-    ) -> &'lifespan Element {
-        let elt_sym = &string[self.element_start..self.element_end];
-        let elt = &periodic_table[elt_sym];
-        self.element_start = 0;
-        self.element_end = 0;
-        elt
+    /// Re-locate an error from parsing the part of `formula` starting at `offset`
+    pub(crate) fn within(mut self, formula: &str, offset: usize) -> Self {
+        self.formula = formula.to_string();
+        self.span = (self.span.start + offset)..(self.span.end + offset);
+        self
+    }
+}
+
+impl From<grammar::ErrorKind> for FormulaParserErrorKind {
+    fn from(kind: grammar::ErrorKind) -> Self {
+        match kind {
+            grammar::ErrorKind::InvalidStart => Self::InvalidStart,
+            grammar::ErrorKind::ElementCountMalformed => Self::ElementCountMalformed,
+            grammar::ErrorKind::IsotopeCountMalformed => Self::IsotopeCountMalformed,
+            grammar::ErrorKind::GroupCountMalformed => Self::GroupCountMalformed,
+            grammar::ErrorKind::IncompleteFormula => Self::IncompleteFormula,
+            grammar::ErrorKind::InvalidElement => Self::InvalidElement,
+            grammar::ErrorKind::UnknownElement => Self::UnknownElement,
+        }
     }
+}
 
-    pub fn parse_element_count(&mut self, string: &str) -> Result<i32, ParseIntError> {
-        let count_parse = string[self.count_start..self.count_end].parse::<i32>();
-        self.count_start = 0;
-        self.count_end = 0;
-        count_parse
+impl Display for FormulaParserError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write_diagnostic(f, &self.kind, &self.formula, &self.span, &self.suggestions)
     }
+}
 
-    pub fn parse_group_count(&mut self, string: &str) -> Result<i32, ParseIntError> {
-        let count_parse = string[self.group_count_start..self.group_count_end].parse::<i32>();
-        self.group_count_start = 0;
-        self.group_count_end = 0;
-        count_parse
+impl std::error::Error for FormulaParserError {}
+
+/**
+Parses formulas into compositions, following the grammar of `chemical_elements_grammar`,
+which the `formula!` macro shares.
+*/
+#[derive(Debug)]
+pub struct FormulaParser;
+
+impl<'transient, 'lifespan: 'transient> FormulaParser {
+    pub fn parse(string: &str) -> Result<ChemicalComposition<'lifespan>, FormulaParserError> {
+        Self::parse_formula_with_table_generic(string, &PERIODIC_TABLE)
     }
 
-    pub fn handle_group_state(&mut self, c: char, i: usize) {
-        if c == ')' {
-            self.paren_stack -= 1;
-            if self.paren_stack == 0 {
-                self.group_end = i;
-                self.state = FormulaParserState::GroupToGroupCount;
-            }
-        } else if c == '(' {
-            self.paren_stack += 1;
-        }
+    pub fn parse_with_table(
+        string: &'transient str,
+        periodic_table: &'lifespan PeriodicTable,
+    ) -> Result<ChemicalComposition<'lifespan>, FormulaParserError> {
+        Self::parse_formula_with_table_generic(string, periodic_table)
     }
 
     pub fn parse_formula_with_table_generic<C: From<ChemicalComposition<'lifespan>>>(
-        &mut self,
         string: &str,
         periodic_table: &'lifespan PeriodicTable,
     ) -> Result<C, FormulaParserError> {
-        let mut acc = ChemicalComposition::default();
-        let n = string.len();
-
-        for (i, c) in string.char_indices() {
-            match self.state {
-                FormulaParserState::New => {
-                    if c.is_ascii_alphabetic() && c.is_ascii_uppercase() {
-                        self.element_start = i;
-                        self.state = FormulaParserState::Element;
-                    } else if c == '(' {
-                        self.paren_stack += 1;
-                        self.group_start = i + 1;
-                        self.state = FormulaParserState::Group;
-                    } else {
-                        return Err(FormulaParserError::InvalidStart);
-                    }
-                }
-                FormulaParserState::Group => {
-                    self.handle_group_state(c, i);
-                }
-                FormulaParserState::Element => {
-                    if c.is_ascii_alphabetic() {
-                        if c.is_uppercase() {
-                            self.element_end = i;
-                            let elt = self.parse_element_from_string(string, periodic_table);
-                            let elt_spec = ElementSpecification {
-                                element: elt,
-                                isotope: 0,
-                            };
-                            acc.inc(elt_spec, 1);
-                            self.state = FormulaParserState::Element;
-                            self.element_start = i;
-                            self.element_end = 0;
-                        }
-                    } else if c.is_numeric() {
-                        self.element_end = i;
-                        self.count_start = i;
-                        self.state = FormulaParserState::Count;
-                    } else if c == '[' {
-                        self.isotope_start = i + 1;
-                        self.state = FormulaParserState::Isotope;
-                    } else if c == '(' {
-                        self.element_end = i;
-                        let elt = self.parse_element_from_string(string, periodic_table);
-                        let elt_spec = ElementSpecification {
-                            element: elt,
-                            isotope: 0,
-                        };
-                        acc.inc(elt_spec, 1);
-
-                        self.paren_stack += 1;
-                        self.group_start = i + 1;
-                        self.state = FormulaParserState::Group;
-                    }
+        let atoms = grammar::parse(string, |symbol| periodic_table.get(symbol).is_some())
+            .map_err(|err| match charge_suffix(string, periodic_table) {
+                // Charges are only read into a `ChargedComposition`
+                Some(suffix) => {
+                    FormulaParserError::new(FormulaParserErrorKind::InvalidCharge, string, suffix)
                 }
-                FormulaParserState::Isotope => {
-                    if c == ']' {
-                        self.isotope_end = i;
-                        self.state = FormulaParserState::IsotopeToCount;
-                    } else if !c.is_numeric() {
-                        return Err(FormulaParserError::IsotopeCountMalformed);
-                    }
+                None if err.kind == grammar::ErrorKind::UnknownElement => {
+                    unknown_element(string, err.span, periodic_table)
                 }
-                FormulaParserState::Count => {
-                    if !c.is_numeric() {
-                        self.count_end = i;
-                        let count_parse = self.parse_element_count(string);
-                        let count: i32 = match count_parse {
-                            Ok(val) => val,
-                            Err(_msg) => {
-                                return Err(FormulaParserError::ElementCountMalformed);
-                            }
-                        };
-                        let isotope: u16 = if self.isotope_end != self.isotope_start {
-                            match string[self.isotope_start..self.isotope_end].parse::<u16>() {
-                                Ok(val) => val,
-                                Err(_msg) => {
-                                    return Err(FormulaParserError::IsotopeCountMalformed);
-                                }
-                            }
-                        } else {
-                            0
-                        };
-
-                        let elt = self.parse_element_from_string(string, periodic_table);
-                        let elt_spec = ElementSpecification {
-                            element: elt,
-                            isotope: isotope,
-                        };
-                        acc.inc(elt_spec, count);
-                        self.isotope_start = 0;
-                        self.isotope_end = 0;
-
-                        if c == '(' {
-                            self.paren_stack = 1;
-                            self.group_start = i + 1;
-                            self.state = FormulaParserState::Group;
-                        } else if c.is_ascii_alphabetic() && c.is_ascii_uppercase() {
-                            self.element_start = i;
-                            self.state = FormulaParserState::Element;
-                        } else {
-                            return Err(FormulaParserError::InvalidElement);
-                        }
-                    }
-                }
-                FormulaParserState::IsotopeToCount => {
-                    if c.is_numeric() {
-                        self.count_start = i;
-                        self.state = FormulaParserState::Count;
-                    } else {
-                        let elt = self.parse_element_from_string(string, periodic_table);
-                        let isotope: u16 =
-                            match string[self.isotope_start..self.isotope_end].parse::<u16>() {
-                                Ok(val) => val,
-                                Err(_msg) => {
-                                    return Err(FormulaParserError::IsotopeCountMalformed);
-                                }
-                            };
-                        let elt_spec = ElementSpecification {
-                            element: elt,
-                            isotope: isotope,
-                        };
-                        acc.inc(elt_spec, 1);
-                        self.isotope_start = 0;
-                        self.isotope_end = 0;
-
-                        if c == '(' {
-                            self.paren_stack += 1;
-                            self.group_start = i + 1;
-                            self.state = FormulaParserState::Group;
-                        } else if c.is_ascii_uppercase() {
-                            self.element_start = i;
-                            self.state = FormulaParserState::Element;
-                        } else {
-                            return Err(FormulaParserError::IsotopeCountMalformed);
-                        }
-                    }
-                }
-                FormulaParserState::GroupToGroupCount => {
-                    if !c.is_numeric() {
-                        let group = match Self::parse_with_table(
-                            &string[self.group_start..self.group_end],
-                            periodic_table,
-                        ) {
-                            Ok(grp) => grp,
-                            Err(err) => return Err(err),
-                        };
-                        self.group_start = 0;
-                        self.group_end = 0;
-                        acc += &group;
-                        if c == '(' {
-                            self.paren_stack = 1;
-                            self.group_start = i + 1;
-                            self.state = FormulaParserState::Group;
-                        } else if c.is_ascii_alphabetic() && c.is_ascii_uppercase() {
-                            self.element_start = i;
-                            self.state = FormulaParserState::Element;
-                        } else {
-                            return Err(FormulaParserError::InvalidElement);
-                        }
-                    } else {
-                        self.group_count_start = i;
-                        self.state = FormulaParserState::GroupCount;
-                    }
-                }
-                FormulaParserState::GroupCount => {
-                    if !c.is_numeric() {
-                        self.group_count_end = i;
-                        let group = match Self::parse_with_table(
-                            &string[self.group_start..self.group_end],
-                            periodic_table,
-                        ) {
-                            Ok(grp) => grp,
-                            Err(err) => return Err(err),
-                        };
-                        self.group_start = 0;
-                        self.group_end = 0;
-
-                        let group_count: i32 = match self.parse_group_count(string) {
-                            Ok(val) => val,
-                            Err(_msg) => {
-                                return Err(FormulaParserError::ElementCountMalformed);
-                            }
-                        };
-                        acc += &(&group * group_count);
-
-                        if c == '(' {
-                            self.paren_stack = 1;
-                            self.group_start = i + 1;
-                            self.state = FormulaParserState::Group;
-                        } else if c.is_ascii_alphabetic() && c.is_ascii_uppercase() {
-                            self.element_start = i;
-                            self.state = FormulaParserState::Element;
-                        } else {
-                            return Err(FormulaParserError::InvalidElement);
-                        }
-                    }
-                }
-            }
-        }
-
-        let i = n;
-        match self.state {
-            FormulaParserState::Element => {
-                self.element_end = i;
-                let elt = self.parse_element_from_string(string, periodic_table);
-                let elt_spec = ElementSpecification {
-                    element: elt,
-                    isotope: 0,
-                };
-                acc.inc(elt_spec, 1);
-            }
-            FormulaParserState::Count => {
-                self.count_end = i;
-                let count: i32 = match self.parse_element_count(string) {
-                    Ok(val) => val,
-                    Err(_msg) => {
-                        return Err(FormulaParserError::ElementCountMalformed);
-                    }
-                };
-                let isotope: u16 = if self.isotope_end != self.isotope_start {
-                    match string[self.isotope_start..self.isotope_end].parse::<u16>() {
-                        Ok(val) => val,
-                        Err(_msg) => {
-                            return Err(FormulaParserError::IsotopeCountMalformed);
-                        }
-                    }
-                } else {
-                    0
-                };
-                let elt = self.parse_element_from_string(string, periodic_table);
-                let elt_spec = ElementSpecification {
-                    element: elt,
-                    isotope: isotope,
-                };
-                acc.inc(elt_spec, count);
-            }
-            FormulaParserState::GroupToGroupCount => {
-                let group = match Self::parse_with_table(
-                    &string[self.group_start..self.group_end],
-                    periodic_table,
-                ) {
-                    Ok(grp) => grp,
-                    Err(err) => return Err(err),
-                };
-                acc += &group;
-            }
-            FormulaParserState::GroupCount => {
-                self.group_count_end = i;
-                let group = match Self::parse_with_table(
-                    &string[self.group_start..self.group_end],
-                    periodic_table,
-                ) {
-                    Ok(grp) => grp,
-                    Err(err) => return Err(err),
-                };
-                self.group_start = 0;
-                self.group_end = 0;
-
-                let group_count: i32 = match self.parse_group_count(string) {
-                    Ok(val) => val,
-                    Err(_msg) => {
-                        return Err(FormulaParserError::GroupCountMalformed);
-                    }
-                };
-                acc += &(&group * group_count);
+                None => FormulaParserError::new(err.kind.into(), string, err.span),
+            })?;
+        let mut acc = ChemicalComposition::default();
+        for atom in atoms {
+            let element = &periodic_table[&string[atom.symbol]];
+            if !atom.isotope_span.is_empty() && !element.has_isotope(atom.isotope) {
+                return Err(FormulaParserError::new(
+                    FormulaParserErrorKind::UnknownIsotope,
+                    string,
+                    atom.isotope_span,
+                ));
             }
-            _ => return Err(FormulaParserError::IncompleteFormula),
+            acc.inc(ElementSpecification::new(element, atom.isotope), atom.count);
         }
-        return Ok(acc.into());
+        Ok(acc.into())
     }
+}
 
-    // pub fn parse_formula_with_table(
-    //     &mut self,
-    //     string: &str,
-    //     periodic_table: &'lifespan PeriodicTable,
-    // ) -> Result<ChemicalComposition<'lifespan>, FormulaParserError> {
-    //     let mut acc = ChemicalComposition::new();
-    //     let n = string.len();
-
-    //     for (i, c) in string.char_indices() {
-    //         match self.state {
-    //             FormulaParserState::New => {
-    //                 if c.is_ascii_alphabetic() && c.is_ascii_uppercase() {
-    //                     self.element_start = i;
-    //                     self.state = FormulaParserState::Element;
-    //                 } else if c == '(' {
-    //                     self.paren_stack += 1;
-    //                     self.group_start = i + 1;
-    //                     self.state = FormulaParserState::Group;
-    //                 } else {
-    //                     return Err(FormulaParserError::InvalidStart);
-    //                 }
-    //             }
-    //             FormulaParserState::Group => {
-    //                 self.handle_group_state(c, i);
-    //             }
-    //             FormulaParserState::Element => {
-    //                 if c.is_ascii_alphabetic() {
-    //                     if c.is_uppercase() {
-    //                         self.element_end = i;
-    //                         let elt = self.parse_element_from_string(string, periodic_table);
-    //                         let elt_spec = ElementSpecification {
-    //                             element: elt,
-    //                             isotope: 0,
-    //                         };
-    //                         acc.inc(elt_spec, 1);
-    //                         self.state = FormulaParserState::Element;
-    //                         self.element_start = i;
-    //                         self.element_end = 0;
-    //                     }
-    //                 } else if c.is_numeric() {
-    //                     self.element_end = i;
-    //                     self.count_start = i;
-    //                     self.state = FormulaParserState::Count;
-    //                 } else if c == '[' {
-    //                     self.isotope_start = i + 1;
-    //                     self.state = FormulaParserState::Isotope;
-    //                 } else if c == '(' {
-    //                     self.element_end = i;
-    //                     let elt = self.parse_element_from_string(string, periodic_table);
-    //                     let elt_spec = ElementSpecification {
-    //                         element: elt,
-    //                         isotope: 0,
-    //                     };
-    //                     acc.inc(elt_spec, 1);
-
-    //                     self.paren_stack += 1;
-    //                     self.group_start = i + 1;
-    //                     self.state = FormulaParserState::Group;
-    //                 }
-    //             }
-    //             FormulaParserState::Isotope => {
-    //                 if c == ']' {
-    //                     self.isotope_end = i;
-    //                     self.state = FormulaParserState::IsotopeToCount;
-    //                 } else if !c.is_numeric() {
-    //                     return Err(FormulaParserError::IsotopeCountMalformed);
-    //                 }
-    //             }
-    //             FormulaParserState::Count => {
-    //                 if !c.is_numeric() {
-    //                     self.count_end = i;
-    //                     let count_parse = self.parse_element_count(string);
-    //                     let count: i32 = match count_parse {
-    //                         Ok(val) => val,
-    //                         Err(_msg) => {
-    //                             return Err(FormulaParserError::ElementCountMalformed);
-    //                         }
-    //                     };
-    //                     let isotope: u16 = if self.isotope_end != self.isotope_start {
-    //                         match string[self.isotope_start..self.isotope_end].parse::<u16>() {
-    //                             Ok(val) => val,
-    //                             Err(_msg) => {
-    //                                 return Err(FormulaParserError::IsotopeCountMalformed);
-    //                             }
-    //                         }
-    //                     } else {
-    //                         0
-    //                     };
-
-    //                     let elt = self.parse_element_from_string(string, periodic_table);
-    //                     let elt_spec = ElementSpecification {
-    //                         element: elt,
-    //                         isotope: isotope,
-    //                     };
-    //                     acc.inc(elt_spec, count);
-    //                     self.isotope_start = 0;
-    //                     self.isotope_end = 0;
-
-    //                     if c == '(' {
-    //                         self.paren_stack = 1;
-    //                         self.group_start = i + 1;
-    //                         self.state = FormulaParserState::Group;
-    //                     } else if c.is_ascii_alphabetic() && c.is_ascii_uppercase() {
-    //                         self.element_start = i;
-    //                         self.state = FormulaParserState::Element;
-    //                     } else {
-    //                         return Err(FormulaParserError::InvalidElement);
-    //                     }
-    //                 }
-    //             }
-    //             FormulaParserState::IsotopeToCount => {
-    //                 if c.is_numeric() {
-    //                     self.count_start = i;
-    //                     self.state = FormulaParserState::Count;
-    //                 } else {
-    //                     let elt = self.parse_element_from_string(string, periodic_table);
-    //                     let isotope: u16 =
-    //                         match string[self.isotope_start..self.isotope_end].parse::<u16>() {
-    //                             Ok(val) => val,
-    //                             Err(_msg) => {
-    //                                 return Err(FormulaParserError::IsotopeCountMalformed);
-    //                             }
-    //                         };
-    //                     let elt_spec = ElementSpecification {
-    //                         element: elt,
-    //                         isotope: isotope,
-    //                     };
-    //                     acc.inc(elt_spec, 1);
-    //                     self.isotope_start = 0;
-    //                     self.isotope_end = 0;
-
-    //                     if c == '(' {
-    //                         self.paren_stack += 1;
-    //                         self.group_start = i + 1;
-    //                         self.state = FormulaParserState::Group;
-    //                     } else if c.is_ascii_uppercase() {
-    //                         self.element_start = i;
-    //                         self.state = FormulaParserState::Element;
-    //                     } else {
-    //                         return Err(FormulaParserError::IsotopeCountMalformed);
-    //                     }
-    //                 }
-    //             }
-    //             FormulaParserState::GroupToGroupCount => {
-    //                 if !c.is_numeric() {
-    //                     let group = match Self::parse_with_table(
-    //                         &string[self.group_start..self.group_end],
-    //                         periodic_table,
-    //                     ) {
-    //                         Ok(grp) => grp,
-    //                         Err(err) => return Err(err),
-    //                     };
-    //                     self.group_start = 0;
-    //                     self.group_end = 0;
-    //                     acc += &group;
-    //                     if c == '(' {
-    //                         self.paren_stack = 1;
-    //                         self.group_start = i + 1;
-    //                         self.state = FormulaParserState::Group;
-    //                     } else if c.is_ascii_alphabetic() && c.is_ascii_uppercase() {
-    //                         self.element_start = i;
-    //                         self.state = FormulaParserState::Element;
-    //                     } else {
-    //                         return Err(FormulaParserError::InvalidElement);
-    //                     }
-    //                 } else {
-    //                     self.group_count_start = i;
-    //                     self.state = FormulaParserState::GroupCount;
-    //                 }
-    //             }
-    //             FormulaParserState::GroupCount => {
-    //                 if !c.is_numeric() {
-    //                     self.group_count_end = i;
-    //                     let group = match Self::parse_with_table(
-    //                         &string[self.group_start..self.group_end],
-    //                         periodic_table,
-    //                     ) {
-    //                         Ok(grp) => grp,
-    //                         Err(err) => return Err(err),
-    //                     };
-    //                     self.group_start = 0;
-    //                     self.group_end = 0;
-
-    //                     let group_count: i32 = match self.parse_group_count(string) {
-    //                         Ok(val) => val,
-    //                         Err(_msg) => {
-    //                             return Err(FormulaParserError::ElementCountMalformed);
-    //                         }
-    //                     };
-    //                     acc += &(&group * group_count);
-
-    //                     if c == '(' {
-    //                         self.paren_stack = 1;
-    //                         self.group_start = i + 1;
-    //                         self.state = FormulaParserState::Group;
-    //                     } else if c.is_ascii_alphabetic() && c.is_ascii_uppercase() {
-    //                         self.element_start = i;
-    //                         self.state = FormulaParserState::Element;
-    //                     } else {
-    //                         return Err(FormulaParserError::InvalidElement);
-    //                     }
-    //                 }
-    //             }
-    //         }
-    //     }
-
-    //     let i = n;
-    //     match self.state {
-    //         FormulaParserState::Element => {
-    //             self.element_end = i;
-    //             let elt = self.parse_element_from_string(string, periodic_table);
-    //             let elt_spec = ElementSpecification {
-    //                 element: elt,
-    //                 isotope: 0,
-    //             };
-    //             acc.inc(elt_spec, 1);
-    //         }
-    //         FormulaParserState::Count => {
-    //             self.count_end = i;
-    //             let count: i32 = match self.parse_element_count(string) {
-    //                 Ok(val) => val,
-    //                 Err(_msg) => {
-    //                     return Err(FormulaParserError::ElementCountMalformed);
-    //                 }
-    //             };
-    //             let isotope: u16 = if self.isotope_end != self.isotope_start {
-    //                 match string[self.isotope_start..self.isotope_end].parse::<u16>() {
-    //                     Ok(val) => val,
-    //                     Err(_msg) => {
-    //                         return Err(FormulaParserError::IsotopeCountMalformed);
-    //                     }
-    //                 }
-    //             } else {
-    //                 0
-    //             };
-    //             let elt = self.parse_element_from_string(string, periodic_table);
-    //             let elt_spec = ElementSpecification {
-    //                 element: elt,
-    //                 isotope: isotope,
-    //             };
-    //             acc.inc(elt_spec, count);
-    //         }
-    //         FormulaParserState::GroupToGroupCount => {
-    //             let group = match Self::parse_with_table(
-    //                 &string[self.group_start..self.group_end],
-    //                 periodic_table,
-    //             ) {
-    //                 Ok(grp) => grp,
-    //                 Err(err) => return Err(err),
-    //             };
-    //             acc += &group;
-    //         }
-    //         FormulaParserState::GroupCount => {
-    //             self.group_count_end = i;
-    //             let group = match Self::parse_with_table(
-    //                 &string[self.group_start..self.group_end],
-    //                 periodic_table,
-    //             ) {
-    //                 Ok(grp) => grp,
-    //                 Err(err) => return Err(err),
-    //             };
-    //             self.group_start = 0;
-    //             self.group_end = 0;
+/// The byte range of the character at byte `position` of `string`, empty at its end, for
+/// locating errors which record only where they occurred
+pub(crate) fn char_span(string: &str, position: usize) -> Range<usize> {
+    let width = string
+        .get(position..)
+        .and_then(|rest| rest.chars().next())
+        .map_or(0, char::len_utf8);
+    position..position + width
+}
 
-    //             let group_count: i32 = match self.parse_group_count(string) {
-    //                 Ok(val) => val,
-    //                 Err(_msg) => {
-    //                     return Err(FormulaParserError::GroupCountMalformed);
-    //                 }
-    //             };
-    //             acc += &(&group * group_count);
-    //         }
-    //         _ => return Err(FormulaParserError::IncompleteFormula),
-    //     }
-    //     return Ok(acc);
-    // }
+/// Build the error for an unknown element symbol at `span`, suggesting nearby symbols
+pub(crate) fn unknown_element(
+    string: &str,
+    span: Range<usize>,
+    periodic_table: &PeriodicTable,
+) -> FormulaParserError {
+    let mut err = FormulaParserError::new(FormulaParserErrorKind::UnknownElement, string, span);
+    err.suggestions = periodic_table
+        .nearest_symbols(err.token(), SUGGESTION_LIMIT)
+        .into_iter()
+        .map(String::from)
+        .collect();
+    err
 }
 
 pub fn parse_formula<'transient, 'lifespan: 'transient>(
@@ -724,9 +235,93 @@ mod test {
         assert_eq!(res[&oxygen], 1);
     }
 
+    #[test]
+    fn test_hydrates_and_brackets() {
+        let copper_sulfate = FormulaParser::parse("CuSO4·5H2O").unwrap();
+        assert_eq!(copper_sulfate.to_string(), "H10Cu1O9S1");
+        for formula in ["CuSO4.5H2O", "CuSO4*5H2O", "[CuSO4]·5(H2O)"] {
+            assert_eq!(FormulaParser::parse(formula).unwrap(), copper_sulfate, "{formula}");
+        }
+        let calcium = FormulaParser::parse("CaCl2*2H2O").unwrap();
+        assert_eq!(calcium["Cl"], 2);
+        assert_eq!(calcium["H"], 4);
+        assert_eq!(FormulaParser::parse("2H2O").unwrap()["H"], 4);
+
+        let neohexane = FormulaParser::parse("(CH3)3C(CH2)2").unwrap();
+        assert_eq!(neohexane.to_string(), "C6H13");
+        let ferrocyanide = FormulaParser::parse("K4[Fe(CN)6]·3H2O").unwrap();
+        assert_eq!(ferrocyanide["K"], 4);
+        assert_eq!(ferrocyanide["N"], 6);
+        assert_eq!(ferrocyanide["O"], 3);
+        let isotopes = FormulaParser::parse("[C[13]H3]2O").unwrap();
+        assert_eq!(isotopes[&ElementSpecification::parse("C[13]").unwrap()], 2);
+
+        let mut table = PERIODIC_TABLE.clone();
+        table.add(PERIODIC_TABLE["C"].enriched("C*", 13, 0.99).unwrap());
+        let labeled = FormulaParser::parse_with_table("C*6H12O6*H2O", &table).unwrap();
+        assert_eq!(labeled["C*"], 6);
+        assert_eq!(labeled["O"], 7);
+        assert!(FormulaParser::parse("CuSO4·").is_err());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = FormulaParser::parse("C6H12O6Xx").unwrap_err();
+        assert_eq!(err.kind, FormulaParserErrorKind::UnknownElement);
+        assert_eq!(err.span, 7..9);
+        assert_eq!(err.suggestions, vec!["Xe".to_string()]);
+        assert_eq!(
+            err.to_string(),
+            "unknown element `Xx` at byte 7, did you mean `Xe`?\nC6H12O6Xx\n       ^^"
+        );
+
+        let err = FormulaParser::parse("K4[Fe(CX)6]·3H2O").unwrap_err();
+        assert_eq!(err.token(), "X");
+        assert_eq!(err.formula, "K4[Fe(CX)6]·3H2O");
+        let err = FormulaParser::parse("CuSO4·5H2Q").unwrap_err();
+        assert_eq!(err.span, 10..11);
+        assert!(err.to_string().ends_with("CuSO4·5H2Q\n         ^"));
+
+        let err = FormulaParser::parse("C6H12(O6").unwrap_err();
+        assert_eq!(err.kind, FormulaParserErrorKind::IncompleteFormula);
+        assert_eq!(err.token(), "(");
+        for (formula, closer) in [("(C6H12O6]", 8), ("[C6H12O6)", 8), ("C(H2]3", 4), ("(CuSO4]·5H2O", 6)] {
+            let err = FormulaParser::parse(formula).unwrap_err();
+            assert_eq!(err.kind, FormulaParserErrorKind::IncompleteFormula, "{formula}");
+            assert_eq!(err.span, closer..closer + 1, "{formula}");
+        }
+        let err = FormulaParser::parse("C[1x]H4").unwrap_err();
+        assert_eq!(err.kind, FormulaParserErrorKind::IsotopeCountMalformed);
+        assert_eq!(err.token(), "x");
+        for (formula, token) in [("C[99]H4", "99"), ("C[0]H4", "0"), ("[C[13]H3]2O[2]", "2")] {
+            let err = FormulaParser::parse(formula).unwrap_err();
+            assert_eq!(err.kind, FormulaParserErrorKind::UnknownIsotope, "{formula}");
+            assert_eq!(err.token(), token, "{formula}");
+        }
+    }
+
+    #[cfg(feature = "macros")]
+    #[test]
+    fn test_formula_macro() {
+        use std::sync::LazyLock;
+
+        static HEXOSE: LazyLock<ChemicalComposition<'static>> =
+            LazyLock::new(|| crate::formula!("C6H12O6"));
+        assert_eq!(*HEXOSE, FormulaParser::parse("C6H12O6").unwrap());
+        for (expanded, formula) in [
+            (crate::formula!("K4[Fe(CN)6]·3H2O"), "K4[Fe(CN)6]·3H2O"),
+            (crate::formula!("C[13]2C4H12O6"), "C[13]2C4H12O6"),
+            (crate::formula!("(CH3)3C(CH2)2"), "(CH3)3C(CH2)2"),
+        ] {
+            assert_eq!(expanded, FormulaParser::parse(formula).unwrap(), "{formula}");
+        }
+    }
+
     #[test]
     fn test_to_string() {
         let res = FormulaParser::parse("H12O6C6N2").unwrap();
         assert_eq!(res.to_string(), "C6H12N2O6");
+        let res = FormulaParser::parse("CH3N[15]").unwrap();
+        assert_eq!(res.to_string(), "C1H3N[15]1");
     }
 }
@@ -0,0 +1,826 @@
+//! Write formulas in the conventions required by different reports, databases and
+//! typesetting targets, and read them back.
+//!
+//! A [`FormulaFormatter`] chooses the order of elements, how fixed isotopes are written and
+//! how counts and mass numbers are marked up. [`FormulaFormatter::parse`] reads any formula
+//! written by a formatter, whatever its style. Unimod compositions and ProForma formulas, as
+//! used for modification deltas, are read and written by [`ChemicalComposition::parse_unimod`],
+//! [`ChemicalComposition::parse_proforma`] and their counterparts.
+//!
+//! ```rust
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use chemical_elements::ChemicalComposition;
+//! use chemical_elements::formula_format::{FormulaFormatter, FormulaMarkup, IsotopeStyle};
+//!
+//! let glucose = ChemicalComposition::parse("C[13]2C4H12O6")?;
+//! assert_eq!(FormulaFormatter::hill().format(&glucose), "C4C[13]2H12O6");
+//! assert_eq!(FormulaFormatter::proforma().format(&glucose), "C4 [13C2] H12 O6");
+//!
+//! let html = FormulaFormatter::hill()
+//!     .isotopes(IsotopeStyle::Prefix)
+//!     .markup(FormulaMarkup::Html);
+//! let text = html.format(&glucose);
+//! assert_eq!(text, "C<sub>4</sub><sup>13</sup>C<sub>2</sub>H<sub>12</sub>O<sub>6</sub>");
+//! assert_eq!(FormulaFormatter::parse(&text)?, glucose);
+//! # Ok(())
+//! # }
+//! ```
+use std::ops::Range;
+
+use crate::abstract_composition::{ChemicalComposition, ChemicalCompositionRef};
+use crate::formula::{unknown_element, FormulaParserError, FormulaParserErrorKind};
+use crate::table::PERIODIC_TABLE;
+use crate::{ElementSpecification, PeriodicTable};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// The order elements are written in
+pub enum ElementOrder {
+    /// The Hill system: carbon, then hydrogen, then the other elements alphabetically, or
+    /// every element alphabetically if there is no carbon
+    #[default]
+    Hill,
+    /// Every element alphabetically, including carbon and hydrogen
+    Alphabetical,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// How an element with a fixed isotope is written, shown for two <sup>13</sup>C atoms
+pub enum IsotopeStyle {
+    /// `C[13]2`, as read by [`ChemicalComposition::parse`]
+    #[default]
+    Suffix,
+    /// `[13C]2`
+    Bracketed,
+    /// `13C2`
+    Prefix,
+    /// `[13C2]`, as in ProForma formulas
+    ProForma,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// How counts and mass numbers are typeset
+pub enum FormulaMarkup {
+    /// Plain digits, `C6H12O6`
+    #[default]
+    Plain,
+    /// Unicode subscript counts and superscript mass numbers, `C₆H₁₂O₆`
+    Unicode,
+    /// LaTeX upright symbols with subscripts, `\mathrm{C}_{6}\mathrm{H}_{12}\mathrm{O}_{6}`, and
+    /// mass numbers as pre-superscripts on an empty group, `{}^{13}\mathrm{C}`
+    LaTeX,
+    /// HTML `<sub>` and `<sup>` elements, `C<sub>6</sub>H<sub>12</sub>O<sub>6</sub>`
+    Html,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/**
+Writes a composition's formula in a configurable style.
+
+Elements with a fixed isotope are written after the element's unfixed atoms, lightest
+isotope first. Elements with a count of zero are omitted, and negative counts are written
+with a `-` sign as in ProForma formulas.
+*/
+pub struct FormulaFormatter {
+    pub order: ElementOrder,
+    pub isotopes: IsotopeStyle,
+    pub markup: FormulaMarkup,
+    /// Separate elements with spaces
+    pub separated: bool,
+    /// Write counts of one, like `C1`, instead of omitting them
+    pub explicit_ones: bool,
+    /// Write counts in parentheses, like `C(2)`, as in Unimod compositions
+    pub parenthesized: bool,
+}
+
+impl FormulaFormatter {
+    /// A formatter for the Hill system with plain text counts
+    pub fn hill() -> Self {
+        Self::default()
+    }
+
+    /// A formatter writing every element alphabetically with plain text counts
+    pub fn alphabetical() -> Self {
+        Self::default().order(ElementOrder::Alphabetical)
+    }
+
+    /// A formatter for ProForma formulas, space-separated with isotopes like `[13C2]`
+    pub fn proforma() -> Self {
+        Self::default()
+            .isotopes(IsotopeStyle::ProForma)
+            .separated(true)
+    }
+
+    /// A formatter for Unimod compositions, like `C(2) H(2) 13C(-1) O`
+    pub fn unimod() -> Self {
+        Self::default()
+            .isotopes(IsotopeStyle::Prefix)
+            .separated(true)
+            .parenthesized(true)
+    }
+
+    pub fn order(mut self, order: ElementOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    pub fn isotopes(mut self, isotopes: IsotopeStyle) -> Self {
+        self.isotopes = isotopes;
+        self
+    }
+
+    pub fn markup(mut self, markup: FormulaMarkup) -> Self {
+        self.markup = markup;
+        self
+    }
+
+    pub fn separated(mut self, separated: bool) -> Self {
+        self.separated = separated;
+        self
+    }
+
+    pub fn explicit_ones(mut self, explicit_ones: bool) -> Self {
+        self.explicit_ones = explicit_ones;
+        self
+    }
+
+    pub fn parenthesized(mut self, parenthesized: bool) -> Self {
+        self.parenthesized = parenthesized;
+        self
+    }
+
+    /// Write the formula of `composition` in this formatter's style
+    pub fn format<'inner, 'lifespan: 'inner, C>(&self, composition: &'inner C) -> String
+    where
+        &'inner C: Into<ChemicalCompositionRef<'inner, 'lifespan>> + 'inner,
+    {
+        let composition: ChemicalCompositionRef<'inner, 'lifespan> = composition.into();
+        let mut items: Vec<(&ElementSpecification, i32)> = composition
+            .iter()
+            .filter(|(_, count)| **count != 0)
+            .map(|(key, count)| (key, *count))
+            .collect();
+        let carbon_first = self.order == ElementOrder::Hill
+            && items.iter().any(|(key, _)| key.element.symbol == "C");
+        let rank = |key: &ElementSpecification| match key.element.symbol.as_str() {
+            "C" if carbon_first => 0,
+            "H" if carbon_first => 1,
+            _ => 2,
+        };
+        items.sort_by(|(a, _), (b, _)| {
+            (rank(a), &a.element.symbol, a.isotope).cmp(&(rank(b), &b.element.symbol, b.isotope))
+        });
+
+        // Plain prefixed mass numbers would run into the preceding count, like `C1013C2`
+        let separated = self.separated
+            || (self.isotopes == IsotopeStyle::Prefix
+                && self.markup == FormulaMarkup::Plain
+                && !self.parenthesized
+                && items.iter().any(|(key, _)| key.isotope != 0));
+        let tokens: Vec<String> = items
+            .into_iter()
+            .map(|(key, count)| self.format_element(key, count))
+            .collect();
+        tokens.join(if separated { " " } else { "" })
+    }
+
+    fn format_element(&self, key: &ElementSpecification, count: i32) -> String {
+        let symbol = match self.markup {
+            FormulaMarkup::LaTeX => format!("\\mathrm{{{}}}", key.element.symbol),
+            _ => key.element.symbol.clone(),
+        };
+        let count = self.format_count(count);
+        if key.isotope == 0 {
+            return format!("{symbol}{count}");
+        }
+        let mass_number = self.format_mass_number(key.isotope);
+        match self.isotopes {
+            IsotopeStyle::Suffix => format!("{symbol}[{mass_number}]{count}"),
+            IsotopeStyle::Bracketed => format!("[{mass_number}{symbol}]{count}"),
+            IsotopeStyle::Prefix => format!("{mass_number}{symbol}{count}"),
+            IsotopeStyle::ProForma => format!("[{mass_number}{symbol}{count}]"),
+        }
+    }
+
+    fn format_count(&self, count: i32) -> String {
+        if count == 1 && !self.explicit_ones {
+            return String::new();
+        }
+        let count = count.to_string();
+        let count = match self.markup {
+            FormulaMarkup::Plain => count,
+            FormulaMarkup::Unicode => count.chars().map(subscript).collect(),
+            FormulaMarkup::LaTeX => format!("_{{{count}}}"),
+            FormulaMarkup::Html => format!("<sub>{count}</sub>"),
+        };
+        if self.parenthesized {
+            format!("({count})")
+        } else {
+            count
+        }
+    }
+
+    fn format_mass_number(&self, mass_number: u16) -> String {
+        let mass_number = mass_number.to_string();
+        match self.markup {
+            FormulaMarkup::Plain => mass_number,
+            FormulaMarkup::Unicode => mass_number.chars().map(superscript).collect(),
+            // Superscripting an empty group keeps the mass number off the preceding count
+            FormulaMarkup::LaTeX => format!("{{}}^{{{mass_number}}}"),
+            FormulaMarkup::Html => format!("<sup>{mass_number}</sup>"),
+        }
+    }
+
+    /**
+    Parse a formula written in any of the styles a [`FormulaFormatter`] writes using the
+    global [`PeriodicTable`].
+
+    Unlike [`ChemicalComposition::parse`], digits before an element symbol are its mass
+    number, not a multiplier, and groups are not supported.
+    */
+    pub fn parse(string: &str) -> Result<ChemicalComposition<'static>, FormulaParserError> {
+        Self::parse_with(string, &PERIODIC_TABLE)
+    }
+
+    /// Parse a formula written in any of the styles a [`FormulaFormatter`] writes using the
+    /// specified [`PeriodicTable`]
+    pub fn parse_with<'lifespan>(
+        string: &str,
+        periodic_table: &'lifespan PeriodicTable,
+    ) -> Result<ChemicalComposition<'lifespan>, FormulaParserError> {
+        let normalized = Normalized::new(string);
+        parse_flat(&normalized.text, periodic_table).map_err(|mut err| {
+            err.span = normalized.original_span(&err.span);
+            err.formula = string.to_string();
+            err
+        })
+    }
+}
+
+/// The tag which introduces a ProForma formula
+const PROFORMA_PREFIX: &str = "Formula:";
+
+impl<'lifespan> ChemicalComposition<'lifespan> {
+    /**
+    Parse a Unimod composition, like `H(2) C(2) O`, `H(-1) N(-1) 18O` or `C(6)13C(-6)`,
+    using the global [`PeriodicTable`].
+
+    Unimod's building block abbreviations, like `Hex` or `Me`, are not element symbols and
+    are not supported.
+    */
+    pub fn parse_unimod(
+        string: &str,
+    ) -> Result<ChemicalComposition<'lifespan>, FormulaParserError> {
+        FormulaFormatter::parse(string)
+    }
+
+    /// Parse a Unimod composition using the specified [`PeriodicTable`]
+    pub fn parse_unimod_with(
+        string: &str,
+        periodic_table: &'lifespan PeriodicTable,
+    ) -> Result<ChemicalComposition<'lifespan>, FormulaParserError> {
+        FormulaFormatter::parse_with(string, periodic_table)
+    }
+
+    /// Write this composition as a Unimod composition. See [`FormulaFormatter::unimod`]
+    pub fn to_unimod(&self) -> String {
+        FormulaFormatter::unimod().format(self)
+    }
+
+    /**
+    Parse a ProForma formula, like `Formula:C12H20O2[13C2]` or `[Formula:[13C2]C-2H2N]`,
+    using the global [`PeriodicTable`]. The `Formula:` tag and its enclosing brackets are
+    optional.
+    */
+    pub fn parse_proforma(
+        string: &str,
+    ) -> Result<ChemicalComposition<'lifespan>, FormulaParserError> {
+        Self::parse_proforma_with(string, &PERIODIC_TABLE)
+    }
+
+    /// Parse a ProForma formula using the specified [`PeriodicTable`]
+    pub fn parse_proforma_with(
+        string: &str,
+        periodic_table: &'lifespan PeriodicTable,
+    ) -> Result<ChemicalComposition<'lifespan>, FormulaParserError> {
+        let mut offset = string.len() - string.trim_start().len();
+        let mut body = string.trim();
+        if let Some(tag) = body.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            if strip_proforma_prefix(tag).is_some() {
+                body = tag;
+                offset += 1;
+            }
+        }
+        if let Some(formula) = strip_proforma_prefix(body) {
+            offset += PROFORMA_PREFIX.len();
+            body = formula;
+        }
+        FormulaFormatter::parse_with(body, periodic_table).map_err(|err| err.within(string, offset))
+    }
+
+    /// Write this composition as a ProForma formula with its `Formula:` tag. See
+    /// [`FormulaFormatter::proforma`]
+    pub fn to_proforma(&self) -> String {
+        format!(
+            "{PROFORMA_PREFIX}{}",
+            FormulaFormatter::proforma().format(self)
+        )
+    }
+}
+
+fn strip_proforma_prefix(string: &str) -> Option<&str> {
+    let prefix = string.get(..PROFORMA_PREFIX.len())?;
+    prefix
+        .eq_ignore_ascii_case(PROFORMA_PREFIX)
+        .then(|| &string[PROFORMA_PREFIX.len()..])
+}
+
+fn subscript(c: char) -> char {
+    match c {
+        '0'..='9' => char::from_u32('₀' as u32 + c.to_digit(10).unwrap()).unwrap(),
+        '-' => '₋',
+        _ => c,
+    }
+}
+
+fn superscript(c: char) -> char {
+    match c {
+        '1' => '¹',
+        '2' => '²',
+        '3' => '³',
+        '0' | '4'..='9' => char::from_u32('⁰' as u32 + c.to_digit(10).unwrap()).unwrap(),
+        _ => c,
+    }
+}
+
+fn from_subscript(c: char) -> Option<char> {
+    match c {
+        '₀'..='₉' => char::from_digit(c as u32 - '₀' as u32, 10),
+        '₋' => Some('-'),
+        _ => None,
+    }
+}
+
+fn from_superscript(c: char) -> Option<u32> {
+    match c {
+        '¹' => Some(1),
+        '²' => Some(2),
+        '³' => Some(3),
+        '⁰' | '⁴'..='⁹' => Some(c as u32 - '⁰' as u32),
+        _ => None,
+    }
+}
+
+/**
+A formula with its markup removed, leaving counts as plain digits and mass numbers as
+superscript digits if they were marked up, remembering where each character came from.
+*/
+struct Normalized {
+    text: String,
+    /// The span in the original string of each byte of `text`
+    origins: Vec<Range<usize>>,
+    length: usize,
+}
+
+impl Normalized {
+    fn new(string: &str) -> Self {
+        let mut normalized = Self {
+            text: String::with_capacity(string.len()),
+            origins: Vec::with_capacity(string.len()),
+            length: string.len(),
+        };
+        let mut in_superscript = false;
+        let mut in_latex_group = false;
+        let mut i = 0;
+        while i < string.len() {
+            let rest = &string[i..];
+            let tag = ["<sup>", "</sup>", "<sub>", "</sub>", "^{", "_{", "{}", "\\mathrm{"]
+                .into_iter()
+                .find(|tag| rest.starts_with(tag));
+            if let Some(tag) = tag {
+                in_superscript = matches!(tag, "<sup>" | "^{");
+                in_latex_group = tag.ends_with('{');
+                i += tag.len();
+                continue;
+            }
+            let c = rest.chars().next().unwrap();
+            let origin = i..i + c.len_utf8();
+            i = origin.end;
+            if c == '}' && in_latex_group {
+                in_superscript = false;
+                in_latex_group = false;
+                continue;
+            }
+            let c = match from_subscript(c) {
+                Some(c) => c,
+                None if in_superscript => superscript(c),
+                None => c,
+            };
+            normalized.push(c, origin);
+        }
+        normalized
+    }
+
+    fn push(&mut self, c: char, origin: Range<usize>) {
+        self.text.push(c);
+        for _ in 0..c.len_utf8() {
+            self.origins.push(origin.clone());
+        }
+    }
+
+    fn original_span(&self, span: &Range<usize>) -> Range<usize> {
+        let start = self
+            .origins
+            .get(span.start)
+            .map(|origin| origin.start)
+            .unwrap_or(self.length);
+        if span.is_empty() {
+            return start..start;
+        }
+        let end = self
+            .origins
+            .get(span.end - 1)
+            .map(|origin| origin.end)
+            .unwrap_or(self.length);
+        start..end
+    }
+}
+
+/// Parse a normalized formula made up only of elements, each with an optional isotope
+/// written before or after its symbol and an optional, possibly negative, count
+fn parse_flat<'lifespan>(
+    string: &str,
+    periodic_table: &'lifespan PeriodicTable,
+) -> Result<ChemicalComposition<'lifespan>, FormulaParserError> {
+    let error = |kind, span| FormulaParserError::new(kind, string, span);
+    let mut acc = ChemicalComposition::default();
+    let mut cursor = Cursor {
+        string,
+        position: 0,
+    };
+    if string.trim().is_empty() {
+        return Err(error(FormulaParserErrorKind::IncompleteFormula, 0..0));
+    }
+    loop {
+        cursor.take_while(char::is_whitespace);
+        let Some(c) = cursor.peek() else {
+            break;
+        };
+        let token_start = cursor.position;
+        let bracketed = c == '[';
+        if bracketed {
+            cursor.advance();
+        }
+        let mut mass_number =
+            cursor.take_while(|c| c.is_ascii_digit() || from_superscript(c).is_some());
+        let mut isotope = parse_mass_number(&string[mass_number.clone()]).ok_or_else(|| {
+            error(
+                FormulaParserErrorKind::IsotopeCountMalformed,
+                mass_number.clone(),
+            )
+        })?;
+
+        let symbol_start = cursor.position;
+        match cursor.peek() {
+            Some(c) if c.is_ascii_uppercase() => cursor.advance(),
+            Some(c) => {
+                let at = cursor.position;
+                return Err(error(
+                    FormulaParserErrorKind::InvalidElement,
+                    at..at + c.len_utf8(),
+                ));
+            }
+            None => {
+                return Err(error(
+                    FormulaParserErrorKind::IncompleteFormula,
+                    token_start..string.len(),
+                ))
+            }
+        }
+        cursor.take_while(|c| c.is_ascii_lowercase());
+        let mut symbol = symbol_start..cursor.position;
+        if cursor.peek() == Some('*')
+            && periodic_table
+                .get(&format!("{}*", &string[symbol.clone()]))
+                .is_some()
+        {
+            cursor.advance();
+            symbol.end = cursor.position;
+        }
+        let element = periodic_table
+            .get(&string[symbol.clone()])
+            .ok_or_else(|| unknown_element(string, symbol.clone(), periodic_table))?;
+
+        if isotope == 0 && cursor.peek() == Some('[') {
+            cursor.advance();
+            let suffix = cursor.take_while(|c| c.is_ascii_digit() || from_superscript(c).is_some());
+            isotope = parse_mass_number(&string[suffix.clone()])
+                .filter(|isotope| *isotope != 0)
+                .ok_or_else(|| {
+                    error(
+                        FormulaParserErrorKind::IsotopeCountMalformed,
+                        suffix.clone(),
+                    )
+                })?;
+            mass_number = suffix;
+            cursor.expect(']', token_start)?;
+        }
+        if !mass_number.is_empty() && !element.has_isotope(isotope) {
+            return Err(error(FormulaParserErrorKind::UnknownIsotope, mass_number));
+        }
+        let mut count = cursor.count()?;
+        if bracketed {
+            cursor.expect(']', token_start)?;
+            let multiplier_start = cursor.position;
+            if let Some(outer) = cursor.count()? {
+                let multiplier = multiplier_start..cursor.position;
+                count = Some(count.unwrap_or(1).checked_mul(outer).ok_or_else(|| {
+                    error(FormulaParserErrorKind::GroupCountMalformed, multiplier)
+                })?);
+            }
+        }
+        acc.inc(
+            ElementSpecification::new(element, isotope),
+            count.unwrap_or(1),
+        );
+    }
+    Ok(acc)
+}
+
+/// Parse a mass number of plain or superscript digits, or 0 if there is none
+fn parse_mass_number(digits: &str) -> Option<u16> {
+    if digits.is_empty() {
+        return Some(0);
+    }
+    digits.chars().try_fold(0u16, |acc, c| {
+        let digit = c.to_digit(10).or_else(|| from_superscript(c))?;
+        acc.checked_mul(10)?.checked_add(digit as u16)
+    })
+}
+
+struct Cursor<'a> {
+    string: &'a str,
+    position: usize,
+}
+
+impl Cursor<'_> {
+    fn peek(&self) -> Option<char> {
+        self.string[self.position..].chars().next()
+    }
+
+    fn advance(&mut self) {
+        if let Some(c) = self.peek() {
+            self.position += c.len_utf8();
+        }
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> Range<usize> {
+        let start = self.position;
+        while self.peek().is_some_and(&predicate) {
+            self.advance();
+        }
+        start..self.position
+    }
+
+    fn expect(&mut self, expected: char, token_start: usize) -> Result<(), FormulaParserError> {
+        if self.peek() == Some(expected) {
+            self.advance();
+            Ok(())
+        } else {
+            Err(FormulaParserError::new(
+                FormulaParserErrorKind::IncompleteFormula,
+                self.string,
+                token_start..self.position,
+            ))
+        }
+    }
+
+    /// Read an optional, possibly negative, count, which may be in parentheses
+    fn count(&mut self) -> Result<Option<i32>, FormulaParserError> {
+        if self.peek() == Some('(') {
+            let open = self.position;
+            self.advance();
+            return match self.count()? {
+                Some(count) => {
+                    self.expect(')', open)?;
+                    Ok(Some(count))
+                }
+                None => Err(FormulaParserError::new(
+                    FormulaParserErrorKind::ElementCountMalformed,
+                    self.string,
+                    open..self.position,
+                )),
+            };
+        }
+        let start = self.position;
+        if self.peek() == Some('-') {
+            self.advance();
+        }
+        self.take_while(|c| c.is_ascii_digit());
+        let span = start..self.position;
+        if span.is_empty() {
+            return Ok(None);
+        }
+        self.string[span.clone()]
+            .parse::<i32>()
+            .map(Some)
+            .map_err(|_| {
+                FormulaParserError::new(
+                    FormulaParserErrorKind::ElementCountMalformed,
+                    self.string,
+                    span,
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_styles() {
+        let composition = ChemicalComposition::parse("C[13]2C4H12O6S").unwrap();
+        let salt = ChemicalComposition::parse("NaCl").unwrap();
+        let water = ChemicalComposition::parse("H2O").unwrap();
+
+        assert_eq!(
+            FormulaFormatter::hill().format(&composition),
+            "C4C[13]2H12O6S"
+        );
+        assert_eq!(FormulaFormatter::hill().format(&water), "H2O");
+        assert_eq!(FormulaFormatter::hill().format(&salt), "ClNa");
+        assert_eq!(
+            FormulaFormatter::alphabetical().format(&composition),
+            "C4C[13]2H12O6S"
+        );
+        assert_eq!(
+            FormulaFormatter::alphabetical().format(&ChemicalComposition::parse("CH3Br").unwrap()),
+            "BrCH3"
+        );
+        assert_eq!(
+            FormulaFormatter::hill().explicit_ones(true).format(&salt),
+            "Cl1Na1"
+        );
+
+        let hill = FormulaFormatter::hill();
+        for (style, expected) in [
+            (IsotopeStyle::Bracketed, "C4[13C]2H12O6S"),
+            (IsotopeStyle::Prefix, "C4 13C2 H12 O6 S"),
+            (IsotopeStyle::ProForma, "C4[13C2]H12O6S"),
+        ] {
+            assert_eq!(hill.isotopes(style).format(&composition), expected);
+        }
+        assert_eq!(
+            FormulaFormatter::proforma().format(&composition),
+            "C4 [13C2] H12 O6 S"
+        );
+
+        let prefix = hill.isotopes(IsotopeStyle::Prefix);
+        for (markup, expected) in [
+            (FormulaMarkup::Unicode, "C₄¹³C₂H₁₂O₆S"),
+            (
+                FormulaMarkup::LaTeX,
+                r"\mathrm{C}_{4}{}^{13}\mathrm{C}_{2}\mathrm{H}_{12}\mathrm{O}_{6}\mathrm{S}",
+            ),
+            (
+                FormulaMarkup::Html,
+                "C<sub>4</sub><sup>13</sup>C<sub>2</sub>H<sub>12</sub>O<sub>6</sub>S",
+            ),
+        ] {
+            assert_eq!(prefix.markup(markup).format(&composition), expected);
+            assert_eq!(FormulaFormatter::parse(expected).unwrap(), composition);
+        }
+
+        let mut loss = ChemicalComposition::default();
+        loss["H"] = -2;
+        loss["O"] = -1;
+        assert_eq!(FormulaFormatter::proforma().format(&loss), "H-2 O-1");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let composition = ChemicalComposition::parse("C[13]2C4H12O6SN[15]").unwrap();
+        let mut loss = composition.clone();
+        loss["H"] = -2;
+        for composition in [composition, loss] {
+            for order in [ElementOrder::Hill, ElementOrder::Alphabetical] {
+                for isotopes in [
+                    IsotopeStyle::Suffix,
+                    IsotopeStyle::Bracketed,
+                    IsotopeStyle::Prefix,
+                    IsotopeStyle::ProForma,
+                ] {
+                    for markup in [
+                        FormulaMarkup::Plain,
+                        FormulaMarkup::Unicode,
+                        FormulaMarkup::LaTeX,
+                        FormulaMarkup::Html,
+                    ] {
+                        for separated in [false, true] {
+                            let formatter = FormulaFormatter {
+                                order,
+                                isotopes,
+                                markup,
+                                separated,
+                                explicit_ones: separated,
+                                parenthesized: separated && isotopes == IsotopeStyle::Prefix,
+                            };
+                            let text = formatter.format(&composition);
+                            assert_eq!(
+                                FormulaFormatter::parse(&text).unwrap(),
+                                composition,
+                                "{text}"
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_notations() {
+        let acetyl = ChemicalComposition::parse_unimod("H(2) C(2) O").unwrap();
+        assert_eq!(acetyl, ChemicalComposition::parse("C2H2O").unwrap());
+        assert_eq!(acetyl.to_unimod(), "C(2) H(2) O");
+
+        let deamidation = ChemicalComposition::parse_unimod("H(-1) N(-1) 18O").unwrap();
+        assert_eq!(deamidation["H"], -1);
+        assert_eq!(
+            deamidation[&ElementSpecification::parse("O[18]").unwrap()],
+            1
+        );
+        assert!((deamidation.mass() - 2.988261).abs() < 1e-5);
+        let label = ChemicalComposition::parse_unimod("C(6)13C(-6)").unwrap();
+        assert_eq!(label.to_unimod(), "C(6) 13C(-6)");
+        assert_eq!(
+            ChemicalComposition::parse_unimod(&label.to_unimod()).unwrap(),
+            label
+        );
+
+        let proforma = ChemicalComposition::parse_proforma("Formula:C12H20O2[13C2]").unwrap();
+        assert_eq!(proforma["C"], 12);
+        assert_eq!(proforma.to_proforma(), "Formula:C12 [13C2] H20 O2");
+        assert_eq!(
+            ChemicalComposition::parse_proforma(&proforma.to_proforma()).unwrap(),
+            proforma
+        );
+        for tag in [
+            "[Formula:C12H20O2[13C2]]",
+            "formula:C12 H20 O2 [13C2]",
+            "C12H20O2[13C2]",
+        ] {
+            assert_eq!(
+                ChemicalComposition::parse_proforma(tag).unwrap(),
+                proforma,
+                "{tag}"
+            );
+        }
+        let swap = ChemicalComposition::parse_proforma("[Formula:[13C2][12C-2]H2N]").unwrap();
+        assert_eq!(swap[&ElementSpecification::parse("C[12]").unwrap()], -2);
+
+        let err = ChemicalComposition::parse_proforma("[Formula:C12Hx]").unwrap_err();
+        assert_eq!(err.token(), "Hx");
+        assert_eq!(err.span, 12..14);
+        assert!(ChemicalComposition::parse_unimod("Hex(1) HexNAc").is_err());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = FormulaFormatter::parse("C<sub>6</sub>Xx<sub>2</sub>").unwrap_err();
+        assert_eq!(err.kind, FormulaParserErrorKind::UnknownElement);
+        assert_eq!(err.token(), "Xx");
+        let err = FormulaFormatter::parse("C6 [13C2").unwrap_err();
+        assert_eq!(err.kind, FormulaParserErrorKind::IncompleteFormula);
+        assert_eq!(err.token(), "[13C2");
+        assert!(FormulaFormatter::parse("C6(H2O)").is_err());
+
+        let err = ChemicalComposition::parse_proforma("[13C100000]100000").unwrap_err();
+        assert_eq!(err.kind, FormulaParserErrorKind::GroupCountMalformed);
+        assert_eq!(err.token(), "100000");
+        assert_eq!(err.span, 11..17);
+        for (formula, token) in [
+            ("99C(2)", "99"),
+            ("0C", "0"),
+            ("C[99]2", "99"),
+            ("<sup>99</sup>C", "99"),
+        ] {
+            let err = ChemicalComposition::parse_unimod(formula).unwrap_err();
+            assert_eq!(
+                err.kind,
+                FormulaParserErrorKind::UnknownIsotope,
+                "{formula}"
+            );
+            assert_eq!(err.token(), token, "{formula}");
+        }
+        for formula in ["[99C2]", "Formula:C12[99C2]"] {
+            let err = ChemicalComposition::parse_proforma(formula).unwrap_err();
+            assert_eq!(
+                err.kind,
+                FormulaParserErrorKind::UnknownIsotope,
+                "{formula}"
+            );
+            assert_eq!(err.token(), "99", "{formula}");
+        }
+    }
+}
@@ -0,0 +1,506 @@
+//! Compute the elemental composition of glycans from monosaccharide composition strings
+//! like `HexNAc(4)Hex(5)Fuc(1)NeuAc(2)`, with reducing end forms and permethylation.
+//!
+//! ```rust
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use chemical_elements::ChemicalComposition;
+//! use chemical_elements::glycan::{glycan_composition, GlycanComposition, ReducingEnd};
+//!
+//! let composition = glycan_composition("HexNAc(2)Hex(3)")?;
+//! assert_eq!(composition, ChemicalComposition::parse("C34H58N2O26")?);
+//!
+//! let glycan = GlycanComposition::parse("HexNAc(4)Hex(5)Fuc(1)NeuAc(2)")?
+//!     .with_reducing_end(ReducingEnd::Reduced)
+//!     .permethylated(true);
+//! assert!((glycan.composition().mass() - 2959.5138).abs() < 1e-3);
+//! # Ok(())
+//! # }
+//! ```
+use std::fmt::{self, Display};
+use std::ops::Range;
+use std::str::FromStr;
+
+use chemical_elements_grammar::write_diagnostic;
+
+use crate::formula::char_span;
+use crate::table::PERIODIC_TABLE;
+use crate::{ChemicalComposition, ElementSpecification, PeriodicTable};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// A monosaccharide or substituent in a glycan composition
+pub enum Monosaccharide {
+    Hex,
+    HexNAc,
+    DHex,
+    NeuAc,
+    NeuGc,
+    Pent,
+    HexA,
+    Kdn,
+    Sulfate,
+    Phosphate,
+}
+
+impl Monosaccharide {
+    pub const ALL: [Monosaccharide; 10] = [
+        Self::Hex,
+        Self::HexNAc,
+        Self::DHex,
+        Self::NeuAc,
+        Self::NeuGc,
+        Self::Pent,
+        Self::HexA,
+        Self::Kdn,
+        Self::Sulfate,
+        Self::Phosphate,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Hex => "Hex",
+            Self::HexNAc => "HexNAc",
+            Self::DHex => "dHex",
+            Self::NeuAc => "NeuAc",
+            Self::NeuGc => "NeuGc",
+            Self::Pent => "Pent",
+            Self::HexA => "HexA",
+            Self::Kdn => "Kdn",
+            Self::Sulfate => "Sulfate",
+            Self::Phosphate => "Phosphate",
+        }
+    }
+
+    /// Other names accepted when parsing, such as specific monosaccharides standing for
+    /// their generic class
+    fn aliases(&self) -> &'static [&'static str] {
+        match self {
+            Self::Hex => &["Glc", "Gal", "Man"],
+            Self::HexNAc => &["GlcNAc", "GalNAc"],
+            Self::DHex => &["Fuc"],
+            Self::NeuAc => &["Neu5Ac", "SA"],
+            Self::NeuGc => &["Neu5Gc"],
+            Self::Pent => &["Xyl", "Ara"],
+            Self::HexA => &["GlcA", "IdoA"],
+            Self::Kdn => &[],
+            Self::Sulfate => &["Sulfo", "Sulpho", "Sulph"],
+            Self::Phosphate => &["Phospho", "Phos"],
+        }
+    }
+
+    /// The formula of the residue, the free monosaccharide less the water lost forming the
+    /// glycosidic bond, or the group a substituent adds
+    fn formula(&self) -> &'static str {
+        match self {
+            Self::Hex => "C6H10O5",
+            Self::HexNAc => "C8H13NO5",
+            Self::DHex => "C6H10O4",
+            Self::NeuAc => "C11H17NO8",
+            Self::NeuGc => "C11H17NO9",
+            Self::Pent => "C5H8O4",
+            Self::HexA => "C6H8O6",
+            Self::Kdn => "C9H14O8",
+            Self::Sulfate => "SO3",
+            Self::Phosphate => "HPO3",
+        }
+    }
+
+    /// The number of methyl groups the residue gains when permethylated. Substituents
+    /// occupy a hydroxyl that would otherwise be methylated.
+    fn methylation_sites(&self) -> i32 {
+        match self {
+            Self::Hex | Self::HexNAc | Self::HexA => 3,
+            Self::DHex | Self::Pent => 2,
+            Self::NeuAc | Self::Kdn => 5,
+            Self::NeuGc => 6,
+            Self::Sulfate | Self::Phosphate => -1,
+        }
+    }
+
+    /// Find the monosaccharide named `name` or one of its aliases, ignoring case
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|monosaccharide| {
+            monosaccharide.name().eq_ignore_ascii_case(name)
+                || monosaccharide
+                    .aliases()
+                    .iter()
+                    .any(|alias| alias.eq_ignore_ascii_case(name))
+        })
+    }
+
+    /// The composition of the unmodified residue using the global [`PeriodicTable`]
+    pub fn composition(&self) -> ChemicalComposition<'static> {
+        self.composition_with(&PERIODIC_TABLE)
+    }
+
+    /// The composition of the unmodified residue using the specified [`PeriodicTable`]
+    pub fn composition_with<'lifespan>(
+        &self,
+        periodic_table: &'lifespan PeriodicTable,
+    ) -> ChemicalComposition<'lifespan> {
+        ChemicalComposition::parse_with(self.formula(), periodic_table).unwrap()
+    }
+}
+
+impl Display for Monosaccharide {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// The form of the reducing end of the glycan
+pub enum ReducingEnd {
+    /// The free hemiacetal, completing the glycan with a water
+    #[default]
+    Free,
+    /// Reduced to the alditol, as after borohydride release
+    Reduced,
+    /// Labelled with 2-aminobenzamide by reductive amination
+    TwoAminobenzamide,
+}
+
+impl ReducingEnd {
+    /// The formula added to the sum of the residues
+    fn formula(&self) -> &'static str {
+        match self {
+            Self::Free => "H2O",
+            Self::Reduced => "H4O",
+            Self::TwoAminobenzamide => "C7H10N2O",
+        }
+    }
+
+    /// The number of methyl groups the reducing end adds when permethylated
+    fn methylation_sites(&self) -> i32 {
+        match self {
+            // The terminal hydroxyls of the water completing the glycan
+            Self::Free => 2,
+            // The alditol also frees the ring oxygen's hydroxyl
+            Self::Reduced => 3,
+            // The secondary amine and the amide replace the anomeric hydroxyl
+            Self::TwoAminobenzamide => 5,
+        }
+    }
+}
+
+/// The largest total count of one monosaccharide [`GlycanComposition::parse`] accepts
+const MAX_COUNT: u32 = 1_000_000;
+
+#[derive(Debug, Clone, PartialEq)]
+/// The ways a glycan composition string can be malformed
+pub enum GlycanErrorKind {
+    /// The name starting at this byte offset is not a known monosaccharide
+    UnknownMonosaccharide { position: usize, name: String },
+    /// The count starting at this byte offset was malformed, not closed with `)` or too large,
+    /// or the implied count of the name starting there made its total too large
+    CountMalformed(usize),
+    /// The character at this byte offset cannot start a monosaccharide name
+    UnexpectedCharacter { position: usize, character: char },
+    /// The composition string contained no monosaccharides
+    Empty,
+}
+
+impl Display for GlycanErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownMonosaccharide { .. } => f.write_str("unknown monosaccharide"),
+            Self::CountMalformed(_) => f.write_str("malformed count"),
+            Self::UnexpectedCharacter { .. } => f.write_str("unexpected character"),
+            Self::Empty => f.write_str("the composition contains no monosaccharides"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/**
+An error parsing a glycan composition string. Its [`Display`] implementation renders the
+composition with a caret under the offending token.
+*/
+pub struct GlycanError {
+    pub kind: GlycanErrorKind,
+    /// The composition string being parsed
+    pub composition: String,
+}
+
+impl GlycanError {
+    pub fn new(kind: GlycanErrorKind, composition: &str) -> Self {
+        Self {
+            kind,
+            composition: composition.to_string(),
+        }
+    }
+
+    /// The byte range of the offending token in `composition`, unless the composition was empty
+    pub fn span(&self) -> Option<Range<usize>> {
+        match &self.kind {
+            GlycanErrorKind::UnknownMonosaccharide { position, name } => {
+                Some(*position..*position + name.len())
+            }
+            GlycanErrorKind::CountMalformed(position) => {
+                let rest = self.composition.get(*position..).unwrap_or_default();
+                let end = if rest.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                    rest.find(|c: char| !c.is_ascii_alphanumeric())
+                } else {
+                    rest.find(')')
+                };
+                Some(*position..end.map_or(self.composition.len(), |end| position + end))
+            }
+            GlycanErrorKind::UnexpectedCharacter { position, .. } => {
+                Some(char_span(&self.composition, *position))
+            }
+            GlycanErrorKind::Empty => None,
+        }
+    }
+}
+
+impl Display for GlycanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.span() {
+            Some(span) => write_diagnostic(f, &self.kind, &self.composition, &span, &[]),
+            None => write!(f, "{}\n{}", self.kind, self.composition),
+        }
+    }
+}
+
+impl std::error::Error for GlycanError {}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+/**
+A glycan described by the count of each of its monosaccharides, in the order written,
+with the form of its reducing end and whether it is permethylated.
+*/
+pub struct GlycanComposition {
+    pub monosaccharides: Vec<(Monosaccharide, i32)>,
+    pub reducing_end: ReducingEnd,
+    pub permethylated: bool,
+}
+
+impl GlycanComposition {
+    pub fn new(monosaccharides: Vec<(Monosaccharide, i32)>) -> Self {
+        Self {
+            monosaccharides,
+            ..Default::default()
+        }
+    }
+
+    /**
+    Parse a composition string of monosaccharide names each followed by an optional count
+    in parentheses, like `HexNAc(4)Hex(5)Fuc(1)NeuAc(2)`. Names are matched without regard
+    to case and may be separated by whitespace or commas, and repeated names are summed.
+
+    The total count of each monosaccharide may be at most a million either way, which keeps
+    the element counts of the composition from overflowing.
+    */
+    pub fn parse(string: &str) -> Result<Self, GlycanError> {
+        let error = |kind| GlycanError::new(kind, string);
+        let mut monosaccharides: Vec<(Monosaccharide, i32)> = Vec::new();
+        let mut chars = string.char_indices().peekable();
+        while let Some((start, c)) = chars.next() {
+            if c.is_whitespace() || c == ',' {
+                continue;
+            }
+            if !c.is_ascii_alphabetic() {
+                return Err(error(GlycanErrorKind::UnexpectedCharacter {
+                    position: start,
+                    character: c,
+                }));
+            }
+            let mut end = start + 1;
+            while let Some((i, c)) = chars.next_if(|(_, c)| c.is_ascii_alphanumeric()) {
+                end = i + c.len_utf8();
+            }
+            let name = &string[start..end];
+            let monosaccharide = Monosaccharide::from_name(name).ok_or_else(|| {
+                error(GlycanErrorKind::UnknownMonosaccharide {
+                    position: start,
+                    name: name.to_string(),
+                })
+            })?;
+            let mut count = 1;
+            // Where the count is written, or the name when it is implied
+            let mut count_position = start;
+            if let Some((open, _)) = chars.next_if(|(_, c)| *c == '(') {
+                let close = string[open..]
+                    .find(')')
+                    .ok_or_else(|| error(GlycanErrorKind::CountMalformed(open + 1)))?
+                    + open;
+                count = string[open + 1..close]
+                    .trim()
+                    .parse()
+                    .map_err(|_| error(GlycanErrorKind::CountMalformed(open + 1)))?;
+                while chars.next_if(|(i, _)| *i <= close).is_some() {}
+                count_position = open + 1;
+            }
+            let existing = monosaccharides
+                .iter_mut()
+                .find(|(m, _)| *m == monosaccharide);
+            let total = existing
+                .as_ref()
+                .map_or(0, |(_, total)| *total)
+                .checked_add(count)
+                .filter(|total| total.unsigned_abs() <= MAX_COUNT)
+                .ok_or_else(|| error(GlycanErrorKind::CountMalformed(count_position)))?;
+            match existing {
+                Some((_, existing)) => *existing = total,
+                None => monosaccharides.push((monosaccharide, total)),
+            }
+        }
+        if monosaccharides.is_empty() {
+            return Err(error(GlycanErrorKind::Empty));
+        }
+        Ok(Self::new(monosaccharides))
+    }
+
+    pub fn with_reducing_end(mut self, reducing_end: ReducingEnd) -> Self {
+        self.reducing_end = reducing_end;
+        self
+    }
+
+    pub fn permethylated(mut self, permethylated: bool) -> Self {
+        self.permethylated = permethylated;
+        self
+    }
+
+    /// The count of `monosaccharide` in the glycan
+    pub fn count(&self, monosaccharide: Monosaccharide) -> i32 {
+        self.monosaccharides
+            .iter()
+            .filter(|(m, _)| *m == monosaccharide)
+            .map(|(_, count)| *count)
+            .sum()
+    }
+
+    /// The elemental composition of the glycan using the global [`PeriodicTable`]
+    pub fn composition(&self) -> ChemicalComposition<'static> {
+        self.composition_with(&PERIODIC_TABLE)
+    }
+
+    /// The elemental composition of the glycan, including its reducing end and any
+    /// permethylation, using the specified [`PeriodicTable`]
+    pub fn composition_with<'lifespan>(
+        &self,
+        periodic_table: &'lifespan PeriodicTable,
+    ) -> ChemicalComposition<'lifespan> {
+        let mut composition =
+            ChemicalComposition::parse_with(self.reducing_end.formula(), periodic_table).unwrap();
+        let mut methyls = self.reducing_end.methylation_sites();
+        for (monosaccharide, count) in self.monosaccharides.iter() {
+            composition += &(&monosaccharide.composition_with(periodic_table) * *count);
+            methyls += monosaccharide.methylation_sites() * count;
+        }
+        if self.permethylated {
+            composition.inc(ElementSpecification::new(&periodic_table["C"], 0), methyls);
+            composition.inc(
+                ElementSpecification::new(&periodic_table["H"], 0),
+                2 * methyls,
+            );
+        }
+        composition
+    }
+}
+
+impl FromStr for GlycanComposition {
+    type Err = GlycanError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl Display for GlycanComposition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (monosaccharide, count) in self.monosaccharides.iter() {
+            write!(f, "{}({})", monosaccharide, count)?;
+        }
+        Ok(())
+    }
+}
+
+/// The composition of the native glycan with a free reducing end described by the
+/// composition string `string`, using the global [`PeriodicTable`]. See [`GlycanComposition::parse`].
+pub fn glycan_composition(string: &str) -> Result<ChemicalComposition<'static>, GlycanError> {
+    Ok(GlycanComposition::parse(string)?.composition())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let glycan = GlycanComposition::parse("HexNAc(4)Hex(5) Fuc NeuAc(2),Gal(1)").unwrap();
+        assert_eq!(glycan.count(Monosaccharide::Hex), 6);
+        assert_eq!(glycan.count(Monosaccharide::DHex), 1);
+        assert_eq!(glycan.to_string(), "HexNAc(4)Hex(6)dHex(1)NeuAc(2)");
+        let err = GlycanComposition::parse("HexNAc(4)Hx(5)").unwrap_err();
+        assert_eq!(
+            err.kind,
+            GlycanErrorKind::UnknownMonosaccharide {
+                position: 9,
+                name: "Hx".to_string()
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "unknown monosaccharide `Hx` at byte 9\nHexNAc(4)Hx(5)\n         ^^"
+        );
+        assert_eq!(
+            GlycanComposition::parse("HexNAc(4").unwrap_err().kind,
+            GlycanErrorKind::CountMalformed(7)
+        );
+        let err = GlycanComposition::parse("HexNAc(a)").unwrap_err();
+        assert_eq!(err.kind, GlycanErrorKind::CountMalformed(7));
+        assert_eq!(
+            err.to_string(),
+            "malformed count `a` at byte 7\nHexNAc(a)\n       ^"
+        );
+        let err = GlycanComposition::parse("Hex(2147483647)Hex(1)").unwrap_err();
+        assert_eq!(err.kind, GlycanErrorKind::CountMalformed(4));
+        let err = GlycanComposition::parse("Hex(1000000)Hex(1)").unwrap_err();
+        assert_eq!(err.kind, GlycanErrorKind::CountMalformed(16));
+        assert_eq!(err.span(), Some(16..17));
+        let err = GlycanComposition::parse("Hex(1000000) Hex").unwrap_err();
+        assert_eq!(err.span(), Some(13..16));
+        let err = GlycanComposition::parse("Hex(1000000000)").unwrap_err();
+        assert_eq!(err.span(), Some(4..14));
+        let glycan = GlycanComposition::parse("Hex(1000000)HexNAc(-1000000)").unwrap();
+        assert_eq!(glycan.composition()["C"], 6 * 1000000 - 8 * 1000000);
+        assert_eq!(
+            GlycanComposition::parse(" ").unwrap_err().kind,
+            GlycanErrorKind::Empty
+        );
+    }
+
+    #[test]
+    fn test_masses() {
+        // Native and permethylated masses of the biantennary sialylated N-glycan
+        let glycan = GlycanComposition::parse("HexNAc(4)Hex(5)NeuAc(2)").unwrap();
+        for (reducing_end, permethylated, mass) in [
+            (ReducingEnd::Free, false, 2222.7830),
+            (ReducingEnd::Reduced, false, 2224.7987),
+            (ReducingEnd::TwoAminobenzamide, false, 2342.8517),
+            (ReducingEnd::Free, true, 2769.3933),
+            (ReducingEnd::Reduced, true, 2785.4247),
+        ] {
+            let composition = glycan
+                .clone()
+                .with_reducing_end(reducing_end)
+                .permethylated(permethylated)
+                .composition();
+            assert!(
+                (composition.mass() - mass).abs() < 1e-3,
+                "{reducing_end:?} {permethylated} {}",
+                composition.mass()
+            );
+        }
+        let sulfated = glycan_composition("HexNAc(1)Hex(1)Sulfate(1)").unwrap();
+        assert_eq!(sulfated["S"], 1);
+        let permethylated = GlycanComposition::parse("HexNAc(1)Hex(1)Sulfate(1)")
+            .unwrap()
+            .permethylated(true)
+            .composition();
+        assert_eq!(
+            permethylated,
+            sulfated + &ChemicalComposition::parse("C7H14").unwrap()
+        );
+    }
+}
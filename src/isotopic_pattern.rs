@@ -1,11 +1,23 @@
 //! Implementations of isotopic pattern generator algorithms.
 
 pub mod baffling;
+pub mod correction;
+pub mod fine;
+pub mod labeling;
 pub mod peak;
 pub mod poisson;
+pub mod profile;
+pub mod resolution;
 
 pub use crate::isotopic_pattern::baffling::{
-    isotopic_variants, BafflingRecursiveIsotopicPatternGenerator,
+    isotopic_variants, isotopic_variants_anchored, isotopic_variants_with,
+    BafflingRecursiveIsotopicPatternGenerator,
+};
+pub use crate::isotopic_pattern::fine::{
+    fine_structure, isotopologues, FineStructureStopCondition, Isotopologue,
+};
+pub use crate::isotopic_pattern::peak::{IsotopicAnchor, Peak, PeakList, TheoreticalIsotopicPattern};
+pub use crate::isotopic_pattern::resolution::{
+    isotopic_variants_at_resolution, merge_by_resolution, MassAnalyzer, ResolvingPower,
 };
-pub use crate::isotopic_pattern::peak::{Peak, PeakList, TheoreticalIsotopicPattern};
 pub use crate::isotopic_pattern::poisson::{poisson_approximation, poisson_approximate_n_peaks_of};
\ No newline at end of file
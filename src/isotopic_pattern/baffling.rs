@@ -5,8 +5,10 @@ use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 
 use crate::element::Element;
-use crate::isotopic_pattern::{Peak, PeakList, poisson_approximate_n_peaks_of};
-use crate::{mass_charge_ratio, ChemicalComposition, ElementSpecification};
+use crate::isotopic_pattern::{
+    poisson_approximate_n_peaks_of, IsotopicAnchor, Peak, PeakList, TheoreticalIsotopicPattern,
+};
+use crate::{mass_charge_ratio, ChemicalComposition, ElementSpecification, IonizationMode};
 
 use fnv::FnvBuildHasher as RandomState;
 
@@ -129,7 +131,7 @@ impl PolynomialParameters {
     pub fn isotopic_coefficients(element: &Element, with_mass: bool, accumulator: &mut DVec) {
         let max_isotope_number = element.max_neutron_shift;
         let min_neutron_shift = element.min_neutron_shift;
-        let monoisotopic_number = element.element_number as usize;
+        let monoisotopic_number = element.most_abundant_isotope as usize;
         let n = element.isotopes.len();
 
         for z in min_neutron_shift..max_isotope_number + 1 {
@@ -360,6 +362,39 @@ pub fn max_variants(composition: &ChemicalComposition) -> i32 {
     acc
 }
 
+/// The number of nominal mass units spanned by the isotopologues of `composition`,
+/// counted from the lightest isotopologue
+pub fn max_monoisotopic_variants(composition: &ChemicalComposition) -> i32 {
+    composition
+        .iter()
+        .filter(|(elt, _)| elt.isotope == 0)
+        .map(|(elt, cnt)| {
+            let element = elt.element;
+            let heaviest = element
+                .isotopes
+                .values()
+                .filter(|iso| iso.abundance > 0.0)
+                .map(|iso| iso.neutrons)
+                .max()
+                .unwrap_or(0);
+            (heaviest.saturating_sub(element.monoisotopic_isotope())) as i32 * *cnt
+        })
+        .sum()
+}
+
+/// The number of nominal mass units between the lightest and most abundant isotopologues
+fn monoisotopic_offset(composition: &ChemicalComposition) -> i32 {
+    composition
+        .iter()
+        .filter(|(elt, _)| elt.isotope == 0)
+        .map(|(elt, cnt)| {
+            let element = elt.element;
+            (element.most_abundant_isotope.saturating_sub(element.monoisotopic_isotope())) as i32
+                * *cnt
+        })
+        .sum()
+}
+
 pub fn guess_npeaks(composition: &ChemicalComposition, max_npeaks: i32) -> i32 {
     // let total_variants = max_variants(composition);
     // let npeaks = (total_variants as f64).sqrt() as i32 - 2;
@@ -368,6 +403,86 @@ pub fn guess_npeaks(composition: &ChemicalComposition, max_npeaks: i32) -> i32 {
     return result.min(max_npeaks);
 }
 
+/// An isotopic polynomial truncated after some order, holding the probability of each
+/// nominal mass offset from the lightest isotopologue and the probability-weighted mass
+/// of the isotopologues at that offset
+#[derive(Debug, Clone)]
+struct IsotopePolynomial {
+    probability: DVec,
+    mass: DVec,
+}
+
+impl IsotopePolynomial {
+    fn unit() -> Self {
+        Self {
+            probability: vec![1.0],
+            mass: vec![0.0],
+        }
+    }
+
+    fn from_element_specification(elt: &ElementSpecification) -> Self {
+        if elt.isotope != 0 {
+            return Self {
+                probability: vec![1.0],
+                mass: vec![elt.element.isotopes[&elt.isotope].mass],
+            };
+        }
+        let element = elt.element;
+        let lightest = element.monoisotopic_isotope();
+        let mut inst = Self {
+            probability: DVec::new(),
+            mass: DVec::new(),
+        };
+        for iso in element.isotopes.values().filter(|iso| iso.abundance > 0.0) {
+            let k = iso.neutrons.saturating_sub(lightest) as usize;
+            if k >= inst.probability.len() {
+                inst.probability.resize(k + 1, 0.0);
+                inst.mass.resize(k + 1, 0.0);
+            }
+            inst.probability[k] += iso.abundance;
+            inst.mass[k] += iso.abundance * iso.mass;
+        }
+        if inst.probability.is_empty() {
+            return Self {
+                probability: vec![1.0],
+                mass: vec![element.most_abundant_mass],
+            };
+        }
+        inst
+    }
+
+    fn convolve(&self, other: &Self, order: usize) -> Self {
+        let n = (self.probability.len() + other.probability.len() - 1).min(order + 1);
+        let mut probability = vec![0.0; n];
+        let mut mass = vec![0.0; n];
+        for (i, (p1, m1)) in self.probability.iter().zip(self.mass.iter()).enumerate() {
+            for (j, (p2, m2)) in other.probability.iter().zip(other.mass.iter()).enumerate() {
+                if i + j >= n {
+                    break;
+                }
+                probability[i + j] += p1 * p2;
+                mass[i + j] += m1 * p2 + p1 * m2;
+            }
+        }
+        Self { probability, mass }
+    }
+
+    fn power(&self, mut exponent: u32, order: usize) -> Self {
+        let mut result = Self::unit();
+        let mut base = self.clone();
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = result.convolve(&base, order);
+            }
+            exponent >>= 1;
+            if exponent > 0 {
+                base = base.convolve(&base, order);
+            }
+        }
+        result
+    }
+}
+
 struct ElementPolynomialMap<'a> {
     pub polynomials: Vec<(&'a str, DVec)>
 }
@@ -398,6 +513,8 @@ pub struct IsotopicDistribution<'lifespan, 'outer> {
     pub average_mass: f64,
     pub monoisotopic_peak: Peak,
     pub max_variants: i32,
+    /// Which isotopic peak the generated pattern starts from
+    pub anchor: IsotopicAnchor,
 }
 
 impl<'lifespan: 'transient, 'transient, 'outer: 'lifespan> IsotopicDistribution<'lifespan, 'outer> {
@@ -410,13 +527,46 @@ impl<'lifespan: 'transient, 'transient, 'outer: 'lifespan> IsotopicDistribution<
         inst
     }
 
+    /**
+    Create an [`IsotopicDistribution`] whose pattern starts from the peak selected by
+    `anchor`.
+
+    [`IsotopicAnchor::MostAbundant`] uses BRAIN, while [`IsotopicAnchor::Monoisotopic`]
+    convolves each element's isotope polynomial directly, relative to its lightest isotope,
+    so that isotopologues lighter than the most abundant one are included.
+    */
+    pub fn from_composition_anchored(
+        composition: ChemicalComposition<'lifespan>,
+        order: i32,
+        anchor: IsotopicAnchor,
+    ) -> IsotopicDistribution<'lifespan, 'lifespan> {
+        let mut inst = IsotopicDistribution::fill_from_composition_anchored(composition, order, anchor);
+        if anchor == IsotopicAnchor::MostAbundant {
+            inst.populate_constants();
+        }
+        inst
+    }
+
     fn fill_from_composition(
         composition: ChemicalComposition<'outer>,
         order: i32,
     ) -> IsotopicDistribution<'lifespan, 'outer> {
+        IsotopicDistribution::fill_from_composition_anchored(composition, order, IsotopicAnchor::MostAbundant)
+    }
+
+    fn fill_from_composition_anchored(
+        composition: ChemicalComposition<'outer>,
+        order: i32,
+        anchor: IsotopicAnchor,
+    ) -> IsotopicDistribution<'lifespan, 'outer> {
+        let max_variants = match anchor {
+            IsotopicAnchor::MostAbundant => max_variants(&composition),
+            IsotopicAnchor::Monoisotopic => max_monoisotopic_variants(&composition),
+        };
         let mut inst = IsotopicDistribution {
             constants: IsotopicConstants::new(composition.len()),
-            max_variants: max_variants(&composition),
+            max_variants,
+            anchor,
             composition,
             order: 0,
             average_mass: 0.0,
@@ -606,7 +756,76 @@ impl<'lifespan: 'transient, 'transient, 'outer: 'lifespan> IsotopicDistribution<
         return mass_vector;
     }
 
+    /// Generate the isotopic peaks starting from the peak selected by [`IsotopicDistribution::anchor`]
     pub fn isotopic_variants(&self, charge: i32, charge_carrier: f64) -> PeakList {
+        match self.anchor {
+            IsotopicAnchor::MostAbundant => self.most_abundant_variants(charge, charge_carrier),
+            IsotopicAnchor::Monoisotopic => self.monoisotopic_variants(charge, charge_carrier),
+        }
+    }
+
+    /// Generate the isotopic peaks like [`IsotopicDistribution::isotopic_variants`], recording the
+    /// anchor peak's m/z as the pattern's origin
+    pub fn isotopic_pattern(&self, charge: i32, charge_carrier: f64) -> TheoreticalIsotopicPattern {
+        let peaks = self.isotopic_variants(charge, charge_carrier);
+        let anchor_mass = match self.anchor {
+            IsotopicAnchor::MostAbundant => self.composition.mass(),
+            IsotopicAnchor::Monoisotopic => self.composition.monoisotopic_mass(),
+        };
+        let origin = if charge != 0 {
+            mass_charge_ratio(anchor_mass, charge, charge_carrier)
+        } else {
+            anchor_mass
+        };
+        TheoreticalIsotopicPattern::new(peaks, origin).with_anchor(self.anchor)
+    }
+
+    fn monoisotopic_polynomial(&self) -> IsotopePolynomial {
+        let order = self.order.max(0) as usize;
+        let mut polynomial = IsotopePolynomial::unit();
+        for (elt, cnt) in self.composition.iter() {
+            if *cnt <= 0 {
+                continue;
+            }
+            let term = IsotopePolynomial::from_element_specification(elt).power(*cnt as u32, order);
+            polynomial = polynomial.convolve(&term, order);
+        }
+        polynomial
+    }
+
+    /**
+    The probability of each nominal mass offset from the lightest isotopologue, up to
+    [`IsotopicDistribution::order`], regardless of [`IsotopicDistribution::anchor`].
+
+    Unlike the peaks generated by [`IsotopicDistribution::isotopic_variants`], these are
+    not normalized, so the probability mass beyond the order is not redistributed.
+    */
+    pub fn monoisotopic_probability_vector(&self) -> DVec {
+        self.monoisotopic_polynomial().probability
+    }
+
+    fn monoisotopic_variants(&self, charge: i32, charge_carrier: f64) -> PeakList {
+        let polynomial = self.monoisotopic_polynomial();
+
+        let total: f64 = polynomial.probability.iter().sum();
+        let mut peak_list = PeakList::with_capacity(polynomial.probability.len());
+        for (probability, mass) in polynomial.probability.iter().zip(polynomial.mass.iter()) {
+            let intensity = probability / total;
+            if intensity < 1e-10 {
+                continue;
+            }
+            let center_mass = mass / probability;
+            let mz = if charge != 0 {
+                mass_charge_ratio(center_mass, charge, charge_carrier)
+            } else {
+                center_mass
+            };
+            peak_list.push(Peak { mz, intensity, charge });
+        }
+        peak_list
+    }
+
+    fn most_abundant_variants(&self, charge: i32, charge_carrier: f64) -> PeakList {
         let probability_vector = self.probability_vector();
         let center_mass_vector = self.center_mass_vector(&probability_vector);
 
@@ -662,6 +881,53 @@ pub fn isotopic_variants<'a, C: Into<ChemicalComposition<'a>>>(
     dist.isotopic_variants(charge, charge_carrier)
 }
 
+/**
+Generate a coarse isotopic pattern from a [`ChemicalComposition`] starting from the
+peak selected by `anchor`, with the anchor peak's m/z as the pattern's origin.
+
+Use [`IsotopicAnchor::Monoisotopic`] for compositions containing elements whose lightest
+isotope is not their most abundant, like Fe, Se or Sn.
+
+if `npeaks` is 0, a guess will be used.
+*/
+pub fn isotopic_variants_anchored<'a, C: Into<ChemicalComposition<'a>>>(
+    composition: C,
+    npeaks: i32,
+    charge: i32,
+    charge_carrier: f64,
+    anchor: IsotopicAnchor,
+) -> TheoreticalIsotopicPattern {
+    let composition = composition.into();
+    let npeaks = if npeaks == 0 {
+        let offset = match anchor {
+            IsotopicAnchor::MostAbundant => 0,
+            IsotopicAnchor::Monoisotopic => monoisotopic_offset(&composition),
+        };
+        guess_npeaks(&composition, 300) + offset
+    } else {
+        npeaks - 1
+    };
+
+    let dist = IsotopicDistribution::from_composition_anchored(composition, npeaks, anchor);
+    dist.isotopic_pattern(charge, charge_carrier)
+}
+
+/// Generate a coarse isotopic pattern like [`isotopic_variants`], deriving the charge
+/// carrier and the sign of `charge` from `mode`.
+pub fn isotopic_variants_with<'a, C: Into<ChemicalComposition<'a>>>(
+    composition: C,
+    npeaks: i32,
+    charge: i32,
+    mode: IonizationMode,
+) -> PeakList {
+    isotopic_variants(
+        composition,
+        npeaks,
+        mode.normalize_charge(charge),
+        mode.charge_carrier(),
+    )
+}
+
 #[derive(Debug, Clone)]
 pub struct BafflingRecursiveIsotopicPatternGenerator<'lifespan> {
     parameter_cache: IsotopicConstantsCache<'lifespan>,
@@ -722,6 +988,48 @@ mod test {
         assert!((peaks[0].intensity() - 0.8782583).abs() < 1e-6);
     }
 
+    #[test]
+    fn test_monoisotopic_anchor() {
+        let comp = ChemicalComposition::parse("C6H12O6").unwrap();
+        let legacy = isotopic_variants(comp.clone(), 5, 0, PROTON);
+        let anchored = isotopic_variants_anchored(comp, 5, 0, PROTON, IsotopicAnchor::Monoisotopic);
+        assert_eq!(anchored.anchor, IsotopicAnchor::Monoisotopic);
+        for (a, b) in legacy.iter().zip(anchored.iter()) {
+            assert!((a.mz - b.mz).abs() < 1e-3);
+            assert!((a.intensity - b.intensity).abs() < 1e-4);
+        }
+
+        // Selenomethionine's monoisotopic peak carries 74Se, six Da below the most abundant 80Se
+        let comp = ChemicalComposition::parse("C5H11NO2Se").unwrap();
+        let most_abundant = isotopic_variants_anchored(comp.clone(), 0, 1, PROTON, IsotopicAnchor::MostAbundant);
+        let mono = isotopic_variants_anchored(comp.clone(), 0, 1, PROTON, IsotopicAnchor::Monoisotopic);
+        assert!((mono.origin - mass_charge_ratio(comp.monoisotopic_mass(), 1, PROTON)).abs() < 1e-9);
+        assert!((mono[0].mz - mono.origin).abs() < 1e-6);
+        assert!((most_abundant.origin - mono.origin - 5.99).abs() < 0.01);
+        let apex = mono
+            .iter()
+            .max_by(|a, b| a.intensity.total_cmp(&b.intensity))
+            .unwrap();
+        assert!((apex.mz - most_abundant.origin).abs() < 1e-2);
+    }
+
+    #[test]
+    fn test_ionization_modes() {
+        let comp = ChemicalComposition::parse("C6H12O6").unwrap();
+        let mass = comp.mass();
+        let radical = isotopic_variants_with(comp.clone(), 3, 1, IonizationMode::ElectronRemoval);
+        assert!((radical[0].mz - (mass - crate::ELECTRON)).abs() < 1e-9);
+
+        let anion = isotopic_variants_with(comp.clone(), 3, 2, IonizationMode::Deprotonation);
+        assert_eq!(anion[0].charge, -2);
+        assert!((anion[0].mz - (mass - 2.0 * PROTON) / 2.0).abs() < 1e-9);
+        assert!((anion[0].neutral_mass_with(IonizationMode::Deprotonation) - mass).abs() < 1e-9);
+
+        let sodium = IonizationMode::MetalCation(22.98976928);
+        let cation = isotopic_variants_with(comp, 3, 1, sodium);
+        assert!((cation[0].mz - 203.05261).abs() < 1e-4);
+    }
+
     #[test]
     fn test_baffling_generator() {
         let comp = ChemicalComposition::parse("C6H12O6").unwrap();
@@ -0,0 +1,411 @@
+//! Correct measured mass isotopomer distributions (MIDs) from isotope tracing experiments
+//! for the natural isotopic abundance of non-tracer atoms and for tracer impurity, as done
+//! by tools like IsoCor and AccuCor.
+//!
+//! A [`CorrectionMatrix`] maps the true distribution of tracer incorporation, how many tracer
+//! atoms were labeled, to the expected measured MID. Correction inverts this mapping with
+//! non-negative least squares.
+//!
+//! ```rust
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use chemical_elements::ChemicalComposition;
+//! use chemical_elements::isotopic_pattern::correction::CorrectionMatrix;
+//!
+//! let lactate = ChemicalComposition::parse("C3H6O3")?;
+//! let matrix = CorrectionMatrix::new(&lactate, None, "C", 13, 0.99)?;
+//! // A sample where half of the lactate pool is fully labeled
+//! let measured = matrix.expected(&[0.5, 0.0, 0.0, 0.5]);
+//! let corrected = matrix.correct(&measured)?;
+//! assert!((corrected.isotopologues[3] - 0.5).abs() < 1e-6);
+//! # Ok(())
+//! # }
+//! ```
+use std::fmt::{self, Display};
+
+use crate::enrichment::EnrichmentError;
+use crate::isotopic_pattern::baffling::{DVec, IsotopicDistribution};
+use crate::isotopic_pattern::IsotopicAnchor;
+use crate::{ChemicalComposition, ElementSpecification, PeriodicTable};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CorrectionError {
+    /// The tracer element is not in the metabolite's composition
+    UnknownTracer(String),
+    /// The tracer isotope or purity was invalid for the tracer element
+    InvalidEnrichment(EnrichmentError),
+    /// The measured MID did not have one entry per row of the correction matrix
+    MeasurementLength { expected: usize, found: usize },
+}
+
+impl Display for CorrectionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownTracer(symbol) => {
+                write!(f, "tracer element `{symbol}` is not in the metabolite")
+            }
+            Self::InvalidEnrichment(err) => write!(f, "invalid tracer enrichment: {err}"),
+            Self::MeasurementLength { expected, found } => write!(
+                f,
+                "expected a measured MID of {expected} entries, found {found}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CorrectionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::InvalidEnrichment(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<EnrichmentError> for CorrectionError {
+    fn from(value: EnrichmentError) -> Self {
+        Self::InvalidEnrichment(value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// The result of correcting a measured MID
+pub struct CorrectedDistribution {
+    /// The fraction of the metabolite pool with each number of labeled tracer atoms,
+    /// normalized to sum to one
+    pub isotopologues: DVec,
+    /// The difference between the measured MID and the MID expected from the solution
+    /// before normalization
+    pub residuals: DVec,
+    /// The fraction of tracer atoms which were labeled
+    pub mean_enrichment: f64,
+}
+
+impl CorrectedDistribution {
+    /// The Euclidean norm of [`CorrectedDistribution::residuals`]
+    pub fn residual_norm(&self) -> f64 {
+        self.residuals.iter().map(|r| r * r).sum::<f64>().sqrt()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/**
+A matrix whose column `j` is the MID expected when exactly `j` of the metabolite's tracer
+atoms are labeled, accounting for the natural abundance of every other atom, including
+those of a derivatization group, and for the purity of the tracer.
+
+Rows are nominal mass offsets from the unlabeled M+0 isotopologue, so for tracers which
+shift the mass by more than one unit, like <sup>18</sup>O, there are more rows than columns.
+*/
+pub struct CorrectionMatrix {
+    /// The symbol of the tracer element
+    pub tracer: String,
+    /// The mass number of the tracer isotope
+    pub tracer_isotope: u16,
+    /// The number of tracer element atoms in the metabolite which may be labeled
+    pub tracer_atoms: usize,
+    /// The fraction of the tracer isotope in the labeled substrate
+    pub purity: f64,
+    /// The matrix, indexed by row then column
+    pub matrix: Vec<DVec>,
+}
+
+impl CorrectionMatrix {
+    /**
+    Build the correction matrix for `metabolite` traced with `tracer_isotope` of the
+    element `tracer` at `purity`. Atoms of the optional `derivatization` composition are
+    never labeled, but contribute their natural abundance.
+    */
+    pub fn new(
+        metabolite: &ChemicalComposition,
+        derivatization: Option<&ChemicalComposition>,
+        tracer: &str,
+        tracer_isotope: u16,
+        purity: f64,
+    ) -> Result<Self, CorrectionError> {
+        let (tracer_spec, count) = metabolite
+            .iter()
+            .find(|(elt, cnt)| elt.isotope == 0 && elt.element.symbol == tracer && **cnt > 0)
+            .map(|(elt, cnt)| (*elt, *cnt))
+            .ok_or_else(|| CorrectionError::UnknownTracer(tracer.to_string()))?;
+
+        let element = tracer_spec.element;
+        let label_symbol = format!("{}*", element.symbol);
+        let mut variants = PeriodicTable::new();
+        variants.add(element.enriched(&label_symbol, tracer_isotope, purity)?);
+        let label = ElementSpecification::new(&variants[label_symbol.as_str()], 0);
+
+        let tracer_atoms = count as usize;
+        let shift = tracer_isotope.saturating_sub(element.monoisotopic_isotope()) as usize;
+        let n_rows = tracer_atoms * shift + 1;
+
+        let mut base = metabolite.clone();
+        if let Some(derivatization) = derivatization {
+            base += derivatization;
+        }
+        let base_mass = base.monoisotopic_mass();
+
+        let mut matrix = vec![vec![0.0; tracer_atoms + 1]; n_rows];
+        for j in 0..=tracer_atoms {
+            let mut column = base.clone();
+            column.inc(tracer_spec, -(j as i32));
+            column.inc(label, j as i32);
+            let offset = (column.monoisotopic_mass() - base_mass).round() as usize;
+            let dist = IsotopicDistribution::from_composition_anchored(
+                column,
+                (n_rows - 1) as i32,
+                IsotopicAnchor::Monoisotopic,
+            );
+            for (k, probability) in dist
+                .monoisotopic_probability_vector()
+                .into_iter()
+                .enumerate()
+            {
+                if let Some(row) = matrix.get_mut(k + offset) {
+                    row[j] = probability;
+                }
+            }
+        }
+
+        Ok(Self {
+            tracer: tracer.to_string(),
+            tracer_isotope,
+            tracer_atoms,
+            purity,
+            matrix,
+        })
+    }
+
+    pub fn n_rows(&self) -> usize {
+        self.matrix.len()
+    }
+
+    pub fn n_columns(&self) -> usize {
+        self.tracer_atoms + 1
+    }
+
+    /// The MID expected to be measured for a true distribution of tracer incorporation
+    pub fn expected(&self, isotopologues: &[f64]) -> DVec {
+        self.matrix
+            .iter()
+            .map(|row| row.iter().zip(isotopologues).map(|(a, x)| a * x).sum())
+            .collect()
+    }
+
+    /// Correct a measured MID, M+0 first, solving for the true distribution of tracer
+    /// incorporation with non-negative least squares
+    pub fn correct(&self, measured: &[f64]) -> Result<CorrectedDistribution, CorrectionError> {
+        if measured.len() != self.n_rows() {
+            return Err(CorrectionError::MeasurementLength {
+                expected: self.n_rows(),
+                found: measured.len(),
+            });
+        }
+        let solution = nnls(&self.matrix, measured);
+        let residuals = self
+            .expected(&solution)
+            .iter()
+            .zip(measured)
+            .map(|(e, m)| m - e)
+            .collect();
+
+        let total: f64 = solution.iter().sum();
+        let isotopologues: DVec = if total > 0.0 {
+            solution.iter().map(|x| x / total).collect()
+        } else {
+            solution
+        };
+        let mean_enrichment = if self.tracer_atoms > 0 {
+            isotopologues
+                .iter()
+                .enumerate()
+                .map(|(j, x)| j as f64 * x)
+                .sum::<f64>()
+                / self.tracer_atoms as f64
+        } else {
+            0.0
+        };
+        Ok(CorrectedDistribution {
+            isotopologues,
+            residuals,
+            mean_enrichment,
+        })
+    }
+}
+
+/// Solve the linear system restricted to the `passive` columns by least squares using the
+/// normal equations, leaving the other coefficients at zero
+fn solve_passive(a: &[DVec], b: &[f64], passive: &[bool]) -> DVec {
+    let columns: Vec<usize> = (0..passive.len()).filter(|j| passive[*j]).collect();
+    let n = columns.len();
+    let mut system = vec![vec![0.0; n + 1]; n];
+    for (p, jp) in columns.iter().enumerate() {
+        for (q, jq) in columns.iter().enumerate() {
+            system[p][q] = a.iter().map(|row| row[*jp] * row[*jq]).sum();
+        }
+        system[p][n] = a.iter().zip(b).map(|(row, bi)| row[*jp] * bi).sum();
+    }
+
+    // Gaussian elimination with partial pivoting
+    for col in 0..n {
+        let pivot = (col..n)
+            .max_by(|i, j| system[*i][col].abs().total_cmp(&system[*j][col].abs()))
+            .unwrap();
+        system.swap(col, pivot);
+        let diag = system[col][col];
+        if diag.abs() < f64::EPSILON {
+            continue;
+        }
+        let (upper, lower) = system.split_at_mut(col + 1);
+        let pivot_row = &upper[col];
+        for row in lower.iter_mut() {
+            let factor = row[col] / diag;
+            for (value, pivot_value) in row[col..].iter_mut().zip(&pivot_row[col..]) {
+                *value -= factor * pivot_value;
+            }
+        }
+    }
+    let mut reduced = vec![0.0; n];
+    for row in (0..n).rev() {
+        let diag = system[row][row];
+        if diag.abs() < f64::EPSILON {
+            continue;
+        }
+        let tail: f64 = ((row + 1)..n).map(|k| system[row][k] * reduced[k]).sum();
+        reduced[row] = (system[row][n] - tail) / diag;
+    }
+
+    let mut solution = vec![0.0; passive.len()];
+    for (p, j) in columns.iter().enumerate() {
+        solution[*j] = reduced[p];
+    }
+    solution
+}
+
+/**
+Solve `min ||Ax - b||` subject to `x >= 0` with the active set method of Lawson and Hanson,
+where `a` is given row by row.
+*/
+pub fn nnls(a: &[DVec], b: &[f64]) -> DVec {
+    let n = a.first().map(|row| row.len()).unwrap_or(0);
+    let tolerance = 1e-12;
+    let mut x = vec![0.0; n];
+    let mut passive = vec![false; n];
+
+    for _ in 0..(3 * n + 10) {
+        let residual: DVec = a
+            .iter()
+            .zip(b)
+            .map(|(row, bi)| bi - row.iter().zip(x.iter()).map(|(r, xi)| r * xi).sum::<f64>())
+            .collect();
+        let gradient: DVec = (0..n)
+            .map(|j| {
+                a.iter()
+                    .zip(residual.iter())
+                    .map(|(row, r)| row[j] * r)
+                    .sum()
+            })
+            .collect();
+        let candidate = (0..n)
+            .filter(|j| !passive[*j] && gradient[*j] > tolerance)
+            .max_by(|i, j| gradient[*i].total_cmp(&gradient[*j]));
+        let Some(candidate) = candidate else {
+            break;
+        };
+        passive[candidate] = true;
+
+        loop {
+            let z = solve_passive(a, b, &passive);
+            if (0..n).all(|j| !passive[j] || z[j] > tolerance) {
+                x = z;
+                break;
+            }
+            // Step towards z until the first passive coefficient reaches zero
+            let alpha = (0..n)
+                .filter(|j| passive[*j] && z[*j] <= tolerance)
+                .map(|j| x[j] / (x[j] - z[j]))
+                .fold(f64::INFINITY, f64::min);
+            for j in 0..n {
+                x[j] += alpha * (z[j] - x[j]);
+                if passive[j] && x[j] <= tolerance {
+                    passive[j] = false;
+                    x[j] = 0.0;
+                }
+            }
+        }
+    }
+    x
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_matrix() {
+        let glucose = ChemicalComposition::parse("C6H12O6").unwrap();
+        let matrix = CorrectionMatrix::new(&glucose, None, "C", 13, 1.0).unwrap();
+        assert_eq!(matrix.n_rows(), 7);
+        assert_eq!(matrix.n_columns(), 7);
+        // The fully labeled column has no lighter isotopologues with a pure tracer
+        let hydrogen_oxygen = matrix.matrix[0][0] / 0.9893f64.powi(6);
+        assert!((matrix.matrix[6][6] - hydrogen_oxygen).abs() < 1e-6);
+        assert_eq!(matrix.matrix[5][6], 0.0);
+        // The unlabeled column is the natural abundance distribution
+        let ratio = matrix.matrix[1][0] / matrix.matrix[0][0];
+        assert!((ratio - 0.0686).abs() < 0.001, "{ratio}");
+
+        let oxygen = CorrectionMatrix::new(&glucose, None, "O", 18, 0.95).unwrap();
+        assert_eq!(oxygen.n_rows(), 13);
+        assert_eq!(oxygen.n_columns(), 7);
+        assert!(matches!(
+            CorrectionMatrix::new(&glucose, None, "N", 15, 0.99),
+            Err(CorrectionError::UnknownTracer(_))
+        ));
+        assert_eq!(
+            CorrectionMatrix::new(&glucose, None, "C", 14, 0.99)
+                .unwrap_err()
+                .to_string(),
+            "invalid tracer enrichment: the element has no isotope with mass number 14"
+        );
+    }
+
+    #[test]
+    fn test_correct() {
+        let alanine = ChemicalComposition::parse("C3H7NO2").unwrap();
+        let tbdms = ChemicalComposition::parse("C12H30Si2").unwrap();
+        let matrix = CorrectionMatrix::new(&alanine, Some(&tbdms), "C", 13, 0.99).unwrap();
+        let truth = [0.6, 0.1, 0.05, 0.25];
+        let measured = matrix.expected(&truth);
+        // The derivatization's carbons and silicons inflate the apparent labeling
+        assert!(measured[1] > 0.15);
+
+        let corrected = matrix.correct(&measured).unwrap();
+        for (x, t) in corrected.isotopologues.iter().zip(truth) {
+            assert!((x - t).abs() < 1e-6, "{x} {t}");
+        }
+        assert!(corrected.residual_norm() < 1e-9);
+        let enrichment = (0.1 + 2.0 * 0.05 + 3.0 * 0.25) / 3.0;
+        assert!((corrected.mean_enrichment - enrichment).abs() < 1e-6);
+
+        assert!(matches!(
+            matrix.correct(&[1.0, 0.0]),
+            Err(CorrectionError::MeasurementLength {
+                expected: 4,
+                found: 2
+            })
+        ));
+        assert_eq!(
+            matrix.correct(&[1.0, 0.0]).unwrap_err().to_string(),
+            "expected a measured MID of 4 entries, found 2"
+        );
+    }
+
+    #[test]
+    fn test_nnls() {
+        let a = vec![vec![1.0, 0.0], vec![0.0, 1.0], vec![1.0, 1.0]];
+        let x = nnls(&a, &[2.0, -1.0, 1.0]);
+        assert!(x.iter().all(|v| *v >= 0.0));
+        assert!((x[0] - 1.5).abs() < 1e-9, "{x:?}");
+        assert_eq!(x[1], 0.0);
+    }
+}
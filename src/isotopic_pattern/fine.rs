@@ -0,0 +1,498 @@
+//! Isotopic fine structure generation, enumerating individual isotopologues rather than
+//! aggregating them by nominal neutron shift.
+//!
+//! This follows the strategy of IsoSpec described in [Łącki et al., 2017](https://doi.org/10.1021/acs.analchem.6b01459):
+//! each element's multinomial isotope distribution is explored outwards from its mode to
+//! produce the sub-isotopologues above a probability bound, and these marginal configurations
+//! are then combined across elements, pruning any branch which cannot reach the requested
+//! probability threshold.
+use std::collections::{HashSet, VecDeque};
+
+use crate::element::Element;
+use crate::isotopic_pattern::{Peak, PeakList, TheoreticalIsotopicPattern};
+use crate::{mass_charge_ratio, ChemicalComposition, ElementSpecification};
+
+/// The largest total probability [`FineStructureStopCondition::TotalProbability`] will try
+/// to reach, as the sum over every isotopologue is only 1.0 up to rounding error
+const MAX_TOTAL_PROBABILITY: f64 = 1.0 - 1e-12;
+
+/// The probability threshold [`FineStructureStopCondition::TotalProbability`] starts from
+const INITIAL_THRESHOLD: f64 = 1e-2;
+
+/// The factor [`FineStructureStopCondition::TotalProbability`] lowers its threshold by with
+/// each pass, so each pass enumerates a bounded number of isotopologues beyond the last
+const THRESHOLD_STEP: f64 = 1e-2;
+
+/// The smallest probability threshold used by either stop condition
+const MIN_THRESHOLD: f64 = 1e-32;
+
+/// The most enumerations [`FineStructureStopCondition::TotalProbability`] will run, the
+/// last of which uses [`MIN_THRESHOLD`]
+const MAX_PASSES: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Controls which isotopologues are generated
+pub enum FineStructureStopCondition {
+    /// Generate every isotopologue whose probability is at least this value. Values below
+    /// 1e-32, including zero and negative values, are raised to 1e-32.
+    Threshold(f64),
+    /// Generate the smallest set of most probable isotopologues whose probabilities
+    /// sum to at least this value, considering isotopologues down to a probability of 1e-32.
+    /// Values of 1.0 or more generate every isotopologue but the vanishingly improbable.
+    TotalProbability(f64),
+}
+
+#[derive(Debug, Clone)]
+/// A single isotopologue of a [`ChemicalComposition`], with every atom assigned
+/// a specific isotope.
+pub struct Isotopologue<'lifespan> {
+    /// The exact neutral mass of this isotopologue
+    pub mass: f64,
+    /// The probability of observing this isotopologue under the element abundances
+    pub probability: f64,
+    /// The composition of this isotopologue, written with fixed isotopes
+    pub composition: ChemicalComposition<'lifespan>,
+}
+
+impl<'lifespan> Isotopologue<'lifespan> {
+    /// Convert this isotopologue into a [`Peak`] at the specified charge state
+    pub fn to_peak(&self, charge: i32, charge_carrier: f64) -> Peak {
+        let mz = if charge != 0 {
+            mass_charge_ratio(self.mass, charge, charge_carrier)
+        } else {
+            self.mass
+        };
+        Peak {
+            mz,
+            intensity: self.probability,
+            charge,
+        }
+    }
+}
+
+/// The distribution of isotopes of a single element over all positions it occupies.
+#[derive(Debug, Clone)]
+struct MarginalConfiguration {
+    counts: Vec<i32>,
+    mass: f64,
+    log_probability: f64,
+}
+
+#[derive(Debug, Clone)]
+struct Marginal<'lifespan> {
+    element: &'lifespan Element,
+    isotopes: Vec<(u16, f64, f64)>,
+    /// Configurations sorted by descending probability
+    configurations: Vec<MarginalConfiguration>,
+}
+
+fn ln_factorials(n: usize) -> Vec<f64> {
+    let mut acc = Vec::with_capacity(n + 1);
+    acc.push(0.0);
+    let mut total = 0.0;
+    for k in 1..=n {
+        total += (k as f64).ln();
+        acc.push(total);
+    }
+    acc
+}
+
+impl<'lifespan> Marginal<'lifespan> {
+    fn new(element: &'lifespan Element, count: i32) -> Self {
+        let mut isotopes: Vec<(u16, f64, f64)> = element
+            .isotopes
+            .iter()
+            .filter(|(_, iso)| iso.abundance > 0.0)
+            .map(|(num, iso)| (*num, iso.mass, iso.abundance.ln()))
+            .collect();
+        isotopes.sort_by_key(|(num, _, _)| *num);
+        let mut inst = Self {
+            element,
+            isotopes,
+            configurations: Vec::new(),
+        };
+        let mode = inst.mode(count);
+        inst.configurations.push(mode);
+        inst
+    }
+
+    fn fixed(element: &'lifespan Element, isotope: u16, count: i32) -> Self {
+        let mass = element.isotopes[&isotope].mass;
+        Self {
+            element,
+            isotopes: vec![(isotope, mass, 0.0)],
+            configurations: vec![MarginalConfiguration {
+                counts: vec![count],
+                mass: mass * count as f64,
+                log_probability: 0.0,
+            }],
+        }
+    }
+
+    fn configuration(&self, counts: Vec<i32>, ln_fact: &[f64]) -> MarginalConfiguration {
+        let n: i32 = counts.iter().sum();
+        let mut log_probability = ln_fact[n as usize];
+        let mut mass = 0.0;
+        for (c, (_, m, lp)) in counts.iter().zip(self.isotopes.iter()) {
+            log_probability += *c as f64 * lp - ln_fact[*c as usize];
+            mass += *c as f64 * m;
+        }
+        MarginalConfiguration {
+            counts,
+            mass,
+            log_probability,
+        }
+    }
+
+    fn neighbors(counts: &[i32]) -> impl Iterator<Item = Vec<i32>> + '_ {
+        let k = counts.len();
+        (0..k)
+            .flat_map(move |i| (0..k).map(move |j| (i, j)))
+            .filter(move |(i, j)| i != j && counts[*i] > 0)
+            .map(move |(i, j)| {
+                let mut next = counts.to_vec();
+                next[i] -= 1;
+                next[j] += 1;
+                next
+            })
+    }
+
+    /// Locate the most probable configuration by hill-climbing from the expected counts
+    fn mode(&self, count: i32) -> MarginalConfiguration {
+        let ln_fact = ln_factorials(count as usize);
+        let mut counts: Vec<i32> = self
+            .isotopes
+            .iter()
+            .map(|(_, _, lp)| (count as f64 * lp.exp()).floor() as i32)
+            .collect();
+        let assigned: i32 = counts.iter().sum();
+        if let Some(c) = counts.first_mut() {
+            *c += count - assigned;
+        }
+        let mut best = self.configuration(counts, &ln_fact);
+        loop {
+            let improved = Self::neighbors(&best.counts)
+                .map(|c| self.configuration(c, &ln_fact))
+                .max_by(|a, b| a.log_probability.total_cmp(&b.log_probability))
+                .filter(|c| c.log_probability > best.log_probability);
+            match improved {
+                Some(c) => best = c,
+                None => break,
+            }
+        }
+        best
+    }
+
+    fn max_log_probability(&self) -> f64 {
+        self.configurations[0].log_probability
+    }
+
+    /// Populate all configurations whose log probability is at least `cutoff`
+    fn explore(&mut self, cutoff: f64) {
+        if self.isotopes.len() == 1 {
+            return;
+        }
+        let mode = self.configurations[0].clone();
+        let count: i32 = mode.counts.iter().sum();
+        let ln_fact = ln_factorials(count as usize);
+
+        let mut seen: HashSet<Vec<i32>> = HashSet::new();
+        let mut queue = VecDeque::new();
+        let mut accepted = Vec::new();
+        seen.insert(mode.counts.clone());
+        queue.push_back(mode);
+        while let Some(conf) = queue.pop_front() {
+            for next in Self::neighbors(&conf.counts) {
+                if seen.contains(&next) {
+                    continue;
+                }
+                seen.insert(next.clone());
+                let next = self.configuration(next, &ln_fact);
+                if next.log_probability >= cutoff {
+                    queue.push_back(next);
+                }
+            }
+            accepted.push(conf);
+        }
+        accepted.sort_by(|a, b| b.log_probability.total_cmp(&a.log_probability));
+        self.configurations = accepted;
+    }
+
+    fn write_into(
+        &self,
+        conf: &MarginalConfiguration,
+        composition: &mut ChemicalComposition<'lifespan>,
+    ) {
+        for (c, (num, _, _)) in conf.counts.iter().zip(self.isotopes.iter()) {
+            if *c > 0 {
+                composition.inc(ElementSpecification::new(self.element, *num), *c);
+            }
+        }
+    }
+}
+
+fn build_marginals<'lifespan>(
+    composition: &ChemicalComposition<'lifespan>,
+) -> Vec<Marginal<'lifespan>> {
+    let mut marginals: Vec<Marginal<'lifespan>> = Vec::with_capacity(composition.len());
+    for (elt, cnt) in composition.iter() {
+        if *cnt <= 0 {
+            continue;
+        }
+        if elt.isotope != 0 {
+            marginals.push(Marginal::fixed(elt.element, elt.isotope, *cnt));
+        } else {
+            marginals.push(Marginal::new(elt.element, *cnt));
+        }
+    }
+    marginals
+}
+
+fn enumerate_threshold<'lifespan>(
+    marginals: &mut [Marginal<'lifespan>],
+    log_threshold: f64,
+) -> Vec<Isotopologue<'lifespan>> {
+    let total_max: f64 = marginals.iter().map(|m| m.max_log_probability()).sum();
+    for m in marginals.iter_mut() {
+        let cutoff = log_threshold - (total_max - m.max_log_probability());
+        m.explore(cutoff);
+    }
+
+    // The best log probability achievable by the elements after position `i`
+    let mut remaining_max = vec![0.0; marginals.len() + 1];
+    for i in (0..marginals.len()).rev() {
+        remaining_max[i] = remaining_max[i + 1] + marginals[i].max_log_probability();
+    }
+
+    let mut acc = Vec::new();
+    let mut stack: Vec<&MarginalConfiguration> = Vec::with_capacity(marginals.len());
+    combine(
+        marginals,
+        &remaining_max,
+        log_threshold,
+        0,
+        0.0,
+        &mut stack,
+        &mut acc,
+    );
+    acc
+}
+
+fn combine<'a, 'lifespan>(
+    marginals: &'a [Marginal<'lifespan>],
+    remaining_max: &[f64],
+    log_threshold: f64,
+    depth: usize,
+    log_probability: f64,
+    stack: &mut Vec<&'a MarginalConfiguration>,
+    acc: &mut Vec<Isotopologue<'lifespan>>,
+) {
+    if depth == marginals.len() {
+        let mut composition = ChemicalComposition::new();
+        let mut mass = 0.0;
+        for (marginal, conf) in marginals.iter().zip(stack.iter()) {
+            mass += conf.mass;
+            marginal.write_into(conf, &mut composition);
+        }
+        acc.push(Isotopologue {
+            mass,
+            probability: log_probability.exp(),
+            composition,
+        });
+        return;
+    }
+    for conf in marginals[depth].configurations.iter() {
+        let lp = log_probability + conf.log_probability;
+        if lp + remaining_max[depth + 1] < log_threshold {
+            break;
+        }
+        stack.push(conf);
+        combine(
+            marginals,
+            remaining_max,
+            log_threshold,
+            depth + 1,
+            lp,
+            stack,
+            acc,
+        );
+        stack.pop();
+    }
+}
+
+/**
+Enumerate the isotopologues of `composition` satisfying `stop_condition`, sorted by
+ascending mass.
+
+Elements with fixed isotopes contribute only that isotope. Elements with non-positive
+counts are ignored.
+*/
+pub fn isotopologues<'lifespan, C: Into<ChemicalComposition<'lifespan>>>(
+    composition: C,
+    stop_condition: FineStructureStopCondition,
+) -> Vec<Isotopologue<'lifespan>> {
+    let composition = composition.into();
+    let marginals = build_marginals(&composition);
+
+    let mut result = match stop_condition {
+        FineStructureStopCondition::Threshold(threshold) => {
+            let mut marginals = marginals;
+            enumerate_threshold(&mut marginals, threshold.max(MIN_THRESHOLD).ln())
+        }
+        FineStructureStopCondition::TotalProbability(target) => {
+            let target = target.min(MAX_TOTAL_PROBABILITY);
+            let min_log_threshold = MIN_THRESHOLD.ln();
+            let mut log_threshold = INITIAL_THRESHOLD.ln();
+            let mut pass = 0;
+            loop {
+                pass += 1;
+                let mut trial = marginals.clone();
+                let mut acc = enumerate_threshold(&mut trial, log_threshold);
+                let total: f64 = acc.iter().map(|i| i.probability).sum();
+                if total >= target || pass >= MAX_PASSES {
+                    acc.sort_by(|a, b| b.probability.total_cmp(&a.probability));
+                    let mut running = 0.0;
+                    let mut keep = acc.len();
+                    for (i, iso) in acc.iter().enumerate() {
+                        running += iso.probability;
+                        if running >= target {
+                            keep = i + 1;
+                            break;
+                        }
+                    }
+                    acc.truncate(keep);
+                    break acc;
+                }
+                log_threshold = (log_threshold + THRESHOLD_STEP.ln()).max(min_log_threshold);
+            }
+        }
+    };
+    result.sort_by(|a, b| a.mass.total_cmp(&b.mass));
+    result
+}
+
+/**
+Generate the isotopic fine structure of `composition` at the specified charge state,
+one peak per isotopologue.
+
+The peak intensities are the isotopologue probabilities, and are not re-normalized,
+so their sum reflects the fraction of the total distribution covered.
+*/
+pub fn fine_structure<'lifespan, C: Into<ChemicalComposition<'lifespan>>>(
+    composition: C,
+    stop_condition: FineStructureStopCondition,
+    charge: i32,
+    charge_carrier: f64,
+) -> TheoreticalIsotopicPattern {
+    let isotopologues = isotopologues(composition, stop_condition);
+    let mut peaks: PeakList = isotopologues
+        .iter()
+        .map(|iso| iso.to_peak(charge, charge_carrier))
+        .collect();
+    peaks.sort_by(|a, b| a.mz.total_cmp(&b.mz));
+    let origin = peaks.first().map(|p| p.mz).unwrap_or_default();
+    TheoreticalIsotopicPattern::new(peaks, origin)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::isotopic_pattern::isotopic_variants;
+    use crate::PROTON;
+
+    #[test]
+    fn test_fine_structure_matches_coarse() {
+        let comp = ChemicalComposition::parse("C34H53O15N7S1").unwrap();
+        let fine = isotopologues(comp.clone(), FineStructureStopCondition::Threshold(1e-9));
+        let total: f64 = fine.iter().map(|i| i.probability).sum();
+        assert!((total - 1.0).abs() < 1e-5);
+
+        let mono = &fine[0];
+        assert!((mono.mass - comp.mass()).abs() < 1e-6);
+
+        let coarse = isotopic_variants(comp.clone(), 4, 0, PROTON);
+        let m1: f64 = fine
+            .iter()
+            .filter(|i| (i.mass - comp.mass() - 1.0).abs() < 0.1)
+            .map(|i| i.probability)
+            .sum();
+        let coarse_m1 = coarse[1].intensity / coarse.iter().map(|p| p.intensity).sum::<f64>();
+        assert!((m1 - coarse_m1).abs() < 1e-2);
+    }
+
+    #[test]
+    fn test_fine_structure_separates_isotopologues() {
+        let comp = ChemicalComposition::parse("C6H12O6N1").unwrap();
+        let fine = isotopologues(comp, FineStructureStopCondition::Threshold(1e-4));
+        let heavy_atoms = |iso: &Isotopologue| -> i32 {
+            iso.composition
+                .iter()
+                .filter(|(k, _)| k.isotope != k.element.most_abundant_isotope)
+                .map(|(_, v)| *v)
+                .sum()
+        };
+        let c13 = fine
+            .iter()
+            .find(|i| heavy_atoms(i) == 1 && i.composition["C[13]"] == 1)
+            .unwrap();
+        let n15 = fine
+            .iter()
+            .find(|i| heavy_atoms(i) == 1 && i.composition["N[15]"] == 1)
+            .unwrap();
+        assert!((c13.mass - n15.mass).abs() > 1e-3);
+        assert!((c13.composition.mass() - c13.mass).abs() < 1e-9);
+        assert!(c13.probability > n15.probability);
+    }
+
+    #[test]
+    fn test_total_probability() {
+        let comp = ChemicalComposition::parse("C100H160N30O30S2").unwrap();
+        let fine = isotopologues(
+            comp.clone(),
+            FineStructureStopCondition::TotalProbability(0.99),
+        );
+        let total: f64 = fine.iter().map(|i| i.probability).sum();
+        assert!(total >= 0.99);
+        let pattern = fine_structure(
+            comp,
+            FineStructureStopCondition::TotalProbability(0.99),
+            2,
+            PROTON,
+        );
+        assert_eq!(pattern.len(), fine.len());
+        assert!(pattern.iter().all(|p| p.charge == 2));
+    }
+
+    #[test]
+    fn test_thresholds() {
+        // The last pass of `TotalProbability` lands on the smallest threshold
+        let last = INITIAL_THRESHOLD * THRESHOLD_STEP.powi(MAX_PASSES as i32 - 1);
+        assert!((last / MIN_THRESHOLD - 1.0).abs() < 1e-9, "{last}");
+
+        let comp = ChemicalComposition::parse("C6H12O6").unwrap();
+        let floor = isotopologues(
+            comp.clone(),
+            FineStructureStopCondition::Threshold(MIN_THRESHOLD),
+        );
+        for threshold in [0.0, -1.0, f64::NAN, 1e-300] {
+            let clamped = isotopologues(
+                comp.clone(),
+                FineStructureStopCondition::Threshold(threshold),
+            );
+            assert_eq!(clamped.len(), floor.len(), "{threshold}");
+        }
+    }
+
+    #[test]
+    fn test_total_probability_exhaustive() {
+        let comp = ChemicalComposition::parse("C34H53O15N7").unwrap();
+        let fine = isotopologues(
+            comp.clone(),
+            FineStructureStopCondition::TotalProbability(1.0),
+        );
+        let total: f64 = fine.iter().map(|i| i.probability).sum();
+        assert!((total - 1.0).abs() < 1e-9, "{total}");
+        let partial = isotopologues(comp, FineStructureStopCondition::TotalProbability(0.99));
+        assert!(fine.len() > partial.len());
+    }
+}
@@ -0,0 +1,253 @@
+//! Generate isotopic patterns for metabolically labeled compositions, where some or all
+//! atoms of an element are replaced by a heavy isotope with a given incorporation
+//! efficiency, as in SILAC, <sup>15</sup>N metabolic labeling or <sup>13</sup>C tracer
+//! experiments.
+//!
+//! ```rust
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use chemical_elements::{ChemicalComposition, PROTON};
+//! use chemical_elements::isotopic_pattern::labeling::{labeled_isotopic_variants, LabelingSpecification};
+//!
+//! // Lysine labeled with 13C6 15N2 at 99% purity, as used in SILAC
+//! let lysine = ChemicalComposition::parse("C6H14N2O2")?;
+//! let labels = [
+//!     LabelingSpecification::positions("C", 13, 6, 0.99),
+//!     LabelingSpecification::positions("N", 15, 2, 0.99),
+//! ];
+//! let pattern = labeled_isotopic_variants(&lysine, &labels, 0, 1, PROTON)?;
+//! let base_peak = pattern.iter().max_by(|a, b| a.intensity.total_cmp(&b.intensity)).unwrap();
+//! assert!((base_peak.mz - pattern.origin - 8.0).abs() < 0.02);
+//! # Ok(())
+//! # }
+//! ```
+use std::fmt::{self, Display};
+
+use crate::enrichment::EnrichmentError;
+use crate::isotopic_pattern::{
+    isotopic_variants_anchored, IsotopicAnchor, TheoreticalIsotopicPattern,
+};
+use crate::{ChemicalComposition, ElementSpecification, PeriodicTable};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LabelingError {
+    /// The labeled element is not in the composition
+    UnknownElement(String),
+    /// More positions were labeled than there are atoms of the element in the composition
+    TooManyPositions {
+        symbol: String,
+        requested: i32,
+        available: i32,
+    },
+    /// The labeled isotope or enrichment was invalid for the element
+    InvalidEnrichment(EnrichmentError),
+}
+
+impl Display for LabelingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownElement(symbol) => {
+                write!(f, "labeled element `{symbol}` is not in the composition")
+            }
+            Self::TooManyPositions {
+                symbol,
+                requested,
+                available,
+            } => write!(
+                f,
+                "cannot label {requested} positions of `{symbol}`, only {available} are available"
+            ),
+            Self::InvalidEnrichment(err) => write!(f, "invalid label enrichment: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for LabelingError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::InvalidEnrichment(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<EnrichmentError> for LabelingError {
+    fn from(value: EnrichmentError) -> Self {
+        Self::InvalidEnrichment(value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/**
+Describes how an element of a composition is labeled with a heavy isotope.
+
+Each labeled position incorporates `isotope` with probability `enrichment`, and otherwise
+carries the element's natural isotopic distribution.
+*/
+pub struct LabelingSpecification {
+    /// The symbol of the labeled element
+    pub symbol: String,
+    /// The mass number of the heavy isotope
+    pub isotope: u16,
+    /// The number of labeled positions, or `None` to label every atom of the element
+    pub positions: Option<i32>,
+    /// The fraction of labeled positions which incorporate `isotope`, in `[0, 1]`
+    pub enrichment: f64,
+}
+
+impl LabelingSpecification {
+    pub fn new(symbol: &str, isotope: u16, positions: Option<i32>, enrichment: f64) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            isotope,
+            positions,
+            enrichment,
+        }
+    }
+
+    /// Label every atom of the element, like uniform <sup>15</sup>N metabolic labeling
+    pub fn all(symbol: &str, isotope: u16, enrichment: f64) -> Self {
+        Self::new(symbol, isotope, None, enrichment)
+    }
+
+    /// Label exactly `positions` atoms of the element, like a <sup>13</sup>C<sub>6</sub> tracer
+    pub fn positions(symbol: &str, isotope: u16, positions: i32, enrichment: f64) -> Self {
+        Self::new(symbol, isotope, Some(positions), enrichment)
+    }
+}
+
+/**
+Generate the isotopic pattern of `composition` with the atoms described by `labels`
+replaced by enriched variants of their elements. Multiple specifications may label the
+same element, so long as they do not label more atoms than the composition contains.
+
+Incomplete incorporation produces isotopologues lighter than the most abundant one, so
+the pattern is anchored on the lightest isotopologue as with
+[`IsotopicAnchor::Monoisotopic`], and the pattern's origin is the m/z of the lightest
+isotopologue, which is the unlabeled one unless incorporation is complete. If `npeaks`
+is 0, a guess that spans the labeled peaks will be used.
+*/
+pub fn labeled_isotopic_variants(
+    composition: &ChemicalComposition,
+    labels: &[LabelingSpecification],
+    npeaks: i32,
+    charge: i32,
+    charge_carrier: f64,
+) -> Result<TheoreticalIsotopicPattern, LabelingError> {
+    let mut variants = PeriodicTable::new();
+    let mut assignments = Vec::with_capacity(labels.len());
+    for (i, label) in labels.iter().enumerate() {
+        let (spec, available) = composition
+            .iter()
+            .find(|(elt, _)| elt.isotope == 0 && elt.element.symbol == label.symbol)
+            .map(|(elt, cnt)| (*elt, *cnt))
+            .ok_or_else(|| LabelingError::UnknownElement(label.symbol.clone()))?;
+        let labeled: i32 = assignments
+            .iter()
+            .filter(|(s, _, _)| *s == spec)
+            .map(|(_, _, count)| *count)
+            .sum();
+        let requested = label.positions.unwrap_or(available - labeled);
+        if requested < 0 || labeled + requested > available {
+            return Err(LabelingError::TooManyPositions {
+                symbol: label.symbol.clone(),
+                requested,
+                available: available - labeled,
+            });
+        }
+        let symbol = format!("{}*{}", label.symbol, i);
+        variants.add(
+            spec.element
+                .enriched(&symbol, label.isotope, label.enrichment)?,
+        );
+        assignments.push((spec, symbol, requested));
+    }
+
+    let mut labeled = composition.clone();
+    for (spec, symbol, count) in assignments {
+        labeled.inc(spec, -count);
+        labeled.inc(
+            ElementSpecification::new(&variants[symbol.as_str()], 0),
+            count,
+        );
+    }
+    Ok(isotopic_variants_anchored(
+        labeled,
+        npeaks,
+        charge,
+        charge_carrier,
+        IsotopicAnchor::Monoisotopic,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::PROTON;
+
+    #[test]
+    fn test_uniform_labeling() {
+        let peptide = ChemicalComposition::parse("C34H53O15N7").unwrap();
+        let labels = [LabelingSpecification::all("N", 15, 0.95)];
+        let pattern = labeled_isotopic_variants(&peptide, &labels, 0, 1, PROTON).unwrap();
+        let intensities: Vec<f64> = pattern.iter().map(|p| p.intensity).collect();
+        let total: f64 = intensities.iter().sum();
+        assert!((total - 1.0).abs() < 1e-6);
+
+        // The fully labeled isotopologue is 7 Da heavier than the unlabeled origin, and
+        // its neighbor missing one 15N atom is about 7 * 0.05 / 0.95 of its height
+        let full = pattern
+            .iter()
+            .find(|p| ((p.mz - pattern.origin) - 7.0).abs() < 0.1)
+            .unwrap();
+        let missing_one = pattern
+            .iter()
+            .find(|p| ((p.mz - pattern.origin) - 6.0).abs() < 0.1)
+            .unwrap();
+        let ratio = missing_one.intensity / full.intensity;
+        assert!(ratio > 0.3 && ratio < 0.45, "{ratio}");
+    }
+
+    #[test]
+    fn test_positions() {
+        let glucose = ChemicalComposition::parse("C6H12O6").unwrap();
+        let pattern = labeled_isotopic_variants(
+            &glucose,
+            &[LabelingSpecification::positions("C", 13, 2, 1.0)],
+            4,
+            0,
+            PROTON,
+        )
+        .unwrap();
+        // With complete incorporation nothing is lighter than the doubly labeled isotopologue
+        assert!((pattern[0].mz - glucose.mass() - 2.0 * 1.0033548).abs() < 1e-4);
+
+        let error = labeled_isotopic_variants(
+            &glucose,
+            &[
+                LabelingSpecification::positions("C", 13, 4, 0.99),
+                LabelingSpecification::positions("C", 13, 3, 0.5),
+            ],
+            0,
+            0,
+            PROTON,
+        );
+        assert!(matches!(
+            error,
+            Err(LabelingError::TooManyPositions { available: 2, .. })
+        ));
+        assert_eq!(
+            error.unwrap_err().to_string(),
+            "cannot label 3 positions of `C`, only 2 are available"
+        );
+        assert!(matches!(
+            labeled_isotopic_variants(
+                &glucose,
+                &[LabelingSpecification::all("N", 15, 0.9)],
+                0,
+                0,
+                PROTON
+            ),
+            Err(LabelingError::UnknownElement(_))
+        ));
+    }
+}
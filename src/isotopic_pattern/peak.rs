@@ -6,7 +6,7 @@ use std::ops::Range;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
-use crate::mz::{neutral_mass, PROTON};
+use crate::mz::{mass_charge_ratio_with, neutral_mass, neutral_mass_with, IonizationMode, PROTON};
 
 #[derive(Debug, Clone, Copy, Default)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -72,6 +72,16 @@ impl Peak {
             neutral_mass(self.mz, self.charge, PROTON)
         }
     }
+
+    #[inline]
+    /// Compute the neutral mass of this peak assuming it was charged by `mode`
+    pub fn neutral_mass_with(&self, mode: IonizationMode) -> f64 {
+        if self.charge == 0 {
+            self.mz
+        } else {
+            neutral_mass_with(self.mz, self.charge, mode)
+        }
+    }
 }
 
 #[cfg(feature = "mzpeaks")]
@@ -104,21 +114,55 @@ mod mzpeaks_interface {
 
 pub type PeakList = Vec<Peak>;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/**
+The isotopic peak a theoretical isotopic pattern is anchored on, which its `origin` refers to.
+*/
+pub enum IsotopicAnchor {
+    /// The peak made up of each element's most abundant isotope, as traditionally
+    /// computed by BRAIN. For compositions containing elements like Fe, Se or Sn,
+    /// lighter isotopic peaks are omitted.
+    #[default]
+    MostAbundant,
+    /// The peak made up of each element's lightest isotope, the true monoisotopic peak
+    Monoisotopic,
+}
+
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 /**
 A theoretical isotopic pattern that supports a variety of mutating
 transformations.
+
+Build one with [`TheoreticalIsotopicPattern::new`] or from a [`PeakList`], as fields
+may be added over time.
 */
+#[non_exhaustive]
 pub struct TheoreticalIsotopicPattern {
     pub peaks: PeakList,
+    /// The m/z of the peak this pattern is anchored on
     pub origin: f64,
+    /// Which isotopic peak [`TheoreticalIsotopicPattern::origin`] refers to
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub anchor: IsotopicAnchor,
 }
 
 impl TheoreticalIsotopicPattern {
     #[inline]
     pub fn new(peaks: PeakList, origin: f64) -> TheoreticalIsotopicPattern {
-        TheoreticalIsotopicPattern { peaks, origin }
+        TheoreticalIsotopicPattern {
+            peaks,
+            origin,
+            anchor: IsotopicAnchor::default(),
+        }
+    }
+
+    #[inline]
+    /// Record which isotopic peak this pattern's `origin` refers to
+    pub fn with_anchor(mut self, anchor: IsotopicAnchor) -> TheoreticalIsotopicPattern {
+        self.anchor = anchor;
+        self
     }
 
     #[inline]
@@ -144,6 +188,7 @@ impl TheoreticalIsotopicPattern {
         let result = TheoreticalIsotopicPattern {
             peaks,
             origin: self.origin,
+            anchor: self.anchor,
         };
         result.normalize()
     }
@@ -153,7 +198,7 @@ impl TheoreticalIsotopicPattern {
      */
     pub fn slice_normalized(&self, range: Range<usize>) -> Self {
         let slc = &self.peaks[range];
-        let subset = Self::new(slc.to_vec(), self.origin);
+        let subset = Self::new(slc.to_vec(), self.origin).with_anchor(self.anchor);
         subset.normalize()
     }
 
@@ -189,6 +234,26 @@ impl TheoreticalIsotopicPattern {
         TheoreticalIsotopicPattern::new(peaks, self.origin + offset)
     }
 
+    /**
+    Convert each peak, assumed to have been charged by `from`, to the m/z it would have
+    with `charge` charges gained through `to`, like when simulating charge reduction
+    or comparing protonated and radical ions.
+
+    The sign of `charge` is normalized by `to`.
+    */
+    pub fn recharge(mut self, charge: i32, from: IonizationMode, to: IonizationMode) -> TheoreticalIsotopicPattern {
+        let charge = to.normalize_charge(charge);
+        if let Some(first) = self.peaks.first() {
+            let origin = Peak { mz: self.origin, ..*first };
+            self.origin = mass_charge_ratio_with(origin.neutral_mass_with(from), charge, to);
+        }
+        for peak in &mut self {
+            peak.mz = mass_charge_ratio_with(peak.neutral_mass_with(from), charge, to);
+            peak.charge = charge;
+        }
+        self
+    }
+
     #[inline]
     /**Compute the sum of the intensities for this peak list*/
     pub fn total(&self) -> f64 {
@@ -477,4 +542,18 @@ mod test {
         assert_eq!(forms.len(), 6);
         assert_eq!(forms.last().unwrap().len(), 3);
     }
+
+    #[test]
+    fn test_recharge() {
+        let peaks = make_tid();
+        let neutral = peaks[0].neutral_mass();
+        let radical = peaks.clone().recharge(1, IonizationMode::Protonation, IonizationMode::ElectronRemoval);
+        assert_eq!(radical[0].charge, 1);
+        assert!((radical[0].neutral_mass_with(IonizationMode::ElectronRemoval) - neutral).abs() < 1e-9);
+        assert!((radical[0].mz - (neutral - crate::mz::ELECTRON)).abs() < 1e-9);
+
+        let anion = peaks.recharge(3, IonizationMode::Protonation, IonizationMode::Deprotonation);
+        assert_eq!(anion[0].charge, -3);
+        assert!((anion[0].neutral_mass() - neutral).abs() < 1e-9);
+    }
 }
@@ -0,0 +1,329 @@
+//! Render a [`TheoreticalIsotopicPattern`] as a profile-mode spectrum by convolving
+//! each [`Peak`](crate::isotopic_pattern::Peak) with a line shape whose width derives
+//! from the instrument's [`ResolvingPower`], optionally adding seeded noise.
+//!
+//! ```rust
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use chemical_elements::{ChemicalComposition, PROTON};
+//! use chemical_elements::isotopic_pattern::{isotopic_variants, TheoreticalIsotopicPattern};
+//! use chemical_elements::isotopic_pattern::profile::{NoiseModel, PeakShape, ProfileRenderer};
+//! use chemical_elements::isotopic_pattern::resolution::ResolvingPower;
+//!
+//! let pattern: TheoreticalIsotopicPattern =
+//!     isotopic_variants(ChemicalComposition::parse("C34H53O15N7")?, 0, 2, PROTON).into();
+//! let renderer = ProfileRenderer::new(PeakShape::Gaussian, ResolvingPower::orbitrap(60_000.0, 200.0))
+//!     .with_noise(NoiseModel::Gaussian { sigma: 0.001 })
+//!     .with_seed(42);
+//! let spectrum = renderer.render(&pattern);
+//! assert_eq!(spectrum.mz.len(), spectrum.intensity.len());
+//! # Ok(())
+//! # }
+//! ```
+use std::f64::consts::{LN_2, PI};
+
+use crate::isotopic_pattern::resolution::ResolvingPower;
+use crate::isotopic_pattern::TheoreticalIsotopicPattern;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// The line shape used to broaden each centroid
+pub enum PeakShape {
+    Gaussian,
+    Lorentzian,
+    /// A linear combination of a Lorentzian and a Gaussian of the same width, where `eta`
+    /// is the Lorentzian fraction in `[0, 1]`. Values outside that range are clamped to it.
+    PseudoVoigt {
+        eta: f64,
+    },
+}
+
+impl PeakShape {
+    /// Evaluate the line shape with unit height centered at `center` with
+    /// full-width at half maximum `fwhm` at `x`
+    pub fn evaluate(&self, x: f64, center: f64, fwhm: f64) -> f64 {
+        let delta = x - center;
+        match self {
+            PeakShape::Gaussian => {
+                let sigma_sq = fwhm * fwhm / (8.0 * LN_2);
+                (-(delta * delta) / (2.0 * sigma_sq)).exp()
+            }
+            PeakShape::Lorentzian => {
+                let half = fwhm / 2.0;
+                half * half / (delta * delta + half * half)
+            }
+            PeakShape::PseudoVoigt { eta } => {
+                let eta = eta.clamp(0.0, 1.0);
+                eta * PeakShape::Lorentzian.evaluate(x, center, fwhm)
+                    + (1.0 - eta) * PeakShape::Gaussian.evaluate(x, center, fwhm)
+            }
+        }
+    }
+
+    /// How many multiples of the FWHM away from the center the shape must be
+    /// evaluated before its contribution becomes negligible
+    fn support(&self) -> f64 {
+        match self {
+            PeakShape::Gaussian => 4.0,
+            PeakShape::Lorentzian | PeakShape::PseudoVoigt { .. } => 50.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+/// Additive noise applied to a rendered profile
+pub enum NoiseModel {
+    #[default]
+    None,
+    /// Zero-mean Gaussian noise with standard deviation `sigma`, in intensity units
+    Gaussian { sigma: f64 },
+    /// Shot noise where each intensity is treated as a Poisson count after multiplying
+    /// by `scale`, and divided by `scale` afterwards. A `scale` which is not a positive,
+    /// finite number adds no noise.
+    Poisson { scale: f64 },
+}
+
+/// A small, seedable SplitMix64 generator so that simulated spectra are reproducible
+#[derive(Debug, Clone)]
+struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    /// A uniform sample in `(0, 1]`
+    fn next_f64(&mut self) -> f64 {
+        ((self.next_u64() >> 11) as f64 + 1.0) / (1u64 << 53) as f64
+    }
+
+    fn next_gaussian(&mut self) -> f64 {
+        let u1 = self.next_f64();
+        let u2 = self.next_f64();
+        (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+    }
+
+    fn next_poisson(&mut self, lambda: f64) -> f64 {
+        if lambda <= 0.0 {
+            0.0
+        } else if lambda < 30.0 {
+            let limit = (-lambda).exp();
+            let mut k = 0.0;
+            let mut p = self.next_f64();
+            while p > limit {
+                k += 1.0;
+                p *= self.next_f64();
+            }
+            k
+        } else {
+            (lambda + lambda.sqrt() * self.next_gaussian())
+                .round()
+                .max(0.0)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+/// A profile spectrum sampled on an m/z grid
+pub struct ProfileSpectrum {
+    pub mz: Vec<f64>,
+    pub intensity: Vec<f64>,
+}
+
+impl ProfileSpectrum {
+    pub fn len(&self) -> usize {
+        self.mz.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mz.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (f64, f64)> + '_ {
+        self.mz.iter().copied().zip(self.intensity.iter().copied())
+    }
+}
+
+/// Generate an evenly spaced m/z grid from `start` to `end` inclusive
+pub fn mz_grid(start: f64, end: f64, spacing: f64) -> Vec<f64> {
+    if spacing <= 0.0 || end < start {
+        return Vec::new();
+    }
+    let n = ((end - start) / spacing).floor() as usize + 1;
+    (0..n).map(|i| start + i as f64 * spacing).collect()
+}
+
+#[derive(Debug, Clone)]
+/// Renders centroided isotopic patterns into profile spectra
+pub struct ProfileRenderer {
+    pub shape: PeakShape,
+    pub resolving_power: ResolvingPower,
+    pub noise: NoiseModel,
+    pub seed: u64,
+    /// The number of grid points per FWHM used when generating a grid
+    pub points_per_fwhm: f64,
+}
+
+impl ProfileRenderer {
+    pub fn new(shape: PeakShape, resolving_power: ResolvingPower) -> Self {
+        Self {
+            shape,
+            resolving_power,
+            noise: NoiseModel::None,
+            seed: 0,
+            points_per_fwhm: 10.0,
+        }
+    }
+
+    pub fn with_noise(mut self, noise: NoiseModel) -> Self {
+        self.noise = noise;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn with_points_per_fwhm(mut self, points_per_fwhm: f64) -> Self {
+        self.points_per_fwhm = points_per_fwhm;
+        self
+    }
+
+    /**
+    Render `pattern` on a generated grid spanning the peaks plus a margin of several
+    FWHM on either side, sampled at [`ProfileRenderer::points_per_fwhm`] using the
+    width at the first peak.
+    */
+    pub fn render(&self, pattern: &TheoreticalIsotopicPattern) -> ProfileSpectrum {
+        let (lo, hi) = pattern
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), p| {
+                (lo.min(p.mz), hi.max(p.mz))
+            });
+        if !lo.is_finite() {
+            return ProfileSpectrum::default();
+        }
+        let margin = 5.0 * self.resolving_power.fwhm(hi);
+        let spacing = self.resolving_power.fwhm(lo) / self.points_per_fwhm;
+        let grid = mz_grid(lo - margin, hi + margin, spacing);
+        self.render_on(pattern, &grid)
+    }
+
+    /// Render `pattern` on the provided m/z grid, which must be sorted in ascending order
+    pub fn render_on(
+        &self,
+        pattern: &TheoreticalIsotopicPattern,
+        mz_grid: &[f64],
+    ) -> ProfileSpectrum {
+        let mut intensity = vec![0.0; mz_grid.len()];
+        for peak in pattern.iter() {
+            let fwhm = self.resolving_power.fwhm(peak.mz);
+            let reach = fwhm * self.shape.support();
+            let start = mz_grid.partition_point(|mz| *mz < peak.mz - reach);
+            let end = mz_grid.partition_point(|mz| *mz <= peak.mz + reach);
+            for (mz, int) in mz_grid[start..end]
+                .iter()
+                .zip(intensity[start..end].iter_mut())
+            {
+                *int += peak.intensity * self.shape.evaluate(*mz, peak.mz, fwhm);
+            }
+        }
+        self.apply_noise(&mut intensity);
+        ProfileSpectrum {
+            mz: mz_grid.to_vec(),
+            intensity,
+        }
+    }
+
+    fn apply_noise(&self, intensity: &mut [f64]) {
+        let mut rng = SeededRng::new(self.seed);
+        match self.noise {
+            NoiseModel::None => {}
+            NoiseModel::Gaussian { sigma } => {
+                for int in intensity.iter_mut() {
+                    *int += sigma * rng.next_gaussian();
+                }
+            }
+            NoiseModel::Poisson { scale } if scale.is_finite() && scale > 0.0 => {
+                for int in intensity.iter_mut() {
+                    *int = rng.next_poisson(*int * scale) / scale;
+                }
+            }
+            NoiseModel::Poisson { .. } => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::isotopic_pattern::isotopic_variants;
+    use crate::{ChemicalComposition, PROTON};
+
+    fn make_pattern() -> TheoreticalIsotopicPattern {
+        let comp = ChemicalComposition::parse("C34H53O15N7").unwrap();
+        isotopic_variants(comp, 0, 1, PROTON).into()
+    }
+
+    #[test]
+    fn test_shapes() {
+        for shape in [
+            PeakShape::Gaussian,
+            PeakShape::Lorentzian,
+            PeakShape::PseudoVoigt { eta: 0.3 },
+        ] {
+            assert!((shape.evaluate(100.0, 100.0, 0.01) - 1.0).abs() < 1e-12);
+            assert!((shape.evaluate(100.005, 100.0, 0.01) - 0.5).abs() < 1e-9);
+        }
+
+        let lorentzian = PeakShape::Lorentzian.evaluate(100.01, 100.0, 0.01);
+        let gaussian = PeakShape::Gaussian.evaluate(100.01, 100.0, 0.01);
+        assert_eq!(PeakShape::PseudoVoigt { eta: 1.5 }.evaluate(100.01, 100.0, 0.01), lorentzian);
+        assert_eq!(PeakShape::PseudoVoigt { eta: -0.5 }.evaluate(100.01, 100.0, 0.01), gaussian);
+    }
+
+    #[test]
+    fn test_render() {
+        let pattern = make_pattern();
+        let renderer = ProfileRenderer::new(
+            PeakShape::Gaussian,
+            ResolvingPower::orbitrap(60_000.0, 200.0),
+        );
+        let spectrum = renderer.render(&pattern);
+        let (apex_mz, apex) = spectrum.iter().max_by(|a, b| a.1.total_cmp(&b.1)).unwrap();
+        assert!((apex_mz - pattern[0].mz).abs() < renderer.resolving_power.fwhm(apex_mz) / 5.0);
+        assert!((apex - pattern[0].intensity).abs() < 1e-2);
+    }
+
+    #[test]
+    fn test_seeded_noise() {
+        let pattern = make_pattern();
+        let renderer = ProfileRenderer::new(PeakShape::Lorentzian, ResolvingPower::tof(20_000.0))
+            .with_noise(NoiseModel::Poisson { scale: 1000.0 })
+            .with_seed(7);
+        let a = renderer.render(&pattern);
+        let b = renderer.render(&pattern);
+        let c = renderer.clone().with_seed(8).render(&pattern);
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert!(a.intensity.iter().all(|i| *i >= 0.0));
+
+        let clean = renderer.clone().with_noise(NoiseModel::None).render(&pattern);
+        for scale in [0.0, -1.0, f64::NAN] {
+            let noisy = renderer
+                .clone()
+                .with_noise(NoiseModel::Poisson { scale })
+                .render(&pattern);
+            assert_eq!(noisy, clean);
+        }
+    }
+}
@@ -0,0 +1,200 @@
+//! Produce the centroids an instrument would observe at a given resolving power by
+//! merging isotopic fine structure peaks which the instrument could not separate.
+use crate::isotopic_pattern::fine::{fine_structure, FineStructureStopCondition};
+use crate::isotopic_pattern::{Peak, PeakList, TheoreticalIsotopicPattern};
+use crate::ChemicalComposition;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The family of mass analyzer, which determines how resolving power scales with m/z
+pub enum MassAnalyzer {
+    /// Resolving power falls with the square root of m/z
+    Orbitrap,
+    /// Resolving power falls linearly with m/z
+    FTICR,
+    /// Resolving power is approximately constant over m/z
+    TOF,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// The resolving power of an instrument, quoted at a reference m/z
+pub struct ResolvingPower {
+    /// The resolving power, `m / Δm` at full-width at half maximum
+    pub resolution: f64,
+    /// The m/z at which `resolution` is quoted
+    pub reference_mz: f64,
+    pub analyzer: MassAnalyzer,
+}
+
+impl ResolvingPower {
+    pub fn new(resolution: f64, reference_mz: f64, analyzer: MassAnalyzer) -> Self {
+        Self {
+            resolution,
+            reference_mz,
+            analyzer,
+        }
+    }
+
+    pub fn orbitrap(resolution: f64, reference_mz: f64) -> Self {
+        Self::new(resolution, reference_mz, MassAnalyzer::Orbitrap)
+    }
+
+    pub fn fticr(resolution: f64, reference_mz: f64) -> Self {
+        Self::new(resolution, reference_mz, MassAnalyzer::FTICR)
+    }
+
+    pub fn tof(resolution: f64) -> Self {
+        Self::new(resolution, 1.0, MassAnalyzer::TOF)
+    }
+
+    /// The resolving power at `mz`
+    pub fn at(&self, mz: f64) -> f64 {
+        match self.analyzer {
+            MassAnalyzer::Orbitrap => self.resolution * (self.reference_mz / mz).sqrt(),
+            MassAnalyzer::FTICR => self.resolution * self.reference_mz / mz,
+            MassAnalyzer::TOF => self.resolution,
+        }
+    }
+
+    /// The full-width at half maximum of a peak at `mz`
+    pub fn fwhm(&self, mz: f64) -> f64 {
+        mz / self.at(mz)
+    }
+}
+
+/**
+Merge the peaks of `pattern` which lie within one full-width at half maximum of
+each other at the specified resolving power.
+
+Clusters start from their lowest m/z peak and only take peaks within one full-width
+at half maximum of it, so a run of closely spaced peaks can't chain into a cluster wider
+than the instrument would blur together.
+
+Each merged peak is placed at the intensity-weighted mean m/z of its members and
+carries their summed intensity.
+*/
+pub fn merge_by_resolution(
+    pattern: TheoreticalIsotopicPattern,
+    resolving_power: &ResolvingPower,
+) -> TheoreticalIsotopicPattern {
+    let mut peaks = pattern.peaks;
+    peaks.sort_by(|a, b| a.mz.total_cmp(&b.mz));
+
+    let mut merged = PeakList::with_capacity(peaks.len());
+    let mut weighted_mz = 0.0;
+    let mut start_mz = 0.0;
+    let mut current: Option<Peak> = None;
+    for peak in peaks {
+        match current.as_mut() {
+            Some(cluster) if peak.mz - start_mz < resolving_power.fwhm(start_mz) => {
+                weighted_mz += peak.mz * peak.intensity;
+                cluster.intensity += peak.intensity;
+                if cluster.intensity > 0.0 {
+                    cluster.mz = weighted_mz / cluster.intensity;
+                }
+            }
+            _ => {
+                if let Some(cluster) = current.take() {
+                    merged.push(cluster);
+                }
+                weighted_mz = peak.mz * peak.intensity;
+                start_mz = peak.mz;
+                current = Some(peak);
+            }
+        }
+    }
+    if let Some(cluster) = current {
+        merged.push(cluster);
+    }
+    let origin = merged.first().map(|p| p.mz).unwrap_or(pattern.origin);
+    TheoreticalIsotopicPattern::new(merged, origin)
+}
+
+/**
+Generate the isotopic pattern of `composition` as it would be centroided by an instrument
+with the specified resolving power.
+
+The isotopic fine structure is computed subject to `stop_condition`, and then merged
+with [`merge_by_resolution`].
+*/
+pub fn isotopic_variants_at_resolution<'lifespan, C: Into<ChemicalComposition<'lifespan>>>(
+    composition: C,
+    resolving_power: &ResolvingPower,
+    stop_condition: FineStructureStopCondition,
+    charge: i32,
+    charge_carrier: f64,
+) -> TheoreticalIsotopicPattern {
+    let fine = fine_structure(composition, stop_condition, charge, charge_carrier);
+    merge_by_resolution(fine, resolving_power)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::isotopic_pattern::isotopic_variants;
+    use crate::PROTON;
+
+    #[test]
+    fn test_scaling() {
+        let orbi = ResolvingPower::orbitrap(240_000.0, 200.0);
+        assert!((orbi.at(800.0) - 120_000.0).abs() < 1e-6);
+        let ft = ResolvingPower::fticr(240_000.0, 200.0);
+        assert!((ft.at(800.0) - 60_000.0).abs() < 1e-6);
+        let tof = ResolvingPower::tof(40_000.0);
+        assert!((tof.fwhm(800.0) - 0.02).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_no_chaining() {
+        let resolving_power = ResolvingPower::tof(10_000.0);
+        let spacing = resolving_power.fwhm(1000.0) * 0.6;
+        let peaks: PeakList = (0..3)
+            .map(|i| Peak {
+                mz: 1000.0 + spacing * i as f64,
+                intensity: 1.0,
+                charge: 1,
+            })
+            .collect();
+        // Neighbours are within one FWHM of each other, but the outer peaks are not
+        let pattern = TheoreticalIsotopicPattern::new(peaks, 1000.0);
+        let merged = merge_by_resolution(pattern, &resolving_power);
+        assert_eq!(merged.len(), 2);
+        assert!((merged[0].mz - (1000.0 + spacing / 2.0)).abs() < 1e-9);
+        assert_eq!(merged[0].intensity, 2.0);
+        assert!((merged[1].mz - (1000.0 + spacing * 2.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_sulfur_fine_structure() {
+        let comp = ChemicalComposition::parse("C25H40N7O8S2").unwrap();
+        let stop = FineStructureStopCondition::TotalProbability(0.999);
+
+        let high = isotopic_variants_at_resolution(
+            comp.clone(),
+            &ResolvingPower::orbitrap(240_000.0, 200.0),
+            stop,
+            1,
+            PROTON,
+        );
+        let low = isotopic_variants_at_resolution(
+            comp.clone(),
+            &ResolvingPower::orbitrap(15_000.0, 200.0),
+            stop,
+            1,
+            PROTON,
+        );
+        let mono_mz = high[0].mz;
+        let near_m2 = |p: &&Peak| (p.mz - mono_mz - 2.0).abs() < 0.1;
+
+        // 34S and 13C2 are resolved at high resolution but not at low resolution
+        assert!(high.iter().filter(near_m2).count() >= 2);
+        assert_eq!(low.iter().filter(near_m2).count(), 1);
+
+        let coarse = isotopic_variants(comp, 3, 1, PROTON);
+        let coarse_total: f64 = coarse.iter().map(|p| p.intensity).sum();
+        let low_total: f64 = low.iter().take(3).map(|p| p.intensity).sum();
+        for (a, b) in low.iter().zip(coarse.iter()) {
+            assert!((a.mz - b.mz).abs() < 1e-3);
+            assert!((a.intensity / low_total - b.intensity / coarse_total).abs() < 1e-2);
+        }
+    }
+}
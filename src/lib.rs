@@ -31,25 +31,59 @@ assert_eq!(peaks.len(), 6);
 # }
 ```
 */
+// Lets the paths generated by `formula!` resolve within this crate
+extern crate self as chemical_elements;
+
+pub mod adduct;
+mod charged_composition;
 mod composition_map;
+pub mod decomposition;
 mod props;
 mod element;
+pub mod enrichment;
+pub mod glycan;
 mod formula;
+pub mod formula_format;
 mod helper;
 pub mod isotopic_pattern;
+pub mod lipid;
+pub mod nucleic_acid;
+pub mod peptide;
+pub mod smiles;
 mod mz;
 mod table;
+pub mod table_loader;
 mod element_specification;
 mod composition_list;
 mod abstract_composition;
 
-pub use crate::element_specification::{ElementSpecification, ElementSpecificationParsingError};
+pub use crate::element_specification::{
+    ElementSpecification, ElementSpecificationParsingError, ElementSpecificationParsingErrorKind,
+};
 pub use crate::composition_map::ChemicalCompositionMap as ChemicalCompositionMap;
 pub use crate::composition_list::ChemicalCompositionVec as ChemicalCompositionVec;
+pub use crate::adduct::{Adduct, IonType, IonTypeParserError};
+pub use crate::charged_composition::ChargedComposition;
 pub use crate::abstract_composition::{ChemicalComposition, ChemicalCompositionRef};
-pub use crate::element::{Element, Isotope, PeriodicTable};
-pub use crate::formula::{parse_formula, parse_formula_with_table, FormulaParserError};
-pub use crate::mz::{mass_charge_ratio, neutral_mass, PROTON};
+pub use crate::element::{Element, Isotope, PeriodicTable, StandardAtomicWeight};
+pub use crate::enrichment::EnrichmentError;
+pub use crate::formula_format::FormulaFormatter;
+pub use crate::glycan::{GlycanComposition, GlycanError, GlycanErrorKind};
+pub use crate::formula::{
+    parse_formula, parse_formula_with_table, FormulaParserError, FormulaParserErrorKind,
+};
+pub use crate::nucleic_acid::{NucleicAcid, NucleicAcidError, NucleicAcidErrorKind};
+pub use crate::peptide::{Peptide, PeptideError, PeptideErrorKind};
+pub use crate::smiles::{SmilesError, SmilesErrorKind};
+pub use crate::lipid::{Lipid, LipidError};
+pub use crate::mz::{
+    mass_charge_ratio, mass_charge_ratio_with, neutral_mass, neutral_mass_with, IonizationMode,
+    ELECTRON, PROTON,
+};
 pub use crate::table::PERIODIC_TABLE;
+pub use crate::table_loader::TableLoadError;
 pub use helper::ChemicalElements;
-pub use props::ChemicalCompositionLike;
\ No newline at end of file
+pub use props::ChemicalCompositionLike;
+
+#[cfg(feature = "macros")]
+pub use chemical_elements_macros::formula;
\ No newline at end of file
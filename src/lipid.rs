@@ -0,0 +1,484 @@
+//! Compute the elemental composition of lipids from LIPID MAPS shorthand nomenclature at
+//! the species level, like `PC 34:1`, or the molecular species level, like
+//! `TG 16:0_18:1_18:2` or `Cer d18:1/24:0`.
+//!
+//! ```rust
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use chemical_elements::{ChemicalComposition, IonType};
+//! use chemical_elements::lipid::{lipid_composition, Lipid};
+//!
+//! let composition = lipid_composition("PE O-38:4")?;
+//! assert_eq!(composition, ChemicalComposition::parse("C43H80NO7P")?);
+//!
+//! let lipid = Lipid::parse("PC 16:0_18:1")?;
+//! let ion_type: IonType = "[M+H]+".parse()?;
+//! assert!((lipid.ion_mz(&ion_type) - 760.5851).abs() < 1e-3);
+//! # Ok(())
+//! # }
+//! ```
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use crate::adduct::IonType;
+use crate::table::PERIODIC_TABLE;
+use crate::{ChemicalComposition, ElementSpecification, PeriodicTable};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LipidClass {
+    FA,
+    MG,
+    DG,
+    TG,
+    PA,
+    PC,
+    PE,
+    PG,
+    PI,
+    PS,
+    LPA,
+    LPC,
+    LPE,
+    LPG,
+    LPI,
+    LPS,
+    CE,
+    Cer,
+    SM,
+    HexCer,
+}
+
+impl LipidClass {
+    pub const ALL: [LipidClass; 20] = [
+        Self::FA,
+        Self::MG,
+        Self::DG,
+        Self::TG,
+        Self::PA,
+        Self::PC,
+        Self::PE,
+        Self::PG,
+        Self::PI,
+        Self::PS,
+        Self::LPA,
+        Self::LPC,
+        Self::LPE,
+        Self::LPG,
+        Self::LPI,
+        Self::LPS,
+        Self::CE,
+        Self::Cer,
+        Self::SM,
+        Self::HexCer,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::FA => "FA",
+            Self::MG => "MG",
+            Self::DG => "DG",
+            Self::TG => "TG",
+            Self::PA => "PA",
+            Self::PC => "PC",
+            Self::PE => "PE",
+            Self::PG => "PG",
+            Self::PI => "PI",
+            Self::PS => "PS",
+            Self::LPA => "LPA",
+            Self::LPC => "LPC",
+            Self::LPE => "LPE",
+            Self::LPG => "LPG",
+            Self::LPI => "LPI",
+            Self::LPS => "LPS",
+            Self::CE => "CE",
+            Self::Cer => "Cer",
+            Self::SM => "SM",
+            Self::HexCer => "HexCer",
+        }
+    }
+
+    /// Find the class named `name`, accepting the older glyceride names and specific
+    /// hexosylceramides
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "MAG" => Some(Self::MG),
+            "DAG" => Some(Self::DG),
+            "TAG" => Some(Self::TG),
+            "GlcCer" | "GalCer" => Some(Self::HexCer),
+            _ => Self::ALL.into_iter().find(|class| class.name() == name),
+        }
+    }
+
+    /// The number of chains the class carries, counting the sphingoid base of sphingolipids
+    pub fn chain_count(&self) -> usize {
+        match self {
+            Self::FA | Self::MG | Self::CE => 1,
+            Self::LPA | Self::LPC | Self::LPE | Self::LPG | Self::LPI | Self::LPS => 1,
+            Self::TG => 3,
+            _ => 2,
+        }
+    }
+
+    pub fn is_sphingolipid(&self) -> bool {
+        matches!(self, Self::Cer | Self::SM | Self::HexCer)
+    }
+
+    /// For glycerolipids and sterol esters, the formula of the molecule with free hydroxyls
+    /// in place of its acyl chains. For sphingolipids, the formula of the head group added
+    /// to the ceramide.
+    fn core_formula(&self) -> &'static str {
+        match self {
+            Self::FA => "H2O",
+            Self::MG | Self::DG | Self::TG => "C3H8O3",
+            Self::PA | Self::LPA => "C3H9O6P",
+            Self::PC | Self::LPC => "C8H20NO6P",
+            Self::PE | Self::LPE => "C5H14NO6P",
+            Self::PG | Self::LPG => "C6H15O8P",
+            Self::PI | Self::LPI => "C9H19O11P",
+            Self::PS | Self::LPS => "C6H14NO8P",
+            Self::CE => "C27H46O",
+            Self::Cer => "",
+            Self::SM => "C5H12NO3P",
+            Self::HexCer => "C6H10O5",
+        }
+    }
+}
+
+impl Display for LipidClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// How a chain is bound to the glycerol backbone
+pub enum ChainLinkage {
+    /// An ester, written without a prefix
+    #[default]
+    Acyl,
+    /// An alkyl ether, written with the prefix `O-`
+    Alkyl,
+    /// A 1Z-alkenyl ether of a plasmalogen, written with the prefix `P-`, whose vinyl
+    /// double bond is not counted among the chain's double bonds
+    Alkenyl,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/**
+A fatty acyl, ether or sphingoid base chain, or at the species level the sum of all of a
+lipid's chains.
+
+`oxygens` counts the additional oxygens written as `;O2`, except for a sphingoid base,
+where it counts the base's hydroxyls, also written with the prefixes `m`, `d` or `t`.
+The counts are `u16` so that the composition of any lipid fits in its element counts.
+*/
+pub struct Chain {
+    pub carbons: u16,
+    pub double_bonds: u16,
+    pub oxygens: u16,
+    pub linkage: ChainLinkage,
+}
+
+impl Chain {
+    pub fn new(carbons: u16, double_bonds: u16) -> Self {
+        Self {
+            carbons,
+            double_bonds,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LipidError {
+    /// The lipid class is not one of the supported [`LipidClass`]es
+    UnknownClass(String),
+    /// The chain could not be read as `C:DB` with optional prefix and suffixes
+    ChainMalformed(String),
+    /// The number of chains given does not match the class
+    ChainCountMismatch { expected: usize, found: usize },
+}
+
+impl Display for LipidError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownClass(name) => write!(f, "unknown lipid class `{name}`"),
+            Self::ChainMalformed(chain) => {
+                write!(f, "malformed chain `{chain}`, expected `C:DB`")
+            }
+            Self::ChainCountMismatch { expected, found } => write!(
+                f,
+                "expected 1 chain or {expected} chains for the class, found {found}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LipidError {}
+
+#[derive(Debug, Clone, PartialEq)]
+/**
+A lipid described by its class and chains. A single chain for a class carrying several
+is read as the species level sum of all chains, and for sphingolipids the first chain is
+the sphingoid base.
+*/
+pub struct Lipid {
+    pub class: LipidClass,
+    pub chains: Vec<Chain>,
+}
+
+impl Lipid {
+    pub fn new(class: LipidClass, chains: Vec<Chain>) -> Self {
+        Self { class, chains }
+    }
+
+    /**
+    Parse a shorthand name like `PC 34:1`, `PE O-38:4`, `TG 16:0_18:1_18:2` or
+    `Cer d18:1/24:0`. Chains are separated by `_`, or by `/` when their positions are
+    known, and double bond positions in parentheses, like `18:1(9Z)`, are ignored.
+
+    A sphingoid base without a hydroxyl count is taken to be a dihydroxy base.
+    */
+    pub fn parse(string: &str) -> Result<Self, LipidError> {
+        let string = string.trim();
+        let (name, chains) = string
+            .split_once(' ')
+            .ok_or_else(|| LipidError::ChainMalformed(string.to_string()))?;
+        let class = LipidClass::from_name(name)
+            .ok_or_else(|| LipidError::UnknownClass(name.to_string()))?;
+        let chains = chains
+            .trim()
+            .split(['_', '/'])
+            .enumerate()
+            .map(|(i, chain)| parse_chain(chain, class.is_sphingolipid() && i == 0))
+            .collect::<Result<Vec<_>, _>>()?;
+        if chains.len() != 1 && chains.len() != class.chain_count() {
+            return Err(LipidError::ChainCountMismatch {
+                expected: class.chain_count(),
+                found: chains.len(),
+            });
+        }
+        Ok(Self::new(class, chains))
+    }
+
+    /// Whether the chains are summed at the species level
+    pub fn is_species_level(&self) -> bool {
+        self.chains.len() < self.class.chain_count()
+    }
+
+    /// The composition of the neutral lipid using the global [`PeriodicTable`]
+    pub fn composition(&self) -> ChemicalComposition<'static> {
+        self.composition_with(&PERIODIC_TABLE)
+    }
+
+    /// The composition of the neutral lipid using the specified [`PeriodicTable`]
+    pub fn composition_with<'lifespan>(
+        &self,
+        periodic_table: &'lifespan PeriodicTable,
+    ) -> ChemicalComposition<'lifespan> {
+        let mut composition =
+            ChemicalComposition::parse_with(self.class.core_formula(), periodic_table)
+                .unwrap_or_default();
+        let mut inc = |symbol: &str, count: i32| {
+            composition.inc(ElementSpecification::new(&periodic_table[symbol], 0), count)
+        };
+        // A summed chain stands for all of the class's chains
+        let count = if self.is_species_level() {
+            self.class.chain_count() as i32
+        } else {
+            1
+        };
+        for (i, chain) in self.chains.iter().enumerate() {
+            let carbons = i32::from(chain.carbons);
+            let double_bonds = i32::from(chain.double_bonds);
+            let oxygens = i32::from(chain.oxygens);
+            inc("C", carbons);
+            if self.class.is_sphingolipid() && i == 0 {
+                if self.is_species_level() {
+                    // The sphingoid base less the amide's hydrogen, with the acyl chain
+                    inc("H", 2 * carbons + 1 - 2 * double_bonds);
+                    inc("O", oxygens + 1);
+                } else {
+                    inc("H", 2 * carbons + 2 - 2 * double_bonds);
+                    inc("O", oxygens);
+                }
+                inc("N", 1);
+                continue;
+            }
+            // Each acyl chain replaces a hydroxyl's hydrogen with its carbonyl
+            inc("H", 2 * carbons - 2 * double_bonds - count);
+            inc("O", count + oxygens);
+            match chain.linkage {
+                ChainLinkage::Acyl => {}
+                ChainLinkage::Alkyl => {
+                    inc("H", 2);
+                    inc("O", -1);
+                }
+                ChainLinkage::Alkenyl => inc("O", -1),
+            }
+            if !self.class.is_sphingolipid() {
+                inc("H", -count);
+            }
+        }
+        composition
+    }
+
+    /// The composition of the ion of `ion_type` formed from the lipid
+    pub fn ion_composition<'lifespan>(
+        &self,
+        ion_type: &IonType<'lifespan>,
+    ) -> ChemicalComposition<'lifespan> {
+        ion_type.ion_composition(&self.composition())
+    }
+
+    /// The m/z of the ion of `ion_type` formed from the lipid
+    pub fn ion_mz(&self, ion_type: &IonType) -> f64 {
+        ion_type.mass_charge_ratio(self.composition().mass())
+    }
+}
+
+impl FromStr for Lipid {
+    type Err = LipidError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+fn parse_chain(chain: &str, sphingoid_base: bool) -> Result<Chain, LipidError> {
+    let malformed = || LipidError::ChainMalformed(chain.to_string());
+    let mut rest = chain.trim();
+    let mut result = Chain::default();
+    if sphingoid_base {
+        result.oxygens = 2;
+        for (prefix, hydroxyls) in [("m", 1), ("d", 2), ("t", 3)] {
+            if let Some(r) = rest.strip_prefix(prefix) {
+                result.oxygens = hydroxyls;
+                rest = r;
+            }
+        }
+    } else if let Some(r) = rest.strip_prefix("O-") {
+        result.linkage = ChainLinkage::Alkyl;
+        rest = r;
+    } else if let Some(r) = rest.strip_prefix("P-") {
+        result.linkage = ChainLinkage::Alkenyl;
+        rest = r;
+    }
+
+    let (counts, suffix) = match rest.find([';', '(']) {
+        Some(i) => rest.split_at(i),
+        None => (rest, ""),
+    };
+    let (carbons, double_bonds) = counts.split_once(':').ok_or_else(malformed)?;
+    result.carbons = carbons.parse().map_err(|_| malformed())?;
+    result.double_bonds = double_bonds.parse().map_err(|_| malformed())?;
+    if result.double_bonds > result.carbons {
+        return Err(malformed());
+    }
+
+    let mut suffix = suffix;
+    if suffix.starts_with('(') {
+        let close = suffix.find(')').ok_or_else(malformed)?;
+        suffix = &suffix[close + 1..];
+    }
+    if let Some(oxygens) = suffix.strip_prefix(";O") {
+        result.oxygens = if oxygens.is_empty() {
+            1
+        } else {
+            oxygens.parse().map_err(|_| malformed())?
+        };
+    } else if !suffix.is_empty() {
+        return Err(malformed());
+    }
+    Ok(result)
+}
+
+/// The composition of the neutral lipid named by the shorthand `string`, using the global
+/// [`PeriodicTable`]. See [`Lipid::parse`].
+pub fn lipid_composition(string: &str) -> Result<ChemicalComposition<'static>, LipidError> {
+    Ok(Lipid::parse(string)?.composition())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_compositions() {
+        for (shorthand, formula) in [
+            ("PC 34:1", "C42H82NO8P"),
+            ("PC 16:0_18:1", "C42H82NO8P"),
+            ("PC 16:0/18:1(9Z)", "C42H82NO8P"),
+            ("PE 38:4", "C43H78NO8P"),
+            ("PE O-38:4", "C43H80NO7P"),
+            ("PE P-38:4", "C43H78NO7P"),
+            ("PE P-18:0_20:4", "C43H78NO7P"),
+            ("TG 16:0_18:1_18:2", "C55H100O6"),
+            ("TG 52:3", "C55H100O6"),
+            ("DG 34:1", "C37H70O5"),
+            ("LPC 16:0", "C24H50NO7P"),
+            ("PI 38:4", "C47H83O13P"),
+            ("PS 36:1", "C42H80NO10P"),
+            ("FA 18:1", "C18H34O2"),
+            ("FA 18:1;O", "C18H34O3"),
+            ("CE 18:1", "C45H78O2"),
+            ("Cer d18:1/24:0", "C42H83NO3"),
+            ("Cer 18:1;O2/24:0", "C42H83NO3"),
+            ("Cer 42:1;O2", "C42H83NO3"),
+            ("Cer t18:0/24:0", "C42H85NO4"),
+            ("SM d18:1/16:0", "C39H79N2O6P"),
+            ("SM 34:1;O2", "C39H79N2O6P"),
+            ("HexCer d18:1/16:0", "C40H77NO8"),
+        ] {
+            assert_eq!(
+                lipid_composition(shorthand).unwrap(),
+                ChemicalComposition::parse(formula).unwrap(),
+                "{shorthand}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_errors_and_ions() {
+        assert_eq!(
+            Lipid::parse("XY 34:1"),
+            Err(LipidError::UnknownClass("XY".to_string()))
+        );
+        assert_eq!(
+            Lipid::parse("TG 16:0_18:1"),
+            Err(LipidError::ChainCountMismatch {
+                expected: 3,
+                found: 2
+            })
+        );
+        assert_eq!(
+            Lipid::parse("PC 34"),
+            Err(LipidError::ChainMalformed("34".to_string()))
+        );
+        assert_eq!(
+            Lipid::parse("TG 16:0_18:1").unwrap_err().to_string(),
+            "expected 1 chain or 3 chains for the class, found 2"
+        );
+        assert_eq!(
+            Lipid::parse("PC 34").unwrap_err().to_string(),
+            "malformed chain `34`, expected `C:DB`"
+        );
+        assert!(Lipid::parse("PC 34:1;X").is_err());
+        for shorthand in ["FA 4294967295:0", "FA 65536:0", "FA 2:10", "FA 18:1;O65536"] {
+            assert!(
+                matches!(Lipid::parse(shorthand), Err(LipidError::ChainMalformed(_))),
+                "{shorthand}"
+            );
+        }
+        assert_eq!(
+            lipid_composition("FA 2:10").unwrap_err(),
+            LipidError::ChainMalformed("2:10".to_string())
+        );
+        assert!(Lipid::parse("PC 34:1").unwrap().is_species_level());
+
+        let lipid: Lipid = "TG 52:3".parse().unwrap();
+        let ammoniated: IonType = "[M+NH4]+".parse().unwrap();
+        let ion = lipid.ion_composition(&ammoniated);
+        assert_eq!(ion["N"], 1);
+        assert!((lipid.ion_mz(&ammoniated) - 874.7858).abs() < 1e-3);
+    }
+}
@@ -9,3 +9,64 @@ pub fn neutral_mass(mz: f64, z: i32, charge_carrier: f64) -> f64 {
     let zf: f64 = z as f64;
     return (mz * zf.abs()) - (zf * charge_carrier);
 }
+
+/// The mass of an electron
+pub const ELECTRON: f64 = 0.000548579909;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/**
+The process by which an analyte acquired its charge, which determines the mass of
+the charge carrier and the polarity of the charge.
+
+The charge carrier is the mass added per unit of *signed* charge in [`mass_charge_ratio`],
+so positive and negative modes of the same process share a carrier and differ only in
+the sign of the charge.
+*/
+pub enum IonizationMode {
+    /// Adding protons, `[M+zH]z+`
+    Protonation,
+    /// Removing protons, `[M-zH]z-`
+    Deprotonation,
+    /// Removing electrons, forming radical cations like `M+•`
+    ElectronRemoval,
+    /// Adding electrons, forming radical anions like `M-•`
+    ElectronAddition,
+    /// Adding singly charged metal cations whose neutral atom has the given mass, `[M+zNa]z+`
+    MetalCation(f64),
+}
+
+impl IonizationMode {
+    /// The mass added per unit of signed charge
+    pub fn charge_carrier(&self) -> f64 {
+        match self {
+            IonizationMode::Protonation | IonizationMode::Deprotonation => PROTON,
+            IonizationMode::ElectronRemoval | IonizationMode::ElectronAddition => -ELECTRON,
+            IonizationMode::MetalCation(mass) => mass - ELECTRON,
+        }
+    }
+
+    /// The sign of the charges this mode produces
+    pub fn polarity(&self) -> i32 {
+        match self {
+            IonizationMode::Protonation
+            | IonizationMode::ElectronRemoval
+            | IonizationMode::MetalCation(_) => 1,
+            IonizationMode::Deprotonation | IonizationMode::ElectronAddition => -1,
+        }
+    }
+
+    /// Give `z` the sign this mode produces, so that `2` and `-2` mean the same thing
+    pub fn normalize_charge(&self, z: i32) -> i32 {
+        z.abs() * self.polarity()
+    }
+}
+
+/// Compute the m/z of an ion formed by `mode`, with the sign of `z` taken from `mode`
+pub fn mass_charge_ratio_with(neutral_mass: f64, z: i32, mode: IonizationMode) -> f64 {
+    mass_charge_ratio(neutral_mass, mode.normalize_charge(z), mode.charge_carrier())
+}
+
+/// Compute the neutral mass of an ion formed by `mode`, with the sign of `z` taken from `mode`
+pub fn neutral_mass_with(mz: f64, z: i32, mode: IonizationMode) -> f64 {
+    neutral_mass(mz, mode.normalize_charge(z), mode.charge_carrier())
+}
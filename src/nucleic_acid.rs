@@ -0,0 +1,674 @@
+//! Compute the elemental composition of DNA and RNA oligonucleotides from their sequences,
+//! with terminal phosphates, backbone and sugar modifications, and fragment ladders.
+//!
+//! ```rust
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use chemical_elements::ChemicalComposition;
+//! use chemical_elements::nucleic_acid::{
+//!     IonSeries, NucleicAcid, NucleotideModification, Terminus,
+//! };
+//!
+//! let oligo = NucleicAcid::dna("ACGT");
+//! assert_eq!(oligo.composition()?, ChemicalComposition::parse("C39H50N15O22P3")?);
+//!
+//! let sirna = NucleicAcid::rna("GGCUUAGCU")
+//!     .with_five_prime(Terminus::Phosphate)
+//!     .with_modification(0, NucleotideModification::TwoPrimeOMethyl.composition())
+//!     .with_phosphorothioate(0);
+//! let fragments = sirna.fragments(&[IonSeries::C, IonSeries::Y], -2..=-1)?;
+//! let c3 = fragments.iter().find(|f| f.to_string() == "c3 2-").unwrap();
+//! assert_eq!(c3.composition["S"], 1);
+//! # Ok(())
+//! # }
+//! ```
+use std::fmt::{self, Display};
+use std::ops::{Range, RangeInclusive};
+
+use chemical_elements_grammar::write_diagnostic;
+
+use crate::formula::char_span;
+use crate::mz::{mass_charge_ratio, PROTON};
+use crate::table::PERIODIC_TABLE;
+use crate::{ChemicalComposition, PeriodicTable};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NucleicAcidKind {
+    DNA,
+    RNA,
+}
+
+impl NucleicAcidKind {
+    /// The formulas of the nucleoside and the free nucleobase of `code`, if it is a
+    /// nucleotide of this kind
+    fn formulas(&self, code: char) -> Option<(&'static str, &'static str)> {
+        match (self, code) {
+            (Self::DNA, 'A') => Some(("C10H13N5O3", "C5H5N5")),
+            (Self::DNA, 'C') => Some(("C9H13N3O4", "C4H5N3O")),
+            (Self::DNA, 'G') => Some(("C10H13N5O4", "C5H5N5O")),
+            (Self::DNA, 'T') => Some(("C10H14N2O5", "C5H6N2O2")),
+            (Self::RNA, 'A') => Some(("C10H13N5O4", "C5H5N5")),
+            (Self::RNA, 'C') => Some(("C9H13N3O5", "C4H5N3O")),
+            (Self::RNA, 'G') => Some(("C10H13N5O5", "C5H5N5O")),
+            (Self::RNA, 'U') => Some(("C9H12N2O6", "C4H4N2O2")),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// The group terminating the 5' or 3' end of the chain
+pub enum Terminus {
+    #[default]
+    Hydroxyl,
+    Phosphate,
+    Triphosphate,
+}
+
+impl Terminus {
+    fn phosphates(&self) -> i32 {
+        match self {
+            Self::Hydroxyl => 0,
+            Self::Phosphate => 1,
+            Self::Triphosphate => 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// Common modifications of a nucleotide's sugar or base
+pub enum NucleotideModification {
+    /// A 2'-O-methyl ribose
+    TwoPrimeOMethyl,
+    /// A 2'-deoxy-2'-fluoro ribose, replacing the hydroxyl of a ribonucleotide
+    TwoPrimeFluoro,
+    /// A 2'-O-(2-methoxyethyl) ribose
+    TwoPrimeMethoxyethyl,
+    /// A locked ribose bridged by a 2'-O,4'-C methylene
+    Locked,
+    /// A methylated base, like 5-methylcytosine. Add it with
+    /// [`NucleicAcid::with_base_modification`] so that `a-B` ions lose it with the base.
+    Methyl,
+}
+
+impl NucleotideModification {
+    /// The composition added to the nucleotide
+    pub fn composition(&self) -> ChemicalComposition<'static> {
+        let mut composition = ChemicalComposition::new();
+        let delta: &[(&str, i32)] = match self {
+            Self::TwoPrimeOMethyl | Self::Methyl => &[("C", 1), ("H", 2)],
+            Self::TwoPrimeFluoro => &[("F", 1), ("H", -1), ("O", -1)],
+            Self::TwoPrimeMethoxyethyl => &[("C", 3), ("H", 6), ("O", 1)],
+            Self::Locked => &[("C", 1)],
+        };
+        for (symbol, count) in delta {
+            composition.inc_str(symbol, *count);
+        }
+        composition
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// The ways a nucleic acid sequence can fail to describe a composition
+pub enum NucleicAcidErrorKind {
+    /// The character at this position is not a nucleotide of the sequence's kind
+    UnknownNucleotide { position: usize, nucleotide: char },
+    /// A modification, linkage or fragment was placed beyond the end of the sequence
+    InvalidPosition(usize),
+}
+
+impl Display for NucleicAcidErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownNucleotide { .. } => f.write_str("unknown nucleotide"),
+            Self::InvalidPosition(position) => {
+                write!(f, "position {position} is outside the sequence")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/**
+An error computing the composition of a [`NucleicAcid`], locating the problem in its sequence.
+Its [`Display`] implementation renders the sequence with a caret under the offending nucleotide.
+*/
+pub struct NucleicAcidError {
+    pub kind: NucleicAcidErrorKind,
+    /// The sequence of the nucleic acid
+    pub sequence: String,
+}
+
+impl NucleicAcidError {
+    pub fn new(kind: NucleicAcidErrorKind, sequence: &str) -> Self {
+        Self {
+            kind,
+            sequence: sequence.to_string(),
+        }
+    }
+
+    /// The byte range of the offending nucleotide in `sequence`, unless the error is about a
+    /// position outside it
+    pub fn span(&self) -> Option<Range<usize>> {
+        match self.kind {
+            NucleicAcidErrorKind::UnknownNucleotide { position, .. } => self
+                .sequence
+                .char_indices()
+                .nth(position)
+                .map(|(offset, _)| char_span(&self.sequence, offset)),
+            NucleicAcidErrorKind::InvalidPosition(_) => None,
+        }
+    }
+}
+
+impl Display for NucleicAcidError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.span() {
+            Some(span) => write_diagnostic(f, &self.kind, &self.sequence, &span, &[]),
+            None => write!(f, "{}\n{}", self.kind, self.sequence),
+        }
+    }
+}
+
+impl std::error::Error for NucleicAcidError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/**
+The backbone fragment ion series of McLuckey's nomenclature. The 5' fragments `a`, `b`,
+`c` and `d` are cleaved at successive bonds of the 3' linkage, and are complemented by the
+3' fragments `w`, `x`, `y` and `z` respectively.
+*/
+pub enum IonSeries {
+    /// The `a` fragment having also lost the nucleobase of its 3' nucleotide
+    AMinusBase,
+    A,
+    B,
+    C,
+    D,
+    W,
+    X,
+    Y,
+    Z,
+}
+
+impl IonSeries {
+    /// Whether the fragment retains the 5' end of the chain
+    pub fn is_five_prime(&self) -> bool {
+        matches!(
+            self,
+            Self::AMinusBase | Self::A | Self::B | Self::C | Self::D
+        )
+    }
+}
+
+impl Display for IonSeries {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            Self::AMinusBase => "a-B",
+            Self::A => "a",
+            Self::B => "b",
+            Self::C => "c",
+            Self::D => "d",
+            Self::W => "w",
+            Self::X => "x",
+            Self::Y => "y",
+            Self::Z => "z",
+        };
+        f.write_str(label)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A fragment ion of an oligonucleotide, retaining `ordinal` nucleotides from its end.
+/// Displayed in the usual annotation style, like `w3 2-`.
+pub struct Fragment<'lifespan> {
+    pub series: IonSeries,
+    pub ordinal: usize,
+    pub charge: i32,
+    pub composition: ChemicalComposition<'lifespan>,
+}
+
+impl Fragment<'_> {
+    /// The neutral mass of the fragment
+    pub fn mass(&self) -> f64 {
+        self.composition.mass()
+    }
+
+    /// The m/z of the fragment ion, protonated or deprotonated to its charge
+    pub fn mz(&self) -> f64 {
+        mass_charge_ratio(self.mass(), self.charge, PROTON)
+    }
+}
+
+impl Display for Fragment<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.series, self.ordinal)?;
+        // A neutral fragment carries no charge annotation
+        if self.charge == 0 {
+            return Ok(());
+        }
+        write!(
+            f,
+            " {}{}",
+            self.charge.abs(),
+            if self.charge > 0 { "+" } else { "-" }
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/**
+A DNA or RNA oligonucleotide written 5' to 3' in one letter codes, with its termini,
+phosphorothioate linkages and modifications.
+
+Both termini default to [`Terminus::Hydroxyl`]. Linkage `i` joins nucleotide `i` to
+nucleotide `i + 1`, and a phosphorothioate linkage exchanges one of its non-bridging
+oxygens for a sulfur.
+*/
+pub struct NucleicAcid<'lifespan> {
+    pub kind: NucleicAcidKind,
+    pub sequence: String,
+    pub five_prime: Terminus,
+    pub three_prime: Terminus,
+    pub phosphorothioates: Vec<usize>,
+    pub modifications: Vec<(usize, ChemicalComposition<'lifespan>)>,
+    /// Modifications of the nucleobase, which are lost with it
+    pub base_modifications: Vec<(usize, ChemicalComposition<'lifespan>)>,
+}
+
+struct Nucleotide<'lifespan> {
+    nucleoside: ChemicalComposition<'lifespan>,
+    base: ChemicalComposition<'lifespan>,
+}
+
+impl<'lifespan> NucleicAcid<'lifespan> {
+    pub fn new(kind: NucleicAcidKind, sequence: &str) -> Self {
+        Self {
+            kind,
+            sequence: sequence.to_string(),
+            five_prime: Terminus::default(),
+            three_prime: Terminus::default(),
+            phosphorothioates: Vec::new(),
+            modifications: Vec::new(),
+            base_modifications: Vec::new(),
+        }
+    }
+
+    pub fn dna(sequence: &str) -> Self {
+        Self::new(NucleicAcidKind::DNA, sequence)
+    }
+
+    pub fn rna(sequence: &str) -> Self {
+        Self::new(NucleicAcidKind::RNA, sequence)
+    }
+
+    pub fn with_five_prime(mut self, terminus: Terminus) -> Self {
+        self.five_prime = terminus;
+        self
+    }
+
+    pub fn with_three_prime(mut self, terminus: Terminus) -> Self {
+        self.three_prime = terminus;
+        self
+    }
+
+    /// Make the linkage following the nucleotide at `linkage` a phosphorothioate
+    pub fn with_phosphorothioate(mut self, linkage: usize) -> Self {
+        self.phosphorothioates.push(linkage);
+        self
+    }
+
+    /// Make every linkage a phosphorothioate
+    pub fn with_phosphorothioate_backbone(mut self) -> Self {
+        self.phosphorothioates = (0..self.len().saturating_sub(1)).collect();
+        self
+    }
+
+    /// Add `composition` to the nucleotide at the zero-based `position`
+    pub fn with_modification(
+        mut self,
+        position: usize,
+        composition: ChemicalComposition<'lifespan>,
+    ) -> Self {
+        self.modifications.push((position, composition));
+        self
+    }
+
+    /// Add `composition` to the nucleobase of the nucleotide at the zero-based `position`,
+    /// so that it is lost along with the base by `a-B` fragments
+    pub fn with_base_modification(
+        mut self,
+        position: usize,
+        composition: ChemicalComposition<'lifespan>,
+    ) -> Self {
+        self.base_modifications.push((position, composition));
+        self
+    }
+
+    /// The number of nucleotides in the sequence
+    pub fn len(&self) -> usize {
+        self.sequence.chars().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sequence.is_empty()
+    }
+
+    fn error(&self, kind: NucleicAcidErrorKind) -> NucleicAcidError {
+        NucleicAcidError::new(kind, &self.sequence)
+    }
+
+    fn nucleotides(
+        &self,
+        periodic_table: &'lifespan PeriodicTable,
+    ) -> Result<Vec<Nucleotide<'lifespan>>, NucleicAcidError> {
+        let mut nucleotides = Vec::with_capacity(self.sequence.len());
+        for (position, nucleotide) in self.sequence.chars().enumerate() {
+            let (nucleoside, base) = self.kind.formulas(nucleotide).ok_or_else(|| {
+                self.error(NucleicAcidErrorKind::UnknownNucleotide {
+                    position,
+                    nucleotide,
+                })
+            })?;
+            nucleotides.push(Nucleotide {
+                nucleoside: ChemicalComposition::parse_with(nucleoside, periodic_table).unwrap(),
+                base: ChemicalComposition::parse_with(base, periodic_table).unwrap(),
+            });
+        }
+        for (position, modification) in self.modifications.iter() {
+            let nucleotide = nucleotides
+                .get_mut(*position)
+                .ok_or_else(|| self.error(NucleicAcidErrorKind::InvalidPosition(*position)))?;
+            nucleotide.nucleoside += modification;
+        }
+        for (position, modification) in self.base_modifications.iter() {
+            let nucleotide = nucleotides
+                .get_mut(*position)
+                .ok_or_else(|| self.error(NucleicAcidErrorKind::InvalidPosition(*position)))?;
+            nucleotide.nucleoside += modification;
+            nucleotide.base += modification;
+        }
+        if let Some(linkage) = self
+            .phosphorothioates
+            .iter()
+            .find(|linkage| **linkage + 1 >= nucleotides.len())
+        {
+            return Err(self.error(NucleicAcidErrorKind::InvalidPosition(*linkage)));
+        }
+        Ok(nucleotides)
+    }
+
+    /// The composition of the phosphate of the linkage `linkage`, as added when forming
+    /// the phosphodiester or phosphomonoester
+    fn phosphate(
+        &self,
+        linkage: Option<usize>,
+        periodic_table: &'lifespan PeriodicTable,
+    ) -> ChemicalComposition<'lifespan> {
+        let thio = linkage.is_some_and(|linkage| self.phosphorothioates.contains(&linkage));
+        ChemicalComposition::parse_with(if thio { "HPO2S" } else { "HPO3" }, periodic_table)
+            .unwrap()
+    }
+
+    /// The composition of the chain of `nucleotides[range]` with their internal linkages
+    /// and free hydroxyls at both ends
+    fn chain(
+        &self,
+        nucleotides: &[Nucleotide<'lifespan>],
+        range: Range<usize>,
+        periodic_table: &'lifespan PeriodicTable,
+    ) -> ChemicalComposition<'lifespan> {
+        let water = ChemicalComposition::parse_with("H2O", periodic_table).unwrap();
+        let mut composition = ChemicalComposition::new();
+        for (i, nucleotide) in nucleotides[range.clone()].iter().enumerate() {
+            composition += &nucleotide.nucleoside;
+            if i > 0 {
+                let linkage = range.start + i - 1;
+                composition += &self.phosphate(Some(linkage), periodic_table);
+                composition -= &water;
+            }
+        }
+        composition
+    }
+
+    fn terminus(
+        &self,
+        terminus: Terminus,
+        periodic_table: &'lifespan PeriodicTable,
+    ) -> ChemicalComposition<'lifespan> {
+        &self.phosphate(None, periodic_table) * terminus.phosphates()
+    }
+
+    /// The composition of the whole molecule using the global [`PeriodicTable`]
+    pub fn composition(&self) -> Result<ChemicalComposition<'lifespan>, NucleicAcidError> {
+        self.composition_with(&PERIODIC_TABLE)
+    }
+
+    /// The composition of the whole molecule, including its termini, using the specified
+    /// [`PeriodicTable`]
+    pub fn composition_with(
+        &self,
+        periodic_table: &'lifespan PeriodicTable,
+    ) -> Result<ChemicalComposition<'lifespan>, NucleicAcidError> {
+        let nucleotides = self.nucleotides(periodic_table)?;
+        let composition = self.chain(&nucleotides, 0..nucleotides.len(), periodic_table)
+            + &self.terminus(self.five_prime, periodic_table)
+            + &self.terminus(self.three_prime, periodic_table);
+        Ok(composition)
+    }
+
+    /// The neutral composition of the fragment of `series` retaining `ordinal` nucleotides,
+    /// using the global [`PeriodicTable`]
+    pub fn fragment_composition(
+        &self,
+        series: IonSeries,
+        ordinal: usize,
+    ) -> Result<ChemicalComposition<'lifespan>, NucleicAcidError> {
+        let nucleotides = self.nucleotides(&PERIODIC_TABLE)?;
+        self.fragment_composition_of(&nucleotides, series, ordinal, &PERIODIC_TABLE)
+    }
+
+    fn fragment_composition_of(
+        &self,
+        nucleotides: &[Nucleotide<'lifespan>],
+        series: IonSeries,
+        ordinal: usize,
+        periodic_table: &'lifespan PeriodicTable,
+    ) -> Result<ChemicalComposition<'lifespan>, NucleicAcidError> {
+        let n = nucleotides.len();
+        if ordinal == 0 || ordinal >= n {
+            return Err(self.error(NucleicAcidErrorKind::InvalidPosition(ordinal)));
+        }
+        let water = ChemicalComposition::parse_with("H2O", periodic_table).unwrap();
+        let composition = if series.is_five_prime() {
+            // Cleaved within the linkage following the last retained nucleotide
+            let linkage = ordinal - 1;
+            let b = self.chain(nucleotides, 0..ordinal, periodic_table)
+                + &self.terminus(self.five_prime, periodic_table);
+            let d = &b + &self.phosphate(Some(linkage), periodic_table);
+            match series {
+                IonSeries::AMinusBase => b - &water - &nucleotides[linkage].base,
+                IonSeries::A => b - &water,
+                IonSeries::B => b,
+                IonSeries::C => d - &water,
+                _ => d,
+            }
+        } else {
+            // Cleaved within the linkage preceding the first retained nucleotide
+            let linkage = n - ordinal - 1;
+            let y = self.chain(nucleotides, n - ordinal..n, periodic_table)
+                + &self.terminus(self.three_prime, periodic_table);
+            let w = &y + &self.phosphate(Some(linkage), periodic_table);
+            match series {
+                IonSeries::W => w,
+                IonSeries::X => w - &water,
+                IonSeries::Y => y,
+                _ => y - &water,
+            }
+        };
+        Ok(composition)
+    }
+
+    /// Build the fragment ladders of each of `series`, from one nucleotide up to all but
+    /// one, at each of `charges`, using the global [`PeriodicTable`]
+    pub fn fragments(
+        &self,
+        series: &[IonSeries],
+        charges: RangeInclusive<i32>,
+    ) -> Result<Vec<Fragment<'lifespan>>, NucleicAcidError> {
+        self.fragments_with(series, charges, &PERIODIC_TABLE)
+    }
+
+    /// Build the fragment ladders of each of `series` using the specified [`PeriodicTable`].
+    /// See [`NucleicAcid::fragments`].
+    pub fn fragments_with(
+        &self,
+        series: &[IonSeries],
+        charges: RangeInclusive<i32>,
+        periodic_table: &'lifespan PeriodicTable,
+    ) -> Result<Vec<Fragment<'lifespan>>, NucleicAcidError> {
+        let nucleotides = self.nucleotides(periodic_table)?;
+        let mut fragments = Vec::new();
+        for series in series.iter().copied() {
+            for ordinal in 1..nucleotides.len() {
+                let composition =
+                    self.fragment_composition_of(&nucleotides, series, ordinal, periodic_table)?;
+                for charge in charges.clone().filter(|z| *z != 0) {
+                    fragments.push(Fragment {
+                        series,
+                        ordinal,
+                        charge,
+                        composition: composition.clone(),
+                    });
+                }
+            }
+        }
+        Ok(fragments)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::isotopic_pattern::isotopic_variants;
+
+    #[test]
+    fn test_composition() {
+        let parse = |s| ChemicalComposition::parse(s).unwrap();
+        assert_eq!(
+            NucleicAcid::dna("ACGT").composition().unwrap(),
+            parse("C39H50N15O22P3")
+        );
+        let mass = NucleicAcid::dna("TT").composition().unwrap().mass();
+        assert!((mass - 546.13630).abs() < 1e-4, "{mass}");
+        assert_eq!(
+            NucleicAcid::rna("GGCU")
+                .with_five_prime(Terminus::Triphosphate)
+                .composition()
+                .unwrap(),
+            parse("C38H51N15O36P6")
+        );
+
+        let modified = NucleicAcid::rna("GGCU")
+            .with_three_prime(Terminus::Phosphate)
+            .with_phosphorothioate_backbone()
+            .with_modification(1, NucleotideModification::TwoPrimeFluoro.composition());
+        let expected =
+            NucleicAcid::rna("GGCU").composition().unwrap() + &parse("HPO3S3F") - &parse("O4H");
+        assert_eq!(modified.composition().unwrap(), expected);
+
+        let err = NucleicAcid::dna("ACGU").composition().unwrap_err();
+        assert_eq!(
+            err.kind,
+            NucleicAcidErrorKind::UnknownNucleotide {
+                position: 3,
+                nucleotide: 'U'
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "unknown nucleotide `U` at byte 3\nACGU\n   ^"
+        );
+        let err = NucleicAcid::dna("AC")
+            .with_phosphorothioate(1)
+            .composition()
+            .unwrap_err();
+        assert_eq!(err.kind, NucleicAcidErrorKind::InvalidPosition(1));
+        assert_eq!(err.to_string(), "position 1 is outside the sequence\nAC");
+    }
+
+    #[test]
+    fn test_fragments() {
+        let oligo = NucleicAcid::dna("GATTACA")
+            .with_five_prime(Terminus::Phosphate)
+            .with_phosphorothioate(2);
+        let precursor = oligo.composition().unwrap();
+        for ordinal in 1..7 {
+            let fragment = |series, ordinal| oligo.fragment_composition(series, ordinal).unwrap();
+            assert_eq!(
+                fragment(IonSeries::A, ordinal) + &fragment(IonSeries::W, 7 - ordinal),
+                precursor
+            );
+            assert_eq!(
+                fragment(IonSeries::C, ordinal) + &fragment(IonSeries::Y, 7 - ordinal),
+                precursor
+            );
+        }
+
+        let oligo = NucleicAcid::dna("TTT");
+        let w1 = oligo.fragment_composition(IonSeries::W, 1).unwrap();
+        assert_eq!(w1, ChemicalComposition::parse("C10H15N2O8P").unwrap());
+        let a2_b = oligo
+            .fragment_composition(IonSeries::AMinusBase, 2)
+            .unwrap();
+        assert!((a2_b.mass() - 402.0828).abs() < 1e-3, "{}", a2_b.mass());
+
+        let fragments = oligo.fragments(&[IonSeries::W], -2..=-1).unwrap();
+        assert_eq!(fragments.len(), 4);
+        assert_eq!(fragments[0].to_string(), "w1 2-");
+        let neutral = Fragment {
+            charge: 0,
+            ..fragments[0].clone()
+        };
+        assert_eq!(neutral.to_string(), "w1");
+        assert!((fragments[1].mz() - (w1.mass() - PROTON)).abs() < 1e-9);
+
+        // The a-B ion of a 5-methylcytosine loses the methyl with the base
+        let oligo = NucleicAcid::dna("TCG");
+        let methylated = oligo
+            .clone()
+            .with_base_modification(1, NucleotideModification::Methyl.composition());
+        let methyl = NucleotideModification::Methyl.composition();
+        let fragment = |oligo: &NucleicAcid<'static>, series| {
+            oligo.fragment_composition(series, 2).unwrap()
+        };
+        assert_eq!(
+            fragment(&methylated, IonSeries::A),
+            fragment(&oligo, IonSeries::A) + &methyl
+        );
+        assert_eq!(
+            fragment(&methylated, IonSeries::AMinusBase),
+            fragment(&oligo, IonSeries::AMinusBase)
+        );
+        assert_eq!(
+            methylated.composition().unwrap(),
+            oligo.composition().unwrap() + &methyl
+        );
+    }
+
+    #[test]
+    fn test_large_oligo_envelope() {
+        let oligo = NucleicAcid::dna(&"ACGT".repeat(10)).with_phosphorothioate_backbone();
+        let composition = oligo.composition().unwrap();
+        assert!(composition.mass() > 12000.0);
+        let peaks = isotopic_variants(composition.clone(), 0, -5, PROTON);
+        assert!(peaks.len() > 10);
+        let apex = peaks
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.intensity.total_cmp(&b.1.intensity))
+            .unwrap()
+            .0;
+        assert!(apex > 3, "{apex}");
+        assert!((peaks[0].mz - mass_charge_ratio(composition.mass(), -5, PROTON)).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,442 @@
+//! Compute the elemental composition of peptides and proteins from their amino acid
+//! sequences, with terminal groups and modifications.
+//!
+//! ```rust
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use chemical_elements::ChemicalComposition;
+//! use chemical_elements::peptide::{sequence_composition, Peptide};
+//!
+//! let composition = sequence_composition("PEPTIDE")?;
+//! assert_eq!(composition, ChemicalComposition::parse("C34H53N7O15")?);
+//!
+//! let carbamidomethyl = ChemicalComposition::parse("C2H3NO")?;
+//! let oxidation = ChemicalComposition::parse("O")?;
+//! let peptide = Peptide::new("MCPEPTIDE")
+//!     .with_fixed_modification('C', carbamidomethyl)
+//!     .with_modification(0, oxidation);
+//! let composition = peptide.composition()?;
+//! assert_eq!(composition["O"], 19);
+//! # Ok(())
+//! # }
+//! ```
+use std::fmt::{self, Display};
+use std::ops::Range;
+
+use chemical_elements_grammar::write_diagnostic;
+
+use crate::formula::char_span;
+use crate::table::PERIODIC_TABLE;
+use crate::{ChemicalComposition, PeriodicTable};
+
+pub mod fragment;
+
+pub use crate::peptide::fragment::{Fragment, IonSeries, NeutralLoss};
+
+/// The one letter code, name and residue formula of each amino acid, that is, the
+/// amino acid less one water lost when forming the peptide bond
+const RESIDUES: &[(char, &str, &str)] = &[
+    ('G', "Glycine", "C2H3NO"),
+    ('A', "Alanine", "C3H5NO"),
+    ('S', "Serine", "C3H5NO2"),
+    ('P', "Proline", "C5H7NO"),
+    ('V', "Valine", "C5H9NO"),
+    ('T', "Threonine", "C4H7NO2"),
+    ('C', "Cysteine", "C3H5NOS"),
+    ('L', "Leucine", "C6H11NO"),
+    ('I', "Isoleucine", "C6H11NO"),
+    ('N', "Asparagine", "C4H6N2O2"),
+    ('D', "Aspartic acid", "C4H5NO3"),
+    ('Q', "Glutamine", "C5H8N2O2"),
+    ('K', "Lysine", "C6H12N2O"),
+    ('E', "Glutamic acid", "C5H7NO3"),
+    ('M', "Methionine", "C5H9NOS"),
+    ('H', "Histidine", "C6H7N3O"),
+    ('F', "Phenylalanine", "C9H9NO"),
+    ('R', "Arginine", "C6H12N4O"),
+    ('Y', "Tyrosine", "C9H9NO2"),
+    ('W', "Tryptophan", "C11H10N2O"),
+    ('U', "Selenocysteine", "C3H5NOSe"),
+    ('O', "Pyrrolysine", "C12H19N3O2"),
+    // Leucine and isoleucine are isomers, so the ambiguity does not affect the composition
+    ('J', "Leucine or isoleucine", "C6H11NO"),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+/// The ways a peptide sequence can fail to describe a composition
+pub enum PeptideErrorKind {
+    /// The character at this residue position is not an amino acid code
+    UnknownResidue { position: usize, residue: char },
+    /// The residue at this position is ambiguous and [`Ambiguity::Reject`] was in effect
+    AmbiguousResidue { position: usize, residue: char },
+    /// A modification or fragment was placed beyond the end of the sequence
+    InvalidPosition(usize),
+}
+
+impl Display for PeptideErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownResidue { .. } => f.write_str("unknown residue"),
+            Self::AmbiguousResidue { .. } => f.write_str("ambiguous residue"),
+            Self::InvalidPosition(position) => {
+                write!(f, "position {position} is outside the sequence")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/**
+An error computing the composition of a [`Peptide`], locating the problem in its sequence.
+Its [`Display`] implementation renders the sequence with a caret under the offending residue.
+*/
+pub struct PeptideError {
+    pub kind: PeptideErrorKind,
+    /// The sequence of the peptide
+    pub sequence: String,
+}
+
+impl PeptideError {
+    pub fn new(kind: PeptideErrorKind, sequence: &str) -> Self {
+        Self {
+            kind,
+            sequence: sequence.to_string(),
+        }
+    }
+
+    /// The byte range of the offending residue in `sequence`, unless the error is about a
+    /// position outside it
+    pub fn span(&self) -> Option<Range<usize>> {
+        match self.kind {
+            PeptideErrorKind::UnknownResidue { position, .. }
+            | PeptideErrorKind::AmbiguousResidue { position, .. } => self
+                .sequence
+                .char_indices()
+                .nth(position)
+                .map(|(offset, _)| char_span(&self.sequence, offset)),
+            PeptideErrorKind::InvalidPosition(_) => None,
+        }
+    }
+}
+
+impl Display for PeptideError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.span() {
+            Some(span) => write_diagnostic(f, &self.kind, &self.sequence, &span, &[]),
+            None => write!(f, "{}\n{}", self.kind, self.sequence),
+        }
+    }
+}
+
+impl std::error::Error for PeptideError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/**
+How to treat the ambiguous residue codes `B` (aspartic acid or asparagine), `Z` (glutamic
+acid or glutamine) and `X` (any amino acid).
+
+When resolved, `X` contributes an empty residue so that its composition can be supplied by
+a modification at its position.
+*/
+pub enum Ambiguity {
+    /// Fail with [`PeptideErrorKind::AmbiguousResidue`]
+    #[default]
+    Reject,
+    /// Read `B` and `Z` as their acids, `D` and `E`
+    Acid,
+    /// Read `B` and `Z` as their amides, `N` and `Q`
+    Amide,
+}
+
+/// The name of the amino acid with the one letter `code`, if it is known
+pub fn residue_name(code: char) -> Option<&'static str> {
+    RESIDUES
+        .iter()
+        .find(|(c, _, _)| *c == code)
+        .map(|(_, name, _)| *name)
+}
+
+/// The composition of the residue with the one letter `code` using the global [`PeriodicTable`]
+pub fn residue_composition(code: char) -> Option<ChemicalComposition<'static>> {
+    residue_composition_with(code, &PERIODIC_TABLE)
+}
+
+/// The composition of the residue with the one letter `code` using the specified [`PeriodicTable`]
+pub fn residue_composition_with(
+    code: char,
+    periodic_table: &PeriodicTable,
+) -> Option<ChemicalComposition<'_>> {
+    RESIDUES
+        .iter()
+        .find(|(c, _, _)| *c == code)
+        .map(|(_, _, formula)| ChemicalComposition::parse_with(formula, periodic_table).unwrap())
+}
+
+/// The composition of the unmodified peptide or protein `sequence` with free termini, using
+/// the global [`PeriodicTable`]
+pub fn sequence_composition(sequence: &str) -> Result<ChemicalComposition<'static>, PeptideError> {
+    Peptide::new(sequence).composition()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/**
+A peptide or protein sequence in one letter codes, with its terminal groups and
+modifications.
+
+The N-terminal group defaults to `H` and the C-terminal group to `OH`, which complete the
+free amine and acid. Fixed modifications apply to every residue with a given code while
+modifications placed at a position apply to that residue only, and both may occur on the
+same residue.
+*/
+pub struct Peptide<'lifespan> {
+    pub sequence: String,
+    pub n_terminal: ChemicalComposition<'lifespan>,
+    pub c_terminal: ChemicalComposition<'lifespan>,
+    pub fixed_modifications: Vec<(char, ChemicalComposition<'lifespan>)>,
+    pub modifications: Vec<(usize, ChemicalComposition<'lifespan>)>,
+    pub ambiguity: Ambiguity,
+}
+
+impl<'lifespan> Peptide<'lifespan> {
+    pub fn new(sequence: &str) -> Self {
+        let mut n_terminal = ChemicalComposition::new();
+        n_terminal.inc_str("H", 1);
+        let mut c_terminal = ChemicalComposition::new();
+        c_terminal.inc_str("O", 1);
+        c_terminal.inc_str("H", 1);
+        Self {
+            sequence: sequence.to_string(),
+            n_terminal,
+            c_terminal,
+            fixed_modifications: Vec::new(),
+            modifications: Vec::new(),
+            ambiguity: Ambiguity::default(),
+        }
+    }
+
+    pub fn with_n_terminal(mut self, composition: ChemicalComposition<'lifespan>) -> Self {
+        self.n_terminal = composition;
+        self
+    }
+
+    pub fn with_c_terminal(mut self, composition: ChemicalComposition<'lifespan>) -> Self {
+        self.c_terminal = composition;
+        self
+    }
+
+    /// Add `composition` to every residue with the one letter `code`, including the ambiguous
+    /// residues the [`Ambiguity`] resolves to it
+    pub fn with_fixed_modification(
+        mut self,
+        code: char,
+        composition: ChemicalComposition<'lifespan>,
+    ) -> Self {
+        self.fixed_modifications.push((code, composition));
+        self
+    }
+
+    /// Add `composition` to the residue at the zero-based `position`
+    pub fn with_modification(
+        mut self,
+        position: usize,
+        composition: ChemicalComposition<'lifespan>,
+    ) -> Self {
+        self.modifications.push((position, composition));
+        self
+    }
+
+    pub fn with_ambiguity(mut self, ambiguity: Ambiguity) -> Self {
+        self.ambiguity = ambiguity;
+        self
+    }
+
+    pub(crate) fn error(&self, kind: PeptideErrorKind) -> PeptideError {
+        PeptideError::new(kind, &self.sequence)
+    }
+
+    /// The number of residues in the sequence
+    pub fn len(&self) -> usize {
+        self.sequence.chars().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sequence.is_empty()
+    }
+
+    /// The composition of each residue with its modifications, using the global [`PeriodicTable`]
+    pub fn residue_compositions(
+        &self,
+    ) -> Result<Vec<ChemicalComposition<'lifespan>>, PeptideError> {
+        self.residue_compositions_with(&PERIODIC_TABLE)
+    }
+
+    /**
+    The composition of each residue with its modifications, without the terminal groups,
+    using the specified [`PeriodicTable`].
+
+    # Errors
+    If a residue is not a known amino acid code, is ambiguous under [`Ambiguity::Reject`], or
+    a modification is placed beyond the end of the sequence.
+    */
+    pub fn residue_compositions_with(
+        &self,
+        periodic_table: &'lifespan PeriodicTable,
+    ) -> Result<Vec<ChemicalComposition<'lifespan>>, PeptideError> {
+        // Parse each distinct residue's formula once, as proteins repeat them many times
+        let mut cache: Vec<(char, ChemicalComposition<'lifespan>)> = Vec::new();
+        let mut residues = Vec::with_capacity(self.sequence.len());
+        for (position, residue) in self.sequence.chars().enumerate() {
+            let code = match (residue, self.ambiguity) {
+                ('B' | 'Z' | 'X', Ambiguity::Reject) => {
+                    return Err(self.error(PeptideErrorKind::AmbiguousResidue { position, residue }))
+                }
+                ('B', Ambiguity::Acid) => 'D',
+                ('B', Ambiguity::Amide) => 'N',
+                ('Z', Ambiguity::Acid) => 'E',
+                ('Z', Ambiguity::Amide) => 'Q',
+                (code, _) => code,
+            };
+            let mut composition = if code == 'X' {
+                ChemicalComposition::new()
+            } else if let Some((_, composition)) = cache.iter().find(|(c, _)| *c == code) {
+                composition.clone()
+            } else {
+                let composition =
+                    residue_composition_with(code, periodic_table).ok_or_else(|| {
+                        self.error(PeptideErrorKind::UnknownResidue { position, residue })
+                    })?;
+                cache.push((code, composition.clone()));
+                composition
+            };
+            for (_, modification) in self
+                .fixed_modifications
+                .iter()
+                .filter(|(fixed, _)| *fixed == code)
+            {
+                composition += modification;
+            }
+            residues.push(composition);
+        }
+        for (position, modification) in self.modifications.iter() {
+            let residue = residues
+                .get_mut(*position)
+                .ok_or_else(|| self.error(PeptideErrorKind::InvalidPosition(*position)))?;
+            *residue += modification;
+        }
+        Ok(residues)
+    }
+
+    /// The composition of the whole molecule using the global [`PeriodicTable`]
+    pub fn composition(&self) -> Result<ChemicalComposition<'lifespan>, PeptideError> {
+        self.composition_with(&PERIODIC_TABLE)
+    }
+
+    /// The composition of the whole molecule, including the terminal groups, using the
+    /// specified [`PeriodicTable`]
+    pub fn composition_with(
+        &self,
+        periodic_table: &'lifespan PeriodicTable,
+    ) -> Result<ChemicalComposition<'lifespan>, PeptideError> {
+        let mut composition = &self.n_terminal + &self.c_terminal;
+        for residue in self.residue_compositions_with(periodic_table)? {
+            composition += &residue;
+        }
+        Ok(composition)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sequence_composition() {
+        for (sequence, formula) in [
+            ("G", "C2H5NO2"),
+            ("PEPTIDE", "C34H53N7O15"),
+            ("ACDEFGHIKLMNPQRSTVWY", "C107H159N29O30S2"),
+            ("U", "C3H7NO2Se"),
+            ("O", "C12H21N3O3"),
+        ] {
+            assert_eq!(
+                sequence_composition(sequence).unwrap(),
+                ChemicalComposition::parse(formula).unwrap(),
+                "{sequence}"
+            );
+        }
+        let mass = sequence_composition("SAMPLER").unwrap().mass();
+        assert!((mass - 802.40072).abs() < 1e-4, "{mass}");
+    }
+
+    #[test]
+    fn test_modifications() {
+        let peptide = Peptide::new("PEPCTIDEC")
+            .with_fixed_modification('C', ChemicalComposition::parse("C2H3NO").unwrap())
+            .with_modification(1, ChemicalComposition::parse("O").unwrap())
+            .with_n_terminal(ChemicalComposition::parse("C2H3O").unwrap());
+        let residues = peptide.residue_compositions().unwrap();
+        assert_eq!(
+            residues[3],
+            ChemicalComposition::parse("C5H8N2O2S").unwrap()
+        );
+        assert_eq!(residues[1], ChemicalComposition::parse("C5H7NO4").unwrap());
+        let expected = sequence_composition("PEPCTIDEC").unwrap()
+            + &ChemicalComposition::parse("C6H8N2O4").unwrap();
+        assert_eq!(peptide.composition().unwrap(), expected);
+
+        let err = Peptide::new("PEP")
+            .with_modification(3, ChemicalComposition::parse("O").unwrap())
+            .composition();
+        let err = err.unwrap_err();
+        assert_eq!(err.kind, PeptideErrorKind::InvalidPosition(3));
+        assert_eq!(err.to_string(), "position 3 is outside the sequence\nPEP");
+    }
+
+    #[test]
+    fn test_ambiguity() {
+        assert_eq!(
+            sequence_composition("PEBTIDE").unwrap_err().kind,
+            PeptideErrorKind::AmbiguousResidue {
+                position: 2,
+                residue: 'B'
+            }
+        );
+        let err = sequence_composition("PEP1").unwrap_err();
+        assert_eq!(
+            err.kind,
+            PeptideErrorKind::UnknownResidue {
+                position: 3,
+                residue: '1'
+            }
+        );
+        assert_eq!(err.to_string(), "unknown residue `1` at byte 3\nPEP1\n   ^");
+        let acid = Peptide::new("BZ").with_ambiguity(Ambiguity::Acid);
+        assert_eq!(
+            acid.composition().unwrap(),
+            sequence_composition("DE").unwrap()
+        );
+        let water = ChemicalComposition::parse("H2O").unwrap();
+        let modified = Peptide::new("BZ")
+            .with_ambiguity(Ambiguity::Acid)
+            .with_fixed_modification('D', water.clone());
+        assert_eq!(
+            modified.composition().unwrap(),
+            sequence_composition("DE").unwrap() + &water
+        );
+        let amide = Peptide::new("BZ").with_ambiguity(Ambiguity::Amide);
+        assert_eq!(
+            amide.composition().unwrap(),
+            sequence_composition("NQ").unwrap()
+        );
+        let unknown = Peptide::new("GXG")
+            .with_ambiguity(Ambiguity::Acid)
+            .with_modification(1, residue_composition('A').unwrap());
+        assert_eq!(
+            unknown.composition().unwrap(),
+            sequence_composition("GAG").unwrap()
+        );
+        assert_eq!(
+            sequence_composition("LJ").unwrap(),
+            sequence_composition("LI").unwrap()
+        );
+    }
+}
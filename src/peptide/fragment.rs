@@ -0,0 +1,345 @@
+//! Compute the compositions of the backbone fragment ions of a [`Peptide`], with neutral
+//! losses and charge states.
+//!
+//! Each fragment's composition is that of the neutral species, so the ion's m/z adds
+//! protons with [`mass_charge_ratio`], as for precursors.
+//!
+//! ```rust
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use chemical_elements::isotopic_pattern::BafflingRecursiveIsotopicPatternGenerator;
+//! use chemical_elements::peptide::fragment::{IonSeries, NeutralLoss};
+//! use chemical_elements::peptide::Peptide;
+//!
+//! let peptide = Peptide::new("PEPTIDE");
+//! let fragments = peptide.fragments(&[IonSeries::B, IonSeries::Y], 1..=2, &[NeutralLoss::Water])?;
+//! let y2 = fragments.iter().find(|f| f.to_string() == "y2+").unwrap();
+//! assert!((y2.mz() - 263.08737).abs() < 1e-4);
+//!
+//! let mut generator = BafflingRecursiveIsotopicPatternGenerator::new();
+//! for fragment in fragments.iter() {
+//!     let envelope = fragment.isotopic_variants(&mut generator, 0);
+//!     assert!((envelope[0].mz - fragment.mz()).abs() < 1e-3);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+use std::fmt::{self, Display};
+use std::ops::RangeInclusive;
+
+use crate::isotopic_pattern::{BafflingRecursiveIsotopicPatternGenerator, PeakList};
+use crate::mz::{mass_charge_ratio, PROTON};
+use crate::table::PERIODIC_TABLE;
+use crate::{ChemicalComposition, ElementSpecification, PeriodicTable};
+
+use super::{Peptide, PeptideError, PeptideErrorKind};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// The backbone fragment ion series, named by the Roepstorff-Fohlman-Biemann nomenclature
+pub enum IonSeries {
+    A,
+    B,
+    C,
+    /// The `c` ion less a hydrogen, `c-1`
+    CMinusOne,
+    X,
+    Y,
+    Z,
+    /// The `z` ion with an additional hydrogen, `z+1` or `z•`, formed by electron-based
+    /// dissociation
+    ZPlusOne,
+}
+
+impl IonSeries {
+    /// Whether the fragment retains the N-terminus of the peptide
+    pub fn is_n_terminal(&self) -> bool {
+        matches!(self, Self::A | Self::B | Self::C | Self::CMinusOne)
+    }
+
+    /// The elements added to the sum of the residues and the retained terminal group
+    fn delta(&self) -> &'static [(&'static str, i32)] {
+        match self {
+            Self::A => &[("C", -1), ("H", -1), ("O", -1)],
+            Self::B => &[("H", -1)],
+            Self::C => &[("H", 2), ("N", 1)],
+            Self::CMinusOne => &[("H", 1), ("N", 1)],
+            Self::X => &[("C", 1), ("H", -1), ("O", 1)],
+            Self::Y => &[("H", 1)],
+            Self::Z => &[("H", -2), ("N", -1)],
+            Self::ZPlusOne => &[("H", -1), ("N", -1)],
+        }
+    }
+}
+
+impl Display for IonSeries {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            Self::A => "a",
+            Self::B => "b",
+            Self::C => "c",
+            Self::CMinusOne => "c-1",
+            Self::X => "x",
+            Self::Y => "y",
+            Self::Z => "z",
+            Self::ZPlusOne => "z+1",
+        };
+        f.write_str(label)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NeutralLoss {
+    Water,
+    Ammonia,
+}
+
+impl NeutralLoss {
+    fn formula(&self) -> &'static str {
+        match self {
+            Self::Water => "H2O",
+            Self::Ammonia => "NH3",
+        }
+    }
+
+    /// The composition lost from the fragment
+    pub fn composition(&self) -> ChemicalComposition<'static> {
+        ChemicalComposition::parse(self.formula()).unwrap()
+    }
+}
+
+impl Display for NeutralLoss {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "-{}", self.formula())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/**
+A fragment ion of a peptide.
+
+`ordinal` counts the residues retained from the fragment's terminus, so `b2` holds the
+first two residues and `y2` the last two. Displayed in the usual annotation style, like
+`y2-H2O2+`.
+*/
+pub struct Fragment<'lifespan> {
+    pub series: IonSeries,
+    pub ordinal: usize,
+    pub loss: Option<NeutralLoss>,
+    pub charge: i32,
+    pub composition: ChemicalComposition<'lifespan>,
+}
+
+impl<'lifespan> Fragment<'lifespan> {
+    /// The neutral mass of the fragment
+    pub fn mass(&self) -> f64 {
+        self.composition.mass()
+    }
+
+    /// The m/z of the fragment ion, protonated to its charge
+    pub fn mz(&self) -> f64 {
+        mass_charge_ratio(self.mass(), self.charge, PROTON)
+    }
+
+    /// Generate the coarse isotopic pattern of the fragment ion, reusing the isotopic
+    /// constants cached by `generator` across fragments
+    pub fn isotopic_variants(
+        &self,
+        generator: &mut BafflingRecursiveIsotopicPatternGenerator<'lifespan>,
+        npeaks: i32,
+    ) -> PeakList {
+        generator.isotopic_variants(self.composition.clone(), npeaks, self.charge, PROTON)
+    }
+}
+
+impl Display for Fragment<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.series, self.ordinal)?;
+        if let Some(loss) = self.loss {
+            write!(f, "{}", loss)?;
+        }
+        // A neutral fragment carries no charge annotation
+        match self.charge.abs() {
+            0 => Ok(()),
+            1 => write!(f, "{}", if self.charge > 0 { "+" } else { "-" }),
+            z => write!(f, "{}{}", z, if self.charge > 0 { "+" } else { "-" }),
+        }
+    }
+}
+
+impl<'lifespan> Peptide<'lifespan> {
+    /// The neutral composition of the fragment of `series` retaining `ordinal` residues, using
+    /// the global [`PeriodicTable`]
+    pub fn fragment_composition(
+        &self,
+        series: IonSeries,
+        ordinal: usize,
+    ) -> Result<ChemicalComposition<'lifespan>, PeptideError> {
+        let residues = self.residue_compositions()?;
+        fragment_composition(self, &residues, series, ordinal, &PERIODIC_TABLE)
+    }
+
+    /// Build the fragment ladders of each of `series`, from one residue up to all but one,
+    /// at each non-zero charge in `charges`, with and without each of `losses`, using the
+    /// global [`PeriodicTable`]
+    pub fn fragments(
+        &self,
+        series: &[IonSeries],
+        charges: RangeInclusive<i32>,
+        losses: &[NeutralLoss],
+    ) -> Result<Vec<Fragment<'lifespan>>, PeptideError> {
+        self.fragments_with(series, charges, losses, &PERIODIC_TABLE)
+    }
+
+    /**
+    Build the fragment ladders of each of `series` using the specified [`PeriodicTable`].
+    See [`Peptide::fragments`].
+
+    Every loss is applied to every fragment, without regard to which residues could
+    lose water or ammonia, so callers wanting residue-specific losses should filter
+    the result.
+    */
+    pub fn fragments_with(
+        &self,
+        series: &[IonSeries],
+        charges: RangeInclusive<i32>,
+        losses: &[NeutralLoss],
+        periodic_table: &'lifespan PeriodicTable,
+    ) -> Result<Vec<Fragment<'lifespan>>, PeptideError> {
+        let residues = self.residue_compositions_with(periodic_table)?;
+        let loss_compositions: Vec<_> = losses
+            .iter()
+            .map(|loss| {
+                (
+                    *loss,
+                    ChemicalComposition::parse_with(loss.formula(), periodic_table).unwrap(),
+                )
+            })
+            .collect();
+        let mut fragments = Vec::new();
+        for series in series.iter().copied() {
+            for ordinal in 1..residues.len() {
+                let composition =
+                    fragment_composition(self, &residues, series, ordinal, periodic_table)?;
+                for charge in charges.clone().filter(|z| *z != 0) {
+                    fragments.push(Fragment {
+                        series,
+                        ordinal,
+                        loss: None,
+                        charge,
+                        composition: composition.clone(),
+                    });
+                    for (loss, loss_composition) in loss_compositions.iter() {
+                        fragments.push(Fragment {
+                            series,
+                            ordinal,
+                            loss: Some(*loss),
+                            charge,
+                            composition: &composition - loss_composition,
+                        });
+                    }
+                }
+            }
+        }
+        Ok(fragments)
+    }
+}
+
+fn fragment_composition<'lifespan>(
+    peptide: &Peptide<'lifespan>,
+    residues: &[ChemicalComposition<'lifespan>],
+    series: IonSeries,
+    ordinal: usize,
+    periodic_table: &'lifespan PeriodicTable,
+) -> Result<ChemicalComposition<'lifespan>, PeptideError> {
+    if ordinal == 0 || ordinal > residues.len() {
+        return Err(peptide.error(PeptideErrorKind::InvalidPosition(ordinal)));
+    }
+    let (mut composition, retained) = if series.is_n_terminal() {
+        (peptide.n_terminal.clone(), &residues[..ordinal])
+    } else {
+        (
+            peptide.c_terminal.clone(),
+            &residues[residues.len() - ordinal..],
+        )
+    };
+    for residue in retained {
+        composition += residue;
+    }
+    for (symbol, count) in series.delta() {
+        composition.inc(
+            ElementSpecification::new(&periodic_table[*symbol], 0),
+            *count,
+        );
+    }
+    Ok(composition)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_fragment_masses() {
+        let peptide = Peptide::new("PEPTIDE");
+        for (series, ordinal, mz) in [
+            (IonSeries::B, 2, 227.10263),
+            (IonSeries::A, 2, 199.10771),
+            (IonSeries::C, 2, 244.12918),
+            (IonSeries::Y, 2, 263.08737),
+            (IonSeries::X, 2, 289.06664),
+            (IonSeries::Z, 2, 246.06082),
+            (IonSeries::ZPlusOne, 2, 247.06865),
+            (IonSeries::CMinusOne, 2, 243.12136),
+        ] {
+            let composition = peptide.fragment_composition(series, ordinal).unwrap();
+            let observed = mass_charge_ratio(composition.mass(), 1, PROTON);
+            assert!((observed - mz).abs() < 1e-4, "{series}{ordinal} {observed}");
+        }
+        assert_eq!(
+            peptide
+                .fragment_composition(IonSeries::B, 8)
+                .unwrap_err()
+                .kind,
+            PeptideErrorKind::InvalidPosition(8)
+        );
+    }
+
+    #[test]
+    fn test_fragment_ladder() {
+        let peptide = Peptide::new("PEPTIDE")
+            .with_modification(3, ChemicalComposition::parse("HPO3").unwrap());
+        let fragments = peptide
+            .fragments(
+                &[IonSeries::B, IonSeries::Y],
+                1..=2,
+                &[NeutralLoss::Water, NeutralLoss::Ammonia],
+            )
+            .unwrap();
+        assert_eq!(fragments.len(), 2 * 6 * 2 * 3);
+
+        let b4 = fragments
+            .iter()
+            .find(|f| f.to_string() == "b4-H2O2+")
+            .unwrap();
+        assert_eq!(b4.composition["P"], 1);
+        let expected = peptide.fragment_composition(IonSeries::B, 4).unwrap()
+            - &NeutralLoss::Water.composition();
+        assert_eq!(b4.composition, expected);
+        assert!((b4.mz() - (expected.mass() + 2.0 * PROTON) / 2.0).abs() < 1e-9);
+
+        let mut generator = BafflingRecursiveIsotopicPatternGenerator::new();
+        let y3 = fragments.iter().find(|f| f.to_string() == "y3+").unwrap();
+        let envelope = y3.isotopic_variants(&mut generator, 3);
+        assert_eq!(envelope.len(), 3);
+        assert!((envelope[0].mz - y3.mz()).abs() < 1e-3);
+
+        let fragments = peptide.fragments(&[IonSeries::B], -1..=1, &[]).unwrap();
+        assert_eq!(fragments.len(), 6 * 2);
+        assert!(fragments.iter().all(|f| f.charge != 0));
+        assert!(fragments.iter().any(|f| f.to_string() == "b2-"));
+        let neutral = Fragment {
+            charge: 0,
+            ..fragments[0].clone()
+        };
+        assert_eq!(neutral.to_string(), "b1");
+    }
+}
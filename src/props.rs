@@ -46,6 +46,63 @@ pub trait ChemicalCompositionLike<'inner, 'lifespan: 'inner> {
         self.mass()
     }
 
+    // The masses below default to summing over the element counts, so they borrow the
+    // composition for `'inner` like `_iter`. Implementations with caches override them.
+
+    /// Get the mass of this chemical composition from each element's lightest isotope
+    fn monoisotopic_mass(&'inner self) -> f64 {
+        self._iter().fold(0.0, |total, (elt_spec, count)| {
+            elt_spec.monoisotopic_mass().mul_add(*count as f64, total)
+        })
+    }
+
+    /// Get the abundance-weighted average mass of this chemical composition, using
+    /// the cache if it has been populated.
+    fn average_mass(&'inner self) -> f64 {
+        self._iter().fold(0.0, |total, (elt_spec, count)| {
+            elt_spec.average_mass().mul_add(*count as f64, total)
+        })
+    }
+
+    /// Get the average mass of this chemical composition, and cache it, or reuse
+    /// the cached value.
+    fn faverage_mass(&'inner mut self) -> f64 {
+        self.average_mass()
+    }
+
+    /// Get the molar mass of this chemical composition from each element's standard
+    /// atomic weight, using the cache if it has been populated.
+    fn molar_mass(&'inner self) -> f64 {
+        self._iter().fold(0.0, |total, (elt_spec, count)| {
+            elt_spec.molar_mass().mul_add(*count as f64, total)
+        })
+    }
+
+    /// Get the molar mass of this chemical composition, and cache it, or reuse
+    /// the cached value.
+    fn fmolar_mass(&'inner mut self) -> f64 {
+        self.molar_mass()
+    }
+
+    /// Get the integer nominal mass of this chemical composition, using the cache
+    /// if it has been populated.
+    fn nominal_mass(&'inner self) -> i64 {
+        self._iter()
+            .map(|(elt_spec, count)| elt_spec.nominal_mass() as i64 * *count as i64)
+            .sum()
+    }
+
+    /// Get the nominal mass of this chemical composition, and cache it, or reuse
+    /// the cached value.
+    fn fnominal_mass(&'inner mut self) -> i64 {
+        self.nominal_mass()
+    }
+
+    /// Get the difference between the mass and the nominal mass
+    fn mass_defect(&'inner self) -> f64 {
+        self.mass() - self.nominal_mass() as f64
+    }
+
     fn is_empty(&self) -> bool;
 
     fn len(&self) -> usize;
@@ -100,6 +157,34 @@ impl<'transient, 'lifespan: 'transient> ChemicalCompositionLike<'transient, 'lif
         self.fmass()
     }
 
+    fn monoisotopic_mass(&self) -> f64 {
+        self.monoisotopic_mass()
+    }
+
+    fn average_mass(&self) -> f64 {
+        self.average_mass()
+    }
+
+    fn faverage_mass(&mut self) -> f64 {
+        self.faverage_mass()
+    }
+
+    fn molar_mass(&self) -> f64 {
+        self.molar_mass()
+    }
+
+    fn fmolar_mass(&mut self) -> f64 {
+        self.fmolar_mass()
+    }
+
+    fn nominal_mass(&self) -> i64 {
+        self.nominal_mass()
+    }
+
+    fn fnominal_mass(&mut self) -> i64 {
+        self.fnominal_mass()
+    }
+
     fn is_empty(&self) -> bool {
         self.is_empty()
     }
@@ -168,6 +253,34 @@ impl<'transient, 'lifespan: 'transient> ChemicalCompositionLike<'transient, 'lif
         self.fmass()
     }
 
+    fn monoisotopic_mass(&self) -> f64 {
+        self.monoisotopic_mass()
+    }
+
+    fn average_mass(&self) -> f64 {
+        self.average_mass()
+    }
+
+    fn faverage_mass(&mut self) -> f64 {
+        self.faverage_mass()
+    }
+
+    fn molar_mass(&self) -> f64 {
+        self.molar_mass()
+    }
+
+    fn fmolar_mass(&mut self) -> f64 {
+        self.fmolar_mass()
+    }
+
+    fn nominal_mass(&self) -> i64 {
+        self.nominal_mass()
+    }
+
+    fn fnominal_mass(&mut self) -> i64 {
+        self.fnominal_mass()
+    }
+
     fn is_empty(&self) -> bool {
         self.is_empty()
     }
@@ -213,6 +326,34 @@ impl<'transient, 'lifespan: 'transient> ChemicalCompositionLike<'transient, 'lif
         self.fmass()
     }
 
+    fn monoisotopic_mass(&self) -> f64 {
+        self.monoisotopic_mass()
+    }
+
+    fn average_mass(&self) -> f64 {
+        self.average_mass()
+    }
+
+    fn faverage_mass(&mut self) -> f64 {
+        self.faverage_mass()
+    }
+
+    fn molar_mass(&self) -> f64 {
+        self.molar_mass()
+    }
+
+    fn fmolar_mass(&mut self) -> f64 {
+        self.fmolar_mass()
+    }
+
+    fn nominal_mass(&self) -> i64 {
+        self.nominal_mass()
+    }
+
+    fn fnominal_mass(&mut self) -> i64 {
+        self.fnominal_mass()
+    }
+
     fn is_empty(&self) -> bool {
         self.is_empty()
     }
@@ -509,4 +650,61 @@ mod test {
         }
         assert_eq!(24, parts)
     }
+
+    /// An implementation providing only the required methods
+    struct Counts<'lifespan>(ChemicalCompositionVec<'lifespan>);
+
+    impl<'transient, 'lifespan: 'transient> ChemicalCompositionLike<'transient, 'lifespan>
+        for Counts<'lifespan>
+    {
+        type Iter = VecIt<'transient, 'lifespan>;
+
+        fn get(&self, elt_spec: &ElementSpecification<'lifespan>) -> i32 {
+            self.0.get(elt_spec)
+        }
+
+        fn set(&mut self, elt_spec: ElementSpecification<'lifespan>, count: i32) {
+            self.0.set(elt_spec, count)
+        }
+
+        fn mass(&self) -> f64 {
+            self.0.mass()
+        }
+
+        fn is_empty(&self) -> bool {
+            self.0.is_empty()
+        }
+
+        fn len(&self) -> usize {
+            self.0.len()
+        }
+
+        fn _iter(&'transient self) -> Self::Iter {
+            VecIt {
+                composition: &self.0,
+                offset: 0,
+            }
+        }
+
+        fn _mul_by(&mut self, scaler: i32) {
+            self.0._mul_by(scaler)
+        }
+    }
+
+    #[test]
+    fn test_default_masses() {
+        let comp = ChemicalCompositionVec::from(vec![
+            ("C", 34),
+            ("H", 53),
+            ("O", 15),
+            ("N", 7),
+            ("Fe", 1),
+        ]);
+        let counts = Counts(comp.clone());
+        assert!((counts.monoisotopic_mass() - comp.monoisotopic_mass()).abs() < 1e-9);
+        assert!((counts.average_mass() - comp.average_mass()).abs() < 1e-9);
+        assert!((counts.molar_mass() - comp.molar_mass()).abs() < 1e-9);
+        assert_eq!(counts.nominal_mass(), comp.nominal_mass());
+        assert!((counts.mass_defect() - comp.mass_defect()).abs() < 1e-9);
+    }
 }
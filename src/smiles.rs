@@ -0,0 +1,659 @@
+//! Compute the elemental composition of a molecule from a SMILES string, following the
+//! OpenSMILES conventions for implicit hydrogens.
+//!
+//! Only what determines the composition is read: atoms, bonds, branches, ring closures,
+//! bracket atoms with their isotopes, hydrogen counts and charges. Stereochemistry and atom
+//! classes are parsed but ignored.
+//!
+//! ```rust
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use chemical_elements::ChemicalComposition;
+//! use chemical_elements::smiles::parse_smiles;
+//!
+//! let caffeine = parse_smiles("Cn1cnc2c1c(=O)n(C)c(=O)n2C")?;
+//! assert_eq!(caffeine.composition, ChemicalComposition::parse("C8H10N4O2")?);
+//!
+//! let acetate = parse_smiles("[13CH3]C(=O)[O-]")?;
+//! assert_eq!(acetate.charge, -1);
+//! assert_eq!(acetate["H"], 3);
+//! # Ok(())
+//! # }
+//! ```
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::ops::Range;
+
+use chemical_elements_grammar::write_diagnostic;
+
+use crate::charged_composition::ChargedComposition;
+use crate::formula::char_span;
+use crate::table::PERIODIC_TABLE;
+use crate::{ChemicalComposition, ElementSpecification, PeriodicTable};
+
+#[derive(Debug, Clone, PartialEq)]
+/// The ways a SMILES string can fail to describe a composition
+pub enum SmilesErrorKind {
+    /// A character which does not belong at this byte offset
+    UnexpectedCharacter { position: usize, character: char },
+    /// An element symbol not found in the periodic table
+    UnknownElement { position: usize, symbol: String },
+    /// An isotope not known for its element
+    UnknownIsotope {
+        position: usize,
+        symbol: String,
+        isotope: u16,
+    },
+    /// The wildcard atom `*` has no composition
+    WildcardAtom(usize),
+    /// A bracket atom opened at this byte offset was not closed
+    UnclosedBracket(usize),
+    /// A branch opened at this byte offset was not closed
+    UnclosedBranch(usize),
+    /// A branch was closed at this byte offset without being opened
+    UnmatchedBranch(usize),
+    /// A ring bond with this number was opened but never closed
+    UnclosedRing(u32),
+    /// A bond or branch at this byte offset did not follow an atom
+    MissingAtom(usize),
+}
+
+impl Display for SmilesErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnexpectedCharacter { .. } => f.write_str("unexpected character"),
+            Self::UnknownElement { .. } => f.write_str("unknown element"),
+            Self::UnknownIsotope { symbol, .. } => write!(f, "unknown isotope of {symbol}"),
+            Self::WildcardAtom(_) => f.write_str("wildcard atom without a composition"),
+            Self::UnclosedBracket(_) => f.write_str("unclosed bracket atom"),
+            Self::UnclosedBranch(_) => f.write_str("unclosed branch"),
+            Self::UnmatchedBranch(_) => f.write_str("branch closed without being opened"),
+            Self::UnclosedRing(ring) => write!(f, "ring bond {ring} is never closed"),
+            Self::MissingAtom(_) => f.write_str("bond or branch without an atom"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/**
+An error computing a composition from a SMILES string, locating the problem in the string.
+Its [`Display`] implementation renders the string with a caret under the offending token.
+*/
+pub struct SmilesError {
+    pub kind: SmilesErrorKind,
+    /// The SMILES string being parsed, without surrounding whitespace
+    pub smiles: String,
+}
+
+impl SmilesError {
+    pub fn new(kind: SmilesErrorKind, smiles: &str) -> Self {
+        Self {
+            kind,
+            smiles: smiles.to_string(),
+        }
+    }
+
+    /// The byte range of the offending token in `smiles`, unless the error is about the
+    /// string as a whole, like an unclosed ring bond
+    pub fn span(&self) -> Option<Range<usize>> {
+        let span = match &self.kind {
+            SmilesErrorKind::UnknownElement { position, symbol } => {
+                *position..position + symbol.len()
+            }
+            SmilesErrorKind::UnknownIsotope { position, .. } => {
+                let digits = self.smiles[*position..]
+                    .chars()
+                    .take_while(|c| c.is_ascii_digit())
+                    .count();
+                *position..position + digits
+            }
+            SmilesErrorKind::UnexpectedCharacter { position, .. }
+            | SmilesErrorKind::WildcardAtom(position)
+            | SmilesErrorKind::UnclosedBracket(position)
+            | SmilesErrorKind::UnclosedBranch(position)
+            | SmilesErrorKind::UnmatchedBranch(position)
+            | SmilesErrorKind::MissingAtom(position) => char_span(&self.smiles, *position),
+            SmilesErrorKind::UnclosedRing(_) => return None,
+        };
+        Some(span)
+    }
+}
+
+impl Display for SmilesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.span() {
+            Some(span) => write_diagnostic(f, &self.kind, &self.smiles, &span, &[]),
+            None => write!(f, "{}\n{}", self.kind, self.smiles),
+        }
+    }
+}
+
+impl std::error::Error for SmilesError {}
+
+/// The normal valences of the organic subset elements, used to infer implicit hydrogens
+fn normal_valences(symbol: &str) -> &'static [i32] {
+    match symbol {
+        "B" => &[3],
+        "C" => &[4],
+        "N" | "P" => &[3, 5],
+        "O" => &[2],
+        "S" => &[2, 4, 6],
+        "F" | "Cl" | "Br" | "I" => &[1],
+        _ => &[],
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Atom {
+    symbol: String,
+    isotope: u16,
+    aromatic: bool,
+    /// Bracket atoms state their hydrogens explicitly, the others have them inferred
+    bracket: bool,
+    hydrogens: i32,
+    /// The byte offset of the hydrogen count, for reporting a total which overflows
+    hydrogens_position: usize,
+    charge: i32,
+    /// The byte offset of the charge's magnitude, for reporting a net charge which overflows
+    charge_position: usize,
+    /// The sum of the orders of the bonds to this atom, with aromatic bonds counting one
+    bond_order_sum: i32,
+}
+
+impl Atom {
+    fn implicit_hydrogens(&self) -> i32 {
+        if self.bracket {
+            return self.hydrogens;
+        }
+        let valences = normal_valences(&self.symbol);
+        if self.aromatic {
+            // One valence is taken up by the aromatic system, and aromatic atoms only
+            // take their lowest valence
+            valences
+                .first()
+                .map(|v| (v - self.bond_order_sum - 1).max(0))
+                .unwrap_or_default()
+        } else {
+            valences
+                .iter()
+                .find(|v| **v >= self.bond_order_sum)
+                .map(|v| v - self.bond_order_sum)
+                .unwrap_or_default()
+        }
+    }
+}
+
+struct SmilesParser<'a> {
+    smiles: &'a str,
+    position: usize,
+    atoms: Vec<Atom>,
+    previous: Option<usize>,
+    pending_bond: Option<(i32, usize)>,
+    branches: Vec<(Option<usize>, usize)>,
+    /// Open ring bonds by ring number, with the atom and any bond order written at the opening
+    rings: HashMap<u32, (usize, Option<i32>)>,
+}
+
+impl SmilesParser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.smiles[self.position..].chars().next()
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += c.len_utf8();
+        Some(c)
+    }
+
+    fn digits(&mut self) -> Option<u32> {
+        let start = self.position;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.advance();
+        }
+        self.smiles[start..self.position].parse().ok()
+    }
+
+    fn unexpected(&self, position: usize) -> SmilesErrorKind {
+        match self.smiles[position..].chars().next() {
+            Some(character) => SmilesErrorKind::UnexpectedCharacter {
+                position,
+                character,
+            },
+            None => SmilesErrorKind::MissingAtom(position),
+        }
+    }
+
+    fn parse(&mut self, periodic_table: &PeriodicTable) -> Result<(), SmilesErrorKind> {
+        while let Some(c) = self.peek() {
+            let start = self.position;
+            match c {
+                '-' | '=' | '#' | '$' | ':' | '/' | '\\' => {
+                    self.advance();
+                    let order = match c {
+                        '=' => 2,
+                        '#' => 3,
+                        '$' => 4,
+                        _ => 1,
+                    };
+                    if self.previous.is_none() || self.pending_bond.is_some() {
+                        return Err(SmilesErrorKind::MissingAtom(start));
+                    }
+                    self.pending_bond = Some((order, start));
+                }
+                '(' => {
+                    self.advance();
+                    if self.previous.is_none() {
+                        return Err(SmilesErrorKind::MissingAtom(start));
+                    }
+                    self.branches.push((self.previous, start));
+                }
+                ')' => {
+                    self.advance();
+                    let (atom, _) = self
+                        .branches
+                        .pop()
+                        .ok_or(SmilesErrorKind::UnmatchedBranch(start))?;
+                    if self.pending_bond.is_some() {
+                        return Err(SmilesErrorKind::MissingAtom(start));
+                    }
+                    self.previous = atom;
+                }
+                '.' => {
+                    self.advance();
+                    if self.pending_bond.is_some() {
+                        return Err(SmilesErrorKind::MissingAtom(start));
+                    }
+                    self.previous = None;
+                }
+                '0'..='9' | '%' => {
+                    self.advance();
+                    let ring = if c == '%' {
+                        let digits = self.position;
+                        self.advance();
+                        self.advance();
+                        self.smiles
+                            .get(digits..self.position)
+                            .and_then(|d| d.parse().ok())
+                            .ok_or_else(|| self.unexpected(digits))?
+                    } else {
+                        c.to_digit(10).unwrap()
+                    };
+                    self.ring_bond(ring, start)?;
+                }
+                '[' => {
+                    let atom = self.bracket_atom(periodic_table)?;
+                    self.add_atom(atom);
+                }
+                '*' => return Err(SmilesErrorKind::WildcardAtom(start)),
+                _ => {
+                    let atom = self.organic_atom(periodic_table)?;
+                    self.add_atom(atom);
+                }
+            }
+        }
+        if let Some((_, position)) = self.branches.last() {
+            return Err(SmilesErrorKind::UnclosedBranch(*position));
+        }
+        if let Some((_, position)) = self.pending_bond {
+            return Err(SmilesErrorKind::MissingAtom(position));
+        }
+        if let Some(ring) = self.rings.keys().min() {
+            return Err(SmilesErrorKind::UnclosedRing(*ring));
+        }
+        Ok(())
+    }
+
+    fn add_atom(&mut self, atom: Atom) {
+        let index = self.atoms.len();
+        self.atoms.push(atom);
+        if let Some(previous) = self.previous {
+            let order = self
+                .pending_bond
+                .take()
+                .map(|(order, _)| order)
+                .unwrap_or(1);
+            self.atoms[previous].bond_order_sum += order;
+            self.atoms[index].bond_order_sum += order;
+        }
+        self.previous = Some(index);
+    }
+
+    fn ring_bond(&mut self, ring: u32, position: usize) -> Result<(), SmilesErrorKind> {
+        let current = self.previous.ok_or(SmilesErrorKind::MissingAtom(position))?;
+        let order = self.pending_bond.take().map(|(order, _)| order);
+        match self.rings.remove(&ring) {
+            Some((partner, opening_order)) => {
+                let order = order.or(opening_order).unwrap_or(1);
+                self.atoms[partner].bond_order_sum += order;
+                self.atoms[current].bond_order_sum += order;
+            }
+            None => {
+                self.rings.insert(ring, (current, order));
+            }
+        }
+        Ok(())
+    }
+
+    fn organic_atom(&mut self, periodic_table: &PeriodicTable) -> Result<Atom, SmilesErrorKind> {
+        let start = self.position;
+        let rest = &self.smiles[start..];
+        let (symbol, aromatic) = if rest.starts_with("Cl") || rest.starts_with("Br") {
+            (&rest[..2], false)
+        } else {
+            match rest.chars().next() {
+                Some('B' | 'C' | 'N' | 'O' | 'P' | 'S' | 'F' | 'I') => (&rest[..1], false),
+                Some('b' | 'c' | 'n' | 'o' | 'p' | 's') => (&rest[..1], true),
+                _ => return Err(self.unexpected(start)),
+            }
+        };
+        self.position += symbol.len();
+        let symbol = capitalize(symbol);
+        if periodic_table.get(&symbol).is_none() {
+            return Err(SmilesErrorKind::UnknownElement {
+                position: start,
+                symbol,
+            });
+        }
+        Ok(Atom {
+            symbol,
+            aromatic,
+            ..Default::default()
+        })
+    }
+
+    fn bracket_atom(&mut self, periodic_table: &PeriodicTable) -> Result<Atom, SmilesErrorKind> {
+        let open = self.position;
+        self.advance();
+        let isotope_start = self.position;
+        // An isotope too large for a `u16` is rejected rather than truncated to another isotope
+        let isotope = match self.digits() {
+            Some(isotope) => u16::try_from(isotope).map_err(|_| self.unexpected(isotope_start))?,
+            None if self.position > isotope_start => return Err(self.unexpected(isotope_start)),
+            None => 0,
+        };
+
+        let symbol_start = self.position;
+        if self.peek() == Some('*') {
+            return Err(SmilesErrorKind::WildcardAtom(symbol_start));
+        }
+        let rest = &self.smiles[symbol_start..];
+        let symbol = if let Some(c) = rest.chars().next().filter(|c| c.is_ascii_lowercase()) {
+            // Aromatic symbols are lowercase, including the two letter `se` and `as`
+            if rest.starts_with("se") || rest.starts_with("as") || rest.starts_with("te") {
+                &rest[..2]
+            } else {
+                &rest[..c.len_utf8()]
+            }
+        } else if rest.starts_with(|c: char| c.is_ascii_uppercase()) {
+            let lowercase = rest[1..]
+                .chars()
+                .take_while(|c| c.is_ascii_lowercase())
+                .count();
+            // Prefer the two letter symbol, like `Cl`, over a symbol and a hydrogen count
+            if lowercase > 0 && periodic_table.get(&rest[..2]).is_some() {
+                &rest[..2]
+            } else {
+                &rest[..1]
+            }
+        } else {
+            return Err(self.unexpected(symbol_start));
+        };
+        self.position += symbol.len();
+        let aromatic = symbol.starts_with(|c: char| c.is_ascii_lowercase());
+        let symbol = capitalize(symbol);
+        let element = periodic_table
+            .get(&symbol)
+            .ok_or_else(|| SmilesErrorKind::UnknownElement {
+                position: symbol_start,
+                symbol: symbol.clone(),
+            })?;
+        if isotope != 0 && !element.isotopes.contains_key(&isotope) {
+            return Err(SmilesErrorKind::UnknownIsotope {
+                position: open + 1,
+                symbol,
+                isotope,
+            });
+        }
+
+        // Chirality, like `@`, `@@` or `@TH1`, does not affect the composition
+        if self.peek() == Some('@') {
+            self.advance();
+            if self.peek() == Some('@') {
+                self.advance();
+            }
+            let rest = &self.smiles[self.position..];
+            if ["TH", "AL", "SP", "TB", "OH"]
+                .iter()
+                .any(|class| rest.starts_with(class))
+            {
+                self.position += 2;
+                self.digits();
+            }
+        }
+        // Counts too large for an `i32` are rejected like isotopes
+        let mut hydrogens = 0;
+        let mut hydrogens_position = self.position;
+        if self.peek() == Some('H') {
+            self.advance();
+            hydrogens_position = self.position;
+            hydrogens = match self.digits() {
+                Some(count) => {
+                    i32::try_from(count).map_err(|_| self.unexpected(hydrogens_position))?
+                }
+                None if self.position > hydrogens_position => {
+                    return Err(self.unexpected(hydrogens_position))
+                }
+                None => 1,
+            };
+        }
+        let mut charge = 0;
+        let mut charge_position = self.position;
+        if let Some(sign @ ('+' | '-')) = self.peek() {
+            self.advance();
+            charge_position = self.position;
+            let sign = if sign == '+' { 1 } else { -1 };
+            charge = match self.digits() {
+                Some(magnitude) => {
+                    sign * i32::try_from(magnitude).map_err(|_| self.unexpected(charge_position))?
+                }
+                None if self.position > charge_position => {
+                    return Err(self.unexpected(charge_position))
+                }
+                None => {
+                    let mut magnitude = 1;
+                    while self.peek() == Some(if sign > 0 { '+' } else { '-' }) {
+                        self.advance();
+                        magnitude += 1;
+                    }
+                    sign * magnitude
+                }
+            };
+        }
+        // The atom class does not affect the composition
+        if self.peek() == Some(':') {
+            self.advance();
+            self.digits();
+        }
+        match self.advance() {
+            Some(']') => {}
+            Some(_) => return Err(self.unexpected(self.position - 1)),
+            None => return Err(SmilesErrorKind::UnclosedBracket(open)),
+        }
+        Ok(Atom {
+            symbol,
+            isotope,
+            aromatic,
+            bracket: true,
+            hydrogens,
+            hydrogens_position,
+            charge,
+            charge_position,
+            bond_order_sum: 0,
+        })
+    }
+}
+
+fn capitalize(symbol: &str) -> String {
+    let mut chars = symbol.chars();
+    match chars.next() {
+        Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+        None => String::new(),
+    }
+}
+
+/**
+Compute the composition of the molecule described by `smiles`, with its net formal charge,
+using the global [`PeriodicTable`].
+
+Atoms outside brackets gain implicit hydrogens up to their lowest normal valence that
+accommodates their bonds, while bracket atoms carry exactly the hydrogens they state.
+*/
+pub fn parse_smiles(smiles: &str) -> Result<ChargedComposition<'static>, SmilesError> {
+    parse_smiles_with(smiles, &PERIODIC_TABLE)
+}
+
+/// Compute the composition of the molecule described by `smiles` using the specified
+/// [`PeriodicTable`]. See [`parse_smiles`].
+pub fn parse_smiles_with<'lifespan>(
+    smiles: &str,
+    periodic_table: &'lifespan PeriodicTable,
+) -> Result<ChargedComposition<'lifespan>, SmilesError> {
+    let smiles = smiles.trim();
+    let mut parser = SmilesParser {
+        smiles,
+        position: 0,
+        atoms: Vec::new(),
+        previous: None,
+        pending_bond: None,
+        branches: Vec::new(),
+        rings: HashMap::new(),
+    };
+    let error = |kind| SmilesError::new(kind, smiles);
+    parser.parse(periodic_table).map_err(error)?;
+
+    let mut composition = ChemicalComposition::new();
+    let mut hydrogens: i32 = 0;
+    let mut charge: i32 = 0;
+    for atom in parser.atoms.iter() {
+        composition.inc(
+            ElementSpecification::new(&periodic_table[atom.symbol.as_str()], atom.isotope),
+            1,
+        );
+        hydrogens = hydrogens
+            .checked_add(atom.implicit_hydrogens())
+            .ok_or_else(|| error(parser.unexpected(atom.hydrogens_position)))?;
+        charge = charge
+            .checked_add(atom.charge)
+            .ok_or_else(|| error(parser.unexpected(atom.charge_position)))?;
+    }
+    if hydrogens > 0 {
+        composition.inc(ElementSpecification::new(&periodic_table["H"], 0), hydrogens);
+    }
+    Ok(ChargedComposition::new(composition, charge))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_compositions() {
+        for (smiles, formula) in [
+            ("CCO", "C2H6O"),
+            ("c1ccccc1", "C6H6"),
+            ("c1ccncc1", "C5H5N"),
+            ("c1ccsc1", "C4H4S"),
+            ("c1cc[nH]c1", "C4H5N"),
+            ("Cn1cnc2c1c(=O)n(C)c(=O)n2C", "C8H10N4O2"),
+            ("OS(=O)(=O)O", "H2SO4"),
+            ("CS(C)=O", "C2H6OS"),
+            ("N[C@@H](C)C(=O)O", "C3H7NO2"),
+            ("F/C=C/F", "C2H2F2"),
+            ("C1CC%10CC1.C%10", "C6H12"),
+            ("ClC(Cl)(Cl)Br", "CBrCl3"),
+            ("[Na+].[Cl-]", "NaCl"),
+            ("C[N+](=O)[O-]", "CH3NO2"),
+        ] {
+            let molecule = parse_smiles(smiles).unwrap();
+            assert_eq!(
+                molecule.composition,
+                ChemicalComposition::parse(formula).unwrap(),
+                "{smiles}"
+            );
+            assert_eq!(molecule.charge, 0, "{smiles}");
+        }
+
+        let ammonium = parse_smiles("[NH4+]").unwrap();
+        assert_eq!(ammonium.charge, 1);
+        assert_eq!(ammonium["H"], 4);
+        let labeled = parse_smiles("[13CH3]C(=O)O").unwrap();
+        assert_eq!(
+            labeled.composition,
+            ChemicalComposition::parse("C[13]1C1H4O2").unwrap()
+        );
+        assert_eq!(parse_smiles("[Fe++]").unwrap().charge, 2);
+        assert_eq!(
+            parse_smiles("[2H]O[2H]").unwrap()[&ElementSpecification::parse("H[2]").unwrap()],
+            2
+        );
+    }
+
+    fn error_kind(smiles: &str) -> SmilesErrorKind {
+        parse_smiles(smiles).unwrap_err().kind
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(error_kind("C1CC"), SmilesErrorKind::UnclosedRing(1));
+        assert_eq!(error_kind("C(C"), SmilesErrorKind::UnclosedBranch(1));
+        assert_eq!(error_kind("CC)"), SmilesErrorKind::UnmatchedBranch(2));
+        assert_eq!(error_kind("[13C"), SmilesErrorKind::UnclosedBracket(0));
+        assert_eq!(error_kind("C*"), SmilesErrorKind::WildcardAtom(1));
+        assert_eq!(error_kind("CC="), SmilesErrorKind::MissingAtom(2));
+        assert!(matches!(
+            error_kind("C[Xx]"),
+            SmilesErrorKind::UnknownElement { position: 2, .. }
+        ));
+        assert!(matches!(
+            error_kind("[99C]"),
+            SmilesErrorKind::UnknownIsotope { isotope: 99, .. }
+        ));
+        for (smiles, position) in [
+            ("[65548C]", 1),
+            ("[99999999999C]", 1),
+            ("[CH99999999999]", 3),
+            ("[CH4294967295]", 3),
+            ("[C-4294967295]", 3),
+            ("[C-2147483648]", 3),
+            ("[C+2147483647].[C+1]", 18),
+            ("[CH2147483647].[CH1]", 18),
+        ] {
+            let character = smiles[position..].chars().next().unwrap();
+            assert_eq!(
+                error_kind(smiles),
+                SmilesErrorKind::UnexpectedCharacter {
+                    position,
+                    character
+                },
+                "{smiles}"
+            );
+        }
+        assert!(matches!(
+            error_kind("CQ"),
+            SmilesErrorKind::UnexpectedCharacter {
+                position: 1,
+                character: 'Q'
+            }
+        ));
+
+        let err = parse_smiles(" C[Xx]").unwrap_err();
+        assert_eq!(err.span(), Some(2..3));
+        assert_eq!(err.to_string(), "unknown element `X` at byte 2\nC[Xx]\n  ^");
+        let err = parse_smiles("[99C]").unwrap_err();
+        assert_eq!(err.to_string(), "unknown isotope of C `99` at byte 1\n[99C]\n ^^");
+        let err = parse_smiles("CC=").unwrap_err();
+        assert_eq!(err.to_string(), "bond or branch without an atom `=` at byte 2\nCC=\n  ^");
+        let err = parse_smiles("C1CC").unwrap_err();
+        assert_eq!(err.span(), None);
+        assert_eq!(err.to_string(), "ring bond 1 is never closed\nC1CC");
+    }
+}
@@ -1,4 +1,4 @@
-use crate::element::{Element, Isotope, PeriodicTable};
+use crate::element::{Element, Isotope, PeriodicTable, StandardAtomicWeight};
 use std::sync::LazyLock;
 
 pub fn populate_periodic_table(table: &mut PeriodicTable) {
@@ -6,6 +6,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Ac"),
         most_abundant_isotope: 0,
         most_abundant_mass: 227.000000,
+        element_number: 89,
+        name: String::from("Actinium"),
+        standard_atomic_weight: StandardAtomicWeight::MassNumber(227),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -24,7 +27,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Ag"),
         most_abundant_isotope: 107,
         most_abundant_mass: 106.905097,
-        element_number: 107,
+        element_number: 47,
+        name: String::from("Silver"),
+        standard_atomic_weight: StandardAtomicWeight::Value(107.8682),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -52,7 +57,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Al"),
         most_abundant_isotope: 27,
         most_abundant_mass: 26.981539,
-        element_number: 27,
+        element_number: 13,
+        name: String::from("Aluminium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(26.9815385),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -71,6 +78,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Am"),
         most_abundant_isotope: 0,
         most_abundant_mass: 243.000000,
+        element_number: 95,
+        name: String::from("Americium"),
+        standard_atomic_weight: StandardAtomicWeight::Unknown,
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -89,7 +99,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Ar"),
         most_abundant_isotope: 40,
         most_abundant_mass: 39.962383,
-        element_number: 40,
+        element_number: 18,
+        name: String::from("Argon"),
+        standard_atomic_weight: StandardAtomicWeight::Value(39.948),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -126,7 +138,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("As"),
         most_abundant_isotope: 75,
         most_abundant_mass: 74.921597,
-        element_number: 75,
+        element_number: 33,
+        name: String::from("Arsenic"),
+        standard_atomic_weight: StandardAtomicWeight::Value(74.921595),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -145,6 +159,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("At"),
         most_abundant_isotope: 0,
         most_abundant_mass: 210.000000,
+        element_number: 85,
+        name: String::from("Astatine"),
+        standard_atomic_weight: StandardAtomicWeight::MassNumber(210),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -163,7 +180,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Au"),
         most_abundant_isotope: 197,
         most_abundant_mass: 196.966569,
-        element_number: 197,
+        element_number: 79,
+        name: String::from("Gold"),
+        standard_atomic_weight: StandardAtomicWeight::Value(196.966569),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -182,7 +201,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("B"),
         most_abundant_isotope: 11,
         most_abundant_mass: 11.009305,
-        element_number: 11,
+        element_number: 5,
+        name: String::from("Boron"),
+        standard_atomic_weight: StandardAtomicWeight::Interval(10.806, 10.821),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -210,7 +231,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Ba"),
         most_abundant_isotope: 138,
         most_abundant_mass: 137.905247,
-        element_number: 138,
+        element_number: 56,
+        name: String::from("Barium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(137.327),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -283,7 +306,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Be"),
         most_abundant_isotope: 9,
         most_abundant_mass: 9.012182,
-        element_number: 9,
+        element_number: 4,
+        name: String::from("Beryllium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(9.0121831),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -302,6 +327,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Bh"),
         most_abundant_isotope: 0,
         most_abundant_mass: 272.000000,
+        element_number: 107,
+        name: String::from("Bohrium"),
+        standard_atomic_weight: StandardAtomicWeight::Unknown,
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -320,7 +348,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Bi"),
         most_abundant_isotope: 209,
         most_abundant_mass: 208.980399,
-        element_number: 209,
+        element_number: 83,
+        name: String::from("Bismuth"),
+        standard_atomic_weight: StandardAtomicWeight::Value(208.9804),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -339,6 +369,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Bk"),
         most_abundant_isotope: 0,
         most_abundant_mass: 247.000000,
+        element_number: 97,
+        name: String::from("Berkelium"),
+        standard_atomic_weight: StandardAtomicWeight::Unknown,
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -357,7 +390,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Br"),
         most_abundant_isotope: 79,
         most_abundant_mass: 78.918337,
-        element_number: 79,
+        element_number: 35,
+        name: String::from("Bromine"),
+        standard_atomic_weight: StandardAtomicWeight::Interval(79.901, 79.907),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -385,7 +420,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("C"),
         most_abundant_isotope: 12,
         most_abundant_mass: 12.000000,
-        element_number: 12,
+        element_number: 6,
+        name: String::from("Carbon"),
+        standard_atomic_weight: StandardAtomicWeight::Interval(12.0096, 12.0116),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -413,7 +450,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Ca"),
         most_abundant_isotope: 40,
         most_abundant_mass: 39.962591,
-        element_number: 40,
+        element_number: 20,
+        name: String::from("Calcium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(40.078),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -477,7 +516,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Cd"),
         most_abundant_isotope: 114,
         most_abundant_mass: 113.903358,
-        element_number: 114,
+        element_number: 48,
+        name: String::from("Cadmium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(112.414),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -559,7 +600,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Ce"),
         most_abundant_isotope: 140,
         most_abundant_mass: 139.905439,
-        element_number: 140,
+        element_number: 58,
+        name: String::from("Cerium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(140.116),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -605,6 +648,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Cf"),
         most_abundant_isotope: 0,
         most_abundant_mass: 251.000000,
+        element_number: 98,
+        name: String::from("Californium"),
+        standard_atomic_weight: StandardAtomicWeight::Unknown,
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -623,7 +669,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Cl"),
         most_abundant_isotope: 35,
         most_abundant_mass: 34.968853,
-        element_number: 35,
+        element_number: 17,
+        name: String::from("Chlorine"),
+        standard_atomic_weight: StandardAtomicWeight::Interval(35.446, 35.457),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -651,6 +699,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Cm"),
         most_abundant_isotope: 0,
         most_abundant_mass: 247.000000,
+        element_number: 96,
+        name: String::from("Curium"),
+        standard_atomic_weight: StandardAtomicWeight::Unknown,
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -669,6 +720,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Cn"),
         most_abundant_isotope: 0,
         most_abundant_mass: 285.000000,
+        element_number: 112,
+        name: String::from("Copernicium"),
+        standard_atomic_weight: StandardAtomicWeight::Unknown,
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -687,7 +741,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Co"),
         most_abundant_isotope: 59,
         most_abundant_mass: 58.933195,
-        element_number: 59,
+        element_number: 27,
+        name: String::from("Cobalt"),
+        standard_atomic_weight: StandardAtomicWeight::Value(58.933194),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -706,7 +762,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Cr"),
         most_abundant_isotope: 52,
         most_abundant_mass: 51.940508,
-        element_number: 52,
+        element_number: 24,
+        name: String::from("Chromium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(51.9961),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -752,7 +810,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Cs"),
         most_abundant_isotope: 133,
         most_abundant_mass: 132.905452,
-        element_number: 133,
+        element_number: 55,
+        name: String::from("Caesium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(132.90545196),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -771,7 +831,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Cu"),
         most_abundant_isotope: 63,
         most_abundant_mass: 62.929597,
-        element_number: 63,
+        element_number: 29,
+        name: String::from("Copper"),
+        standard_atomic_weight: StandardAtomicWeight::Value(63.546),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -799,6 +861,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Db"),
         most_abundant_isotope: 0,
         most_abundant_mass: 268.000000,
+        element_number: 105,
+        name: String::from("Dubnium"),
+        standard_atomic_weight: StandardAtomicWeight::Unknown,
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -817,6 +882,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Ds"),
         most_abundant_isotope: 0,
         most_abundant_mass: 281.000000,
+        element_number: 110,
+        name: String::from("Darmstadtium"),
+        standard_atomic_weight: StandardAtomicWeight::Unknown,
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -835,7 +903,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Dy"),
         most_abundant_isotope: 164,
         most_abundant_mass: 163.929175,
-        element_number: 164,
+        element_number: 66,
+        name: String::from("Dysprosium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(162.5),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -908,7 +978,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Er"),
         most_abundant_isotope: 166,
         most_abundant_mass: 165.930293,
-        element_number: 166,
+        element_number: 68,
+        name: String::from("Erbium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(167.259),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -972,6 +1044,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Es"),
         most_abundant_isotope: 0,
         most_abundant_mass: 252.000000,
+        element_number: 99,
+        name: String::from("Einsteinium"),
+        standard_atomic_weight: StandardAtomicWeight::Unknown,
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -990,7 +1065,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Eu"),
         most_abundant_isotope: 153,
         most_abundant_mass: 152.921230,
-        element_number: 153,
+        element_number: 63,
+        name: String::from("Europium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(151.964),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -1018,7 +1095,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("F"),
         most_abundant_isotope: 19,
         most_abundant_mass: 18.998403,
-        element_number: 19,
+        element_number: 9,
+        name: String::from("Fluorine"),
+        standard_atomic_weight: StandardAtomicWeight::Value(18.998403163),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -1037,7 +1116,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Fe"),
         most_abundant_isotope: 56,
         most_abundant_mass: 55.934937,
-        element_number: 56,
+        element_number: 26,
+        name: String::from("Iron"),
+        standard_atomic_weight: StandardAtomicWeight::Value(55.845),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -1074,6 +1155,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Fm"),
         most_abundant_isotope: 0,
         most_abundant_mass: 257.000000,
+        element_number: 100,
+        name: String::from("Fermium"),
+        standard_atomic_weight: StandardAtomicWeight::Unknown,
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -1092,6 +1176,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Fr"),
         most_abundant_isotope: 0,
         most_abundant_mass: 223.000000,
+        element_number: 87,
+        name: String::from("Francium"),
+        standard_atomic_weight: StandardAtomicWeight::MassNumber(223),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -1110,7 +1197,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Ga"),
         most_abundant_isotope: 69,
         most_abundant_mass: 68.925574,
-        element_number: 69,
+        element_number: 31,
+        name: String::from("Gallium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(69.723),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -1138,7 +1227,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Gd"),
         most_abundant_isotope: 158,
         most_abundant_mass: 157.924104,
-        element_number: 158,
+        element_number: 64,
+        name: String::from("Gadolinium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(157.25),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -1211,7 +1302,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Ge"),
         most_abundant_isotope: 74,
         most_abundant_mass: 73.921178,
-        element_number: 74,
+        element_number: 32,
+        name: String::from("Germanium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(72.63),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -1267,6 +1360,8 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         most_abundant_isotope: 1,
         most_abundant_mass: 1.007825,
         element_number: 1,
+        name: String::from("Hydrogen"),
+        standard_atomic_weight: StandardAtomicWeight::Interval(1.00784, 1.00811),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -1294,7 +1389,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("H+"),
         most_abundant_isotope: 1,
         most_abundant_mass: 1.007276,
-        element_number: 1,
+        element_number: 0,
+        name: String::from("Proton"),
+        standard_atomic_weight: StandardAtomicWeight::Unknown,
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -1313,7 +1410,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("He"),
         most_abundant_isotope: 4,
         most_abundant_mass: 4.002603,
-        element_number: 4,
+        element_number: 2,
+        name: String::from("Helium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(4.002602),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -1341,7 +1440,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Hf"),
         most_abundant_isotope: 180,
         most_abundant_mass: 179.946550,
-        element_number: 180,
+        element_number: 72,
+        name: String::from("Hafnium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(178.49),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -1405,7 +1506,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Hg"),
         most_abundant_isotope: 202,
         most_abundant_mass: 201.970643,
-        element_number: 202,
+        element_number: 80,
+        name: String::from("Mercury"),
+        standard_atomic_weight: StandardAtomicWeight::Value(200.592),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -1478,7 +1581,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Ho"),
         most_abundant_isotope: 165,
         most_abundant_mass: 164.930322,
-        element_number: 165,
+        element_number: 67,
+        name: String::from("Holmium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(164.93033),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -1497,6 +1602,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Hs"),
         most_abundant_isotope: 0,
         most_abundant_mass: 270.000000,
+        element_number: 108,
+        name: String::from("Hassium"),
+        standard_atomic_weight: StandardAtomicWeight::Unknown,
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -1515,7 +1623,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("I"),
         most_abundant_isotope: 127,
         most_abundant_mass: 126.904473,
-        element_number: 127,
+        element_number: 53,
+        name: String::from("Iodine"),
+        standard_atomic_weight: StandardAtomicWeight::Value(126.90447),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -1534,7 +1644,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("In"),
         most_abundant_isotope: 115,
         most_abundant_mass: 114.903878,
-        element_number: 115,
+        element_number: 49,
+        name: String::from("Indium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(114.818),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -1562,7 +1674,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Ir"),
         most_abundant_isotope: 193,
         most_abundant_mass: 192.962926,
-        element_number: 193,
+        element_number: 77,
+        name: String::from("Iridium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(192.217),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -1590,7 +1704,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("K"),
         most_abundant_isotope: 39,
         most_abundant_mass: 38.963707,
-        element_number: 39,
+        element_number: 19,
+        name: String::from("Potassium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(39.0983),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -1627,7 +1743,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Kr"),
         most_abundant_isotope: 84,
         most_abundant_mass: 83.911507,
-        element_number: 84,
+        element_number: 36,
+        name: String::from("Krypton"),
+        standard_atomic_weight: StandardAtomicWeight::Value(83.798),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -1691,7 +1809,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("La"),
         most_abundant_isotope: 139,
         most_abundant_mass: 138.906353,
-        element_number: 139,
+        element_number: 57,
+        name: String::from("Lanthanum"),
+        standard_atomic_weight: StandardAtomicWeight::Value(138.90547),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -1719,7 +1839,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Li"),
         most_abundant_isotope: 7,
         most_abundant_mass: 7.016005,
-        element_number: 7,
+        element_number: 3,
+        name: String::from("Lithium"),
+        standard_atomic_weight: StandardAtomicWeight::Interval(6.938, 6.997),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -1747,6 +1869,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Lr"),
         most_abundant_isotope: 0,
         most_abundant_mass: 262.000000,
+        element_number: 103,
+        name: String::from("Lawrencium"),
+        standard_atomic_weight: StandardAtomicWeight::Unknown,
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -1765,7 +1890,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Lu"),
         most_abundant_isotope: 175,
         most_abundant_mass: 174.940772,
-        element_number: 175,
+        element_number: 71,
+        name: String::from("Lutetium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(174.9668),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -1793,6 +1920,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Md"),
         most_abundant_isotope: 0,
         most_abundant_mass: 258.000000,
+        element_number: 101,
+        name: String::from("Mendelevium"),
+        standard_atomic_weight: StandardAtomicWeight::Unknown,
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -1811,7 +1941,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Mg"),
         most_abundant_isotope: 24,
         most_abundant_mass: 23.985042,
-        element_number: 24,
+        element_number: 12,
+        name: String::from("Magnesium"),
+        standard_atomic_weight: StandardAtomicWeight::Interval(24.304, 24.307),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -1848,7 +1980,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Mn"),
         most_abundant_isotope: 55,
         most_abundant_mass: 54.938045,
-        element_number: 55,
+        element_number: 25,
+        name: String::from("Manganese"),
+        standard_atomic_weight: StandardAtomicWeight::Value(54.938044),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -1867,7 +2001,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Mo"),
         most_abundant_isotope: 98,
         most_abundant_mass: 97.905408,
-        element_number: 98,
+        element_number: 42,
+        name: String::from("Molybdenum"),
+        standard_atomic_weight: StandardAtomicWeight::Value(95.95),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -1940,6 +2076,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Mt"),
         most_abundant_isotope: 0,
         most_abundant_mass: 276.000000,
+        element_number: 109,
+        name: String::from("Meitnerium"),
+        standard_atomic_weight: StandardAtomicWeight::Unknown,
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -1958,7 +2097,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("N"),
         most_abundant_isotope: 14,
         most_abundant_mass: 14.003074,
-        element_number: 14,
+        element_number: 7,
+        name: String::from("Nitrogen"),
+        standard_atomic_weight: StandardAtomicWeight::Interval(14.00643, 14.00728),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -1986,7 +2127,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Na"),
         most_abundant_isotope: 23,
         most_abundant_mass: 22.989769,
-        element_number: 23,
+        element_number: 11,
+        name: String::from("Sodium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(22.98976928),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -2005,7 +2148,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Nb"),
         most_abundant_isotope: 93,
         most_abundant_mass: 92.906378,
-        element_number: 93,
+        element_number: 41,
+        name: String::from("Niobium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(92.90637),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -2024,7 +2169,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Nd"),
         most_abundant_isotope: 142,
         most_abundant_mass: 141.907723,
-        element_number: 142,
+        element_number: 60,
+        name: String::from("Neodymium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(144.242),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -2097,7 +2244,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Ne"),
         most_abundant_isotope: 20,
         most_abundant_mass: 19.992440,
-        element_number: 20,
+        element_number: 10,
+        name: String::from("Neon"),
+        standard_atomic_weight: StandardAtomicWeight::Value(20.1797),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -2134,7 +2283,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Ni"),
         most_abundant_isotope: 58,
         most_abundant_mass: 57.935343,
-        element_number: 58,
+        element_number: 28,
+        name: String::from("Nickel"),
+        standard_atomic_weight: StandardAtomicWeight::Value(58.6934),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -2189,6 +2340,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("No"),
         most_abundant_isotope: 0,
         most_abundant_mass: 259.000000,
+        element_number: 102,
+        name: String::from("Nobelium"),
+        standard_atomic_weight: StandardAtomicWeight::Unknown,
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -2207,6 +2361,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Np"),
         most_abundant_isotope: 0,
         most_abundant_mass: 237.000000,
+        element_number: 93,
+        name: String::from("Neptunium"),
+        standard_atomic_weight: StandardAtomicWeight::MassNumber(237),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -2225,7 +2382,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("O"),
         most_abundant_isotope: 16,
         most_abundant_mass: 15.994915,
-        element_number: 16,
+        element_number: 8,
+        name: String::from("Oxygen"),
+        standard_atomic_weight: StandardAtomicWeight::Interval(15.99903, 15.99977),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -2262,7 +2421,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Os"),
         most_abundant_isotope: 192,
         most_abundant_mass: 191.961481,
-        element_number: 192,
+        element_number: 76,
+        name: String::from("Osmium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(190.23),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -2335,7 +2496,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("P"),
         most_abundant_isotope: 31,
         most_abundant_mass: 30.973762,
-        element_number: 31,
+        element_number: 15,
+        name: String::from("Phosphorus"),
+        standard_atomic_weight: StandardAtomicWeight::Value(30.973761998),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -2354,7 +2517,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Pa"),
         most_abundant_isotope: 231,
         most_abundant_mass: 231.035884,
-        element_number: 231,
+        element_number: 91,
+        name: String::from("Protactinium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(231.03588),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -2373,7 +2538,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Pb"),
         most_abundant_isotope: 208,
         most_abundant_mass: 207.976652,
-        element_number: 208,
+        element_number: 82,
+        name: String::from("Lead"),
+        standard_atomic_weight: StandardAtomicWeight::Value(207.2),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -2419,7 +2586,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Pd"),
         most_abundant_isotope: 106,
         most_abundant_mass: 105.903486,
-        element_number: 106,
+        element_number: 46,
+        name: String::from("Palladium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(106.42),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -2483,6 +2652,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Pm"),
         most_abundant_isotope: 0,
         most_abundant_mass: 145.000000,
+        element_number: 61,
+        name: String::from("Promethium"),
+        standard_atomic_weight: StandardAtomicWeight::MassNumber(145),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -2501,6 +2673,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Po"),
         most_abundant_isotope: 0,
         most_abundant_mass: 209.000000,
+        element_number: 84,
+        name: String::from("Polonium"),
+        standard_atomic_weight: StandardAtomicWeight::MassNumber(209),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -2519,7 +2694,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Pr"),
         most_abundant_isotope: 141,
         most_abundant_mass: 140.907653,
-        element_number: 141,
+        element_number: 59,
+        name: String::from("Praseodymium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(140.90766),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -2538,7 +2715,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Pt"),
         most_abundant_isotope: 195,
         most_abundant_mass: 194.964791,
-        element_number: 195,
+        element_number: 78,
+        name: String::from("Platinum"),
+        standard_atomic_weight: StandardAtomicWeight::Value(195.084),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -2602,6 +2781,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Pu"),
         most_abundant_isotope: 0,
         most_abundant_mass: 244.000000,
+        element_number: 94,
+        name: String::from("Plutonium"),
+        standard_atomic_weight: StandardAtomicWeight::MassNumber(244),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -2620,6 +2802,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Ra"),
         most_abundant_isotope: 0,
         most_abundant_mass: 226.000000,
+        element_number: 88,
+        name: String::from("Radium"),
+        standard_atomic_weight: StandardAtomicWeight::MassNumber(226),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -2638,7 +2823,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Rb"),
         most_abundant_isotope: 85,
         most_abundant_mass: 84.911790,
-        element_number: 85,
+        element_number: 37,
+        name: String::from("Rubidium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(85.4678),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -2666,7 +2853,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Re"),
         most_abundant_isotope: 187,
         most_abundant_mass: 186.955753,
-        element_number: 187,
+        element_number: 75,
+        name: String::from("Rhenium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(186.207),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -2694,6 +2883,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Rf"),
         most_abundant_isotope: 0,
         most_abundant_mass: 265.000000,
+        element_number: 104,
+        name: String::from("Rutherfordium"),
+        standard_atomic_weight: StandardAtomicWeight::Unknown,
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -2712,6 +2904,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Rg"),
         most_abundant_isotope: 0,
         most_abundant_mass: 280.000000,
+        element_number: 111,
+        name: String::from("Roentgenium"),
+        standard_atomic_weight: StandardAtomicWeight::Unknown,
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -2730,7 +2925,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Rh"),
         most_abundant_isotope: 103,
         most_abundant_mass: 102.905504,
-        element_number: 103,
+        element_number: 45,
+        name: String::from("Rhodium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(102.9055),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -2749,6 +2946,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Rn"),
         most_abundant_isotope: 0,
         most_abundant_mass: 222.000000,
+        element_number: 86,
+        name: String::from("Radon"),
+        standard_atomic_weight: StandardAtomicWeight::MassNumber(222),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -2767,7 +2967,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Ru"),
         most_abundant_isotope: 102,
         most_abundant_mass: 101.904349,
-        element_number: 102,
+        element_number: 44,
+        name: String::from("Ruthenium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(101.07),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -2840,7 +3042,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("S"),
         most_abundant_isotope: 32,
         most_abundant_mass: 31.972071,
-        element_number: 32,
+        element_number: 16,
+        name: String::from("Sulfur"),
+        standard_atomic_weight: StandardAtomicWeight::Interval(32.059, 32.076),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -2886,7 +3090,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Sb"),
         most_abundant_isotope: 121,
         most_abundant_mass: 120.903816,
-        element_number: 121,
+        element_number: 51,
+        name: String::from("Antimony"),
+        standard_atomic_weight: StandardAtomicWeight::Value(121.76),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -2914,7 +3120,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Sc"),
         most_abundant_isotope: 45,
         most_abundant_mass: 44.955912,
-        element_number: 45,
+        element_number: 21,
+        name: String::from("Scandium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(44.955908),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -2933,7 +3141,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Se"),
         most_abundant_isotope: 80,
         most_abundant_mass: 79.916521,
-        element_number: 80,
+        element_number: 34,
+        name: String::from("Selenium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(78.971),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -2997,6 +3207,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Sg"),
         most_abundant_isotope: 0,
         most_abundant_mass: 271.000000,
+        element_number: 106,
+        name: String::from("Seaborgium"),
+        standard_atomic_weight: StandardAtomicWeight::Unknown,
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -3015,7 +3228,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Si"),
         most_abundant_isotope: 28,
         most_abundant_mass: 27.976927,
-        element_number: 28,
+        element_number: 14,
+        name: String::from("Silicon"),
+        standard_atomic_weight: StandardAtomicWeight::Interval(28.084, 28.086),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -3052,7 +3267,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Sm"),
         most_abundant_isotope: 152,
         most_abundant_mass: 151.919732,
-        element_number: 152,
+        element_number: 62,
+        name: String::from("Samarium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(150.36),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -3125,7 +3342,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Sn"),
         most_abundant_isotope: 120,
         most_abundant_mass: 119.902195,
-        element_number: 120,
+        element_number: 50,
+        name: String::from("Tin"),
+        standard_atomic_weight: StandardAtomicWeight::Value(118.71),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -3225,7 +3444,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Sr"),
         most_abundant_isotope: 88,
         most_abundant_mass: 87.905612,
-        element_number: 88,
+        element_number: 38,
+        name: String::from("Strontium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(87.62),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -3271,7 +3492,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Ta"),
         most_abundant_isotope: 181,
         most_abundant_mass: 180.947996,
-        element_number: 181,
+        element_number: 73,
+        name: String::from("Tantalum"),
+        standard_atomic_weight: StandardAtomicWeight::Value(180.94788),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -3299,7 +3522,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Tb"),
         most_abundant_isotope: 159,
         most_abundant_mass: 158.925347,
-        element_number: 159,
+        element_number: 65,
+        name: String::from("Terbium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(158.92535),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -3318,6 +3543,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Tc"),
         most_abundant_isotope: 0,
         most_abundant_mass: 98.000000,
+        element_number: 43,
+        name: String::from("Technetium"),
+        standard_atomic_weight: StandardAtomicWeight::MassNumber(98),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -3336,7 +3564,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Te"),
         most_abundant_isotope: 130,
         most_abundant_mass: 129.906224,
-        element_number: 130,
+        element_number: 52,
+        name: String::from("Tellurium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(127.6),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -3418,7 +3648,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Th"),
         most_abundant_isotope: 232,
         most_abundant_mass: 232.038055,
-        element_number: 232,
+        element_number: 90,
+        name: String::from("Thorium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(232.0377),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -3437,7 +3669,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Ti"),
         most_abundant_isotope: 48,
         most_abundant_mass: 47.947946,
-        element_number: 48,
+        element_number: 22,
+        name: String::from("Titanium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(47.867),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -3492,7 +3726,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Tl"),
         most_abundant_isotope: 205,
         most_abundant_mass: 204.974427,
-        element_number: 205,
+        element_number: 81,
+        name: String::from("Thallium"),
+        standard_atomic_weight: StandardAtomicWeight::Interval(204.382, 204.385),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -3520,7 +3756,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Tm"),
         most_abundant_isotope: 169,
         most_abundant_mass: 168.934213,
-        element_number: 169,
+        element_number: 69,
+        name: String::from("Thulium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(168.93422),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -3539,7 +3777,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("U"),
         most_abundant_isotope: 238,
         most_abundant_mass: 238.050788,
-        element_number: 238,
+        element_number: 92,
+        name: String::from("Uranium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(238.02891),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -3576,6 +3816,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Uuh"),
         most_abundant_isotope: 0,
         most_abundant_mass: 293.000000,
+        element_number: 116,
+        name: String::from("Livermorium"),
+        standard_atomic_weight: StandardAtomicWeight::Unknown,
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -3594,6 +3837,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Uuo"),
         most_abundant_isotope: 0,
         most_abundant_mass: 294.000000,
+        element_number: 118,
+        name: String::from("Oganesson"),
+        standard_atomic_weight: StandardAtomicWeight::Unknown,
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -3612,6 +3858,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Uup"),
         most_abundant_isotope: 0,
         most_abundant_mass: 288.000000,
+        element_number: 115,
+        name: String::from("Moscovium"),
+        standard_atomic_weight: StandardAtomicWeight::Unknown,
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -3630,6 +3879,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Uuq"),
         most_abundant_isotope: 0,
         most_abundant_mass: 289.000000,
+        element_number: 114,
+        name: String::from("Flerovium"),
+        standard_atomic_weight: StandardAtomicWeight::Unknown,
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -3648,6 +3900,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Uus"),
         most_abundant_isotope: 0,
         most_abundant_mass: 292.000000,
+        element_number: 117,
+        name: String::from("Tennessine"),
+        standard_atomic_weight: StandardAtomicWeight::Unknown,
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -3666,6 +3921,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Uut"),
         most_abundant_isotope: 0,
         most_abundant_mass: 284.000000,
+        element_number: 113,
+        name: String::from("Nihonium"),
+        standard_atomic_weight: StandardAtomicWeight::Unknown,
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -3684,7 +3942,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("V"),
         most_abundant_isotope: 51,
         most_abundant_mass: 50.943959,
-        element_number: 51,
+        element_number: 23,
+        name: String::from("Vanadium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(50.9415),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -3712,7 +3972,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("W"),
         most_abundant_isotope: 184,
         most_abundant_mass: 183.950931,
-        element_number: 184,
+        element_number: 74,
+        name: String::from("Tungsten"),
+        standard_atomic_weight: StandardAtomicWeight::Value(183.84),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -3767,7 +4029,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Xe"),
         most_abundant_isotope: 132,
         most_abundant_mass: 131.904154,
-        element_number: 132,
+        element_number: 54,
+        name: String::from("Xenon"),
+        standard_atomic_weight: StandardAtomicWeight::Value(131.293),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -3858,7 +4122,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Y"),
         most_abundant_isotope: 89,
         most_abundant_mass: 88.905848,
-        element_number: 89,
+        element_number: 39,
+        name: String::from("Yttrium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(88.90584),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -3877,7 +4143,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Yb"),
         most_abundant_isotope: 174,
         most_abundant_mass: 173.938862,
-        element_number: 174,
+        element_number: 70,
+        name: String::from("Ytterbium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(173.054),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -3950,7 +4218,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Zn"),
         most_abundant_isotope: 64,
         most_abundant_mass: 63.929142,
-        element_number: 64,
+        element_number: 30,
+        name: String::from("Zinc"),
+        standard_atomic_weight: StandardAtomicWeight::Value(65.38),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -4005,7 +4275,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("Zr"),
         most_abundant_isotope: 90,
         most_abundant_mass: 89.904704,
-        element_number: 90,
+        element_number: 40,
+        name: String::from("Zirconium"),
+        standard_atomic_weight: StandardAtomicWeight::Value(91.224),
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -4060,6 +4332,9 @@ pub fn populate_periodic_table(table: &mut PeriodicTable) {
         symbol: String::from("e*"),
         most_abundant_isotope: 0,
         most_abundant_mass: 0.000549,
+        element_number: 0,
+        name: String::from("Electron"),
+        standard_atomic_weight: StandardAtomicWeight::Unknown,
         ..Default::default()
     };
     elt.isotopes.insert(
@@ -4079,4 +4354,4 @@ pub static PERIODIC_TABLE: LazyLock<PeriodicTable> = LazyLock::new(|| {
     let mut t = PeriodicTable::new();
     populate_periodic_table(&mut t);
     t
-});
\ No newline at end of file
+});
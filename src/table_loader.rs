@@ -0,0 +1,606 @@
+//! Build a [`PeriodicTable`] at runtime from external data files, rather than using the
+//! compiled-in [`PERIODIC_TABLE`](crate::PERIODIC_TABLE).
+//!
+//! Supported sources are:
+//! - The NIST ASCII isotopic composition listing, as in `data/nist_comp.txt`
+//! - The `nist_mass.json` layout, mapping symbols to mass numbers to `[mass, abundance]`,
+//!   requiring the `json` feature
+//! - A serialized [`PeriodicTable`] in JSON or TOML, requiring the `json` or `toml` features
+//!
+//! ```rust
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use chemical_elements::{ChemicalComposition, PeriodicTable};
+//!
+//! let listing = "\
+//! Atomic Number = 6
+//! Atomic Symbol = C
+//! Mass Number = 12
+//! Relative Atomic Mass = 12.0000000(00)
+//! Isotopic Composition = 0.9893(8)
+//! Standard Atomic Weight = [12.0096,12.0116]
+//!
+//! Atomic Number = 6
+//! Atomic Symbol = C
+//! Mass Number = 13
+//! Relative Atomic Mass = 13.00335483507(23)
+//! Isotopic Composition = 0.0107(8)
+//! Standard Atomic Weight = [12.0096,12.0116]
+//! ";
+//! let table = PeriodicTable::from_nist_ascii(listing.as_bytes())?;
+//! let comp = ChemicalComposition::parse_with("C6", &table)?;
+//! assert!((comp.mass() - 72.0).abs() < 1e-6);
+//! # Ok(())
+//! # }
+//! ```
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+use std::io;
+
+use crate::element::{Element, Isotope, PeriodicTable, StandardAtomicWeight};
+use crate::table::PERIODIC_TABLE;
+
+#[derive(Debug)]
+pub enum TableLoadError {
+    /// The source could not be read
+    IO(io::Error),
+    /// A record in the source was malformed, with a description of the problem
+    Malformed(String),
+    /// An element has no isotope with a positive abundance
+    MissingAbundance(String),
+    /// An isotope's mass was not a positive, finite number
+    InvalidMass { symbol: String, mass_number: u16 },
+    /// An isotope's abundance was not in `[0, 1]`
+    InvalidAbundance { symbol: String, mass_number: u16 },
+    /// The abundances of an element's isotopes sum to more than 1
+    AbundanceOverflow { symbol: String, total: f64 },
+    /// An element's most abundant isotope is not among its isotopes
+    InvalidMostAbundantIsotope(String),
+}
+
+impl Display for TableLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IO(err) => write!(f, "failed to read the periodic table: {err}"),
+            Self::Malformed(description) => write!(f, "malformed periodic table: {description}"),
+            Self::MissingAbundance(symbol) => {
+                write!(f, "`{symbol}` has no isotope with a positive abundance")
+            }
+            Self::InvalidMass {
+                symbol,
+                mass_number,
+            } => write!(
+                f,
+                "the mass of `{symbol}[{mass_number}]` is not a positive, finite number"
+            ),
+            Self::InvalidAbundance {
+                symbol,
+                mass_number,
+            } => write!(
+                f,
+                "the abundance of `{symbol}[{mass_number}]` is not between 0 and 1"
+            ),
+            Self::AbundanceOverflow { symbol, total } => {
+                write!(f, "the isotope abundances of `{symbol}` sum to {total}")
+            }
+            Self::InvalidMostAbundantIsotope(symbol) => write!(
+                f,
+                "the most abundant isotope of `{symbol}` is not among its isotopes"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TableLoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::IO(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for TableLoadError {
+    fn from(value: io::Error) -> Self {
+        Self::IO(value)
+    }
+}
+
+/// How far above 1 the sum of an element's isotopic abundances may be before it is rejected,
+/// allowing for rounding in the source data.
+const ABUNDANCE_TOLERANCE: f64 = 1e-3;
+
+/// An isotope as read from a source, before it has been validated
+struct IsotopeRecord {
+    mass_number: u16,
+    mass: f64,
+    abundance: f64,
+}
+
+fn strip_uncertainty(value: &str) -> &str {
+    match value.find('(') {
+        Some(i) => value[..i].trim(),
+        None => value.trim(),
+    }
+}
+
+fn parse_standard_atomic_weight(value: &str) -> Result<StandardAtomicWeight, TableLoadError> {
+    let value = value.trim();
+    let malformed =
+        || TableLoadError::Malformed(format!("Invalid standard atomic weight {value:?}"));
+    if value.is_empty() {
+        return Ok(StandardAtomicWeight::Unknown);
+    }
+    if let Some(inner) = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+        return match inner.split_once(',') {
+            Some((lo, hi)) => Ok(StandardAtomicWeight::Interval(
+                lo.trim().parse().map_err(|_| malformed())?,
+                hi.trim().parse().map_err(|_| malformed())?,
+            )),
+            None => Ok(StandardAtomicWeight::MassNumber(
+                inner.trim().parse().map_err(|_| malformed())?,
+            )),
+        };
+    }
+    Ok(StandardAtomicWeight::Value(
+        strip_uncertainty(value).parse().map_err(|_| malformed())?,
+    ))
+}
+
+fn validate_isotope(symbol: &str, isotope: &Isotope) -> Result<(), TableLoadError> {
+    if !isotope.mass.is_finite() || isotope.mass <= 0.0 {
+        return Err(TableLoadError::InvalidMass {
+            symbol: symbol.to_string(),
+            mass_number: isotope.neutrons,
+        });
+    }
+    if !(0.0..=1.0).contains(&isotope.abundance) {
+        return Err(TableLoadError::InvalidAbundance {
+            symbol: symbol.to_string(),
+            mass_number: isotope.neutrons,
+        });
+    }
+    Ok(())
+}
+
+/// Validate an element's isotopes, which must have positive masses and abundances
+/// that sum to at most 1, and that its most abundant isotope is present.
+pub fn validate_element(element: &Element) -> Result<(), TableLoadError> {
+    let symbol = element.symbol.as_str();
+    let mut total = 0.0;
+    for isotope in element.isotopes.values() {
+        validate_isotope(symbol, isotope)?;
+        if isotope.neutrons != 0 {
+            total += isotope.abundance;
+        }
+    }
+    if !element.isotopes.values().any(|iso| iso.abundance > 0.0) {
+        return Err(TableLoadError::MissingAbundance(symbol.to_string()));
+    }
+    if total > 1.0 + ABUNDANCE_TOLERANCE {
+        return Err(TableLoadError::AbundanceOverflow {
+            symbol: symbol.to_string(),
+            total,
+        });
+    }
+    if !element
+        .isotopes
+        .contains_key(&element.most_abundant_isotope)
+    {
+        return Err(TableLoadError::InvalidMostAbundantIsotope(
+            symbol.to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/**
+Build an [`Element`] the same way the compiled-in table is generated: isotopes without
+abundance are dropped, and the reference entry with mass number 0 is only used when no
+isotope has a known abundance.
+
+Atomic metadata missing from the source is taken from the compiled-in table by symbol.
+*/
+fn build_element(
+    symbol: &str,
+    records: Vec<IsotopeRecord>,
+    reference: Option<IsotopeRecord>,
+) -> Result<Element, TableLoadError> {
+    let mut element = Element {
+        symbol: symbol.to_string(),
+        ..Default::default()
+    };
+    if let Some(builtin) = PERIODIC_TABLE.get(symbol) {
+        element.element_number = builtin.element_number;
+        element.name = builtin.name.clone();
+        element.standard_atomic_weight = builtin.standard_atomic_weight;
+    }
+
+    let records: Vec<_> = records.into_iter().filter(|r| r.abundance != 0.0).collect();
+    if records.is_empty() {
+        let reference =
+            reference.ok_or_else(|| TableLoadError::MissingAbundance(symbol.to_string()))?;
+        element.most_abundant_mass = reference.mass;
+        element.isotopes.insert(
+            0,
+            Isotope {
+                mass: reference.mass,
+                abundance: reference.abundance,
+                neutrons: 0,
+                neutron_shift: 0,
+            },
+        );
+    } else {
+        let most_abundant = records
+            .iter()
+            .max_by(|a, b| a.abundance.total_cmp(&b.abundance))
+            .unwrap();
+        element.most_abundant_isotope = most_abundant.mass_number;
+        element.most_abundant_mass = most_abundant.mass;
+        for record in records.iter() {
+            element.isotopes.insert(
+                record.mass_number,
+                Isotope {
+                    mass: record.mass,
+                    abundance: record.abundance,
+                    neutrons: record.mass_number,
+                    neutron_shift: (record.mass_number as i32
+                        - element.most_abundant_isotope as i32)
+                        as i8,
+                },
+            );
+        }
+    }
+    element.index_isotopes();
+    validate_element(&element)?;
+    Ok(element)
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+/// The symbols of a NIST ASCII listing which did not become elements of their own,
+/// from [`PeriodicTable::from_nist_ascii_with_skipped`]
+pub struct NistAsciiSkipped {
+    /// Symbols folded into the first symbol listed for their atomic number, paired with
+    /// that symbol, like `("D", "H")`
+    pub folded: Vec<(String, String)>,
+    /// Symbols dropped because none of their isotopes has a known abundance and they
+    /// have no reference mass
+    pub dropped: Vec<String>,
+}
+
+impl PeriodicTable {
+    /// Validate every [`Element`] in the table with [`validate_element`]
+    pub fn validate(&self) -> Result<(), TableLoadError> {
+        self.elements.values().try_for_each(validate_element)
+    }
+
+    /**
+    Read a table from the NIST ASCII isotopic composition listing, which is made up of
+    blank-line separated records of `Key = Value` lines.
+
+    Not every record becomes an element of its own:
+    - Isotopes are grouped by atomic number under the first symbol listed for that number,
+      so the `D` and `T` entries become isotopes of `H`, written `H[2]` and `H[3]`.
+    - Elements without any isotope of known abundance use the mass number given as their
+      standard atomic weight as a reference mass, like the compiled-in table, and are
+      dropped if they do not have one.
+
+    The listing also has no entries for the compiled-in table's `H+` and `e*` pseudo-elements,
+    so they are absent from the result, and can be copied over with
+    `table.add(PERIODIC_TABLE["e*"].clone())` where needed.
+
+    Use [`PeriodicTable::from_nist_ascii_with_skipped`] to find out which symbols were
+    folded or dropped.
+    */
+    pub fn from_nist_ascii<R: io::Read>(reader: R) -> Result<PeriodicTable, TableLoadError> {
+        Self::from_nist_ascii_with_skipped(reader).map(|(table, _)| table)
+    }
+
+    /**
+    As [`PeriodicTable::from_nist_ascii`], but also return the symbols which did not become
+    elements of their own
+    */
+    pub fn from_nist_ascii_with_skipped<R: io::Read>(
+        mut reader: R,
+    ) -> Result<(PeriodicTable, NistAsciiSkipped), TableLoadError> {
+        let mut buffer = String::new();
+        reader.read_to_string(&mut buffer)?;
+
+        struct Group {
+            symbol: String,
+            weight: StandardAtomicWeight,
+            records: Vec<IsotopeRecord>,
+        }
+        let mut groups: BTreeMap<u8, Group> = BTreeMap::new();
+        let mut skipped = NistAsciiSkipped::default();
+
+        let mut fields: Vec<(&str, &str)> = Vec::new();
+        let lines = buffer.lines().chain(std::iter::once(""));
+        for (lineno, line) in lines.enumerate() {
+            let line = line.trim();
+            if line.starts_with('#') {
+                continue;
+            }
+            if !line.is_empty() {
+                let (key, value) = line.split_once('=').ok_or_else(|| {
+                    TableLoadError::Malformed(format!(
+                        "Expected `Key = Value` on line {}",
+                        lineno + 1
+                    ))
+                })?;
+                fields.push((key.trim(), value.trim()));
+                continue;
+            }
+            if fields.is_empty() {
+                continue;
+            }
+            let get = |key: &str| {
+                fields
+                    .iter()
+                    .find(|(k, _)| *k == key)
+                    .map(|(_, v)| *v)
+                    .ok_or_else(|| {
+                        TableLoadError::Malformed(format!(
+                            "Missing {key:?} in record ending on line {lineno}"
+                        ))
+                    })
+            };
+            let malformed = |key: &str| {
+                TableLoadError::Malformed(format!(
+                    "Invalid {key:?} in record ending on line {lineno}"
+                ))
+            };
+
+            let atomic_number: u8 = get("Atomic Number")?
+                .parse()
+                .map_err(|_| malformed("Atomic Number"))?;
+            let symbol = get("Atomic Symbol")?;
+            let mass_number: u16 = get("Mass Number")?
+                .parse()
+                .map_err(|_| malformed("Mass Number"))?;
+            let mass: f64 = strip_uncertainty(get("Relative Atomic Mass")?)
+                .parse()
+                .map_err(|_| malformed("Relative Atomic Mass"))?;
+            let abundance = strip_uncertainty(get("Isotopic Composition").unwrap_or(""));
+            let abundance: f64 = if abundance.is_empty() {
+                0.0
+            } else {
+                abundance
+                    .parse()
+                    .map_err(|_| malformed("Isotopic Composition"))?
+            };
+            let weight = parse_standard_atomic_weight(get("Standard Atomic Weight").unwrap_or(""))?;
+
+            let group = groups.entry(atomic_number).or_insert_with(|| Group {
+                symbol: symbol.to_string(),
+                weight,
+                records: Vec::new(),
+            });
+            if group.symbol != symbol && !skipped.folded.iter().any(|(s, _)| s == symbol) {
+                skipped
+                    .folded
+                    .push((symbol.to_string(), group.symbol.clone()));
+            }
+            group.records.push(IsotopeRecord {
+                mass_number,
+                mass,
+                abundance,
+            });
+            fields.clear();
+        }
+
+        let mut table = PeriodicTable::new();
+        for (atomic_number, group) in groups {
+            let reference = match group.weight {
+                StandardAtomicWeight::MassNumber(n) => Some(IsotopeRecord {
+                    mass_number: 0,
+                    mass: n as f64,
+                    abundance: 1.0,
+                }),
+                _ => None,
+            };
+            if reference.is_none() && group.records.iter().all(|r| r.abundance == 0.0) {
+                skipped.dropped.push(group.symbol);
+                continue;
+            }
+            let mut element = build_element(&group.symbol, group.records, reference)?;
+            element.element_number = atomic_number;
+            element.standard_atomic_weight = group.weight;
+            table.add(element);
+        }
+        Ok((table, skipped))
+    }
+}
+
+#[cfg(feature = "json")]
+impl PeriodicTable {
+    /**
+    Read a table from the `nist_mass.json` layout used to generate the compiled-in table,
+    a mapping from element symbol to mass number to `[mass, abundance]`, where mass number
+    `0` denotes a reference entry used when no isotope has a known abundance.
+    */
+    pub fn from_nist_mass_json<R: io::Read>(reader: R) -> Result<PeriodicTable, TableLoadError> {
+        let raw: BTreeMap<String, BTreeMap<String, (f64, f64)>> =
+            serde_json::from_reader(reader)
+                .map_err(|e| TableLoadError::Malformed(e.to_string()))?;
+        let mut table = PeriodicTable::new();
+        for (symbol, isotopes) in raw {
+            let mut records = Vec::with_capacity(isotopes.len());
+            let mut reference = None;
+            for (mass_number, (mass, abundance)) in isotopes {
+                let mass_number: u16 = mass_number.parse().map_err(|_| {
+                    TableLoadError::Malformed(format!(
+                        "Invalid mass number {mass_number:?} for {symbol}"
+                    ))
+                })?;
+                let record = IsotopeRecord {
+                    mass_number,
+                    mass,
+                    abundance,
+                };
+                if mass_number == 0 {
+                    reference = Some(record);
+                } else {
+                    records.push(record);
+                }
+            }
+            table.add(build_element(&symbol, records, reference)?);
+        }
+        Ok(table)
+    }
+
+    /// Read a serialized [`PeriodicTable`] from JSON and validate it
+    pub fn from_json<R: io::Read>(reader: R) -> Result<PeriodicTable, TableLoadError> {
+        let table: PeriodicTable = serde_json::from_reader(reader)
+            .map_err(|e| TableLoadError::Malformed(e.to_string()))?;
+        table.validate()?;
+        Ok(table)
+    }
+}
+
+#[cfg(feature = "toml")]
+impl PeriodicTable {
+    /// Read a serialized [`PeriodicTable`] from TOML and validate it
+    pub fn from_toml(text: &str) -> Result<PeriodicTable, TableLoadError> {
+        let table: PeriodicTable =
+            toml::from_str(text).map_err(|e| TableLoadError::Malformed(e.to_string()))?;
+        table.validate()?;
+        Ok(table)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_nist_ascii() {
+        let reader = fs::File::open("data/nist_comp.txt").unwrap();
+        let (table, skipped) = PeriodicTable::from_nist_ascii_with_skipped(reader).unwrap();
+        assert!(table.get("D").is_none());
+        assert!(table.get("H+").is_none());
+        assert_eq!(
+            skipped.folded[..2],
+            [
+                ("D".to_string(), "H".to_string()),
+                ("T".to_string(), "H".to_string())
+            ]
+        );
+        assert!(skipped.dropped.contains(&"Og".to_string()));
+        assert!(skipped.dropped.iter().all(|symbol| table.get(symbol).is_none()));
+
+        let hydrogen = &table["H"];
+        assert_eq!(hydrogen.element_number, 1);
+        assert_eq!(hydrogen.isotopes.len(), 2);
+        assert_eq!(hydrogen.most_abundant_isotope, 1);
+
+        let iron = &table["Fe"];
+        assert_eq!(iron.isotopes.len(), 4);
+        assert_eq!(iron.monoisotopic_isotope(), 54);
+        assert!((iron.mass() - PERIODIC_TABLE["Fe"].mass()).abs() < 1e-6);
+
+        let technetium = &table["Tc"];
+        assert_eq!(technetium.most_abundant_isotope, 0);
+        assert_eq!(technetium.mass(), 98.0);
+
+        let comp = crate::ChemicalComposition::parse_with("C6H12O6", &table).unwrap();
+        assert!((comp.mass() - 180.06339).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_validation() {
+        let listing = "\
+Atomic Number = 6
+Atomic Symbol = C
+Mass Number = 12
+Relative Atomic Mass = 12.0000000(00)
+Isotopic Composition = 0.9893(8)
+Standard Atomic Weight = [12.0096,12.0116]
+
+Atomic Number = 6
+Atomic Symbol = C
+Mass Number = 13
+Relative Atomic Mass = 13.00335483507(23)
+Isotopic Composition = 0.9893(8)
+Standard Atomic Weight = [12.0096,12.0116]
+";
+        assert!(matches!(
+            PeriodicTable::from_nist_ascii(listing.as_bytes()),
+            Err(TableLoadError::AbundanceOverflow { .. })
+        ));
+        let listing = listing.replace("13.00335483507(23)", "-13.0");
+        assert!(matches!(
+            PeriodicTable::from_nist_ascii(listing.as_bytes()),
+            Err(TableLoadError::InvalidMass {
+                mass_number: 13,
+                ..
+            })
+        ));
+        assert_eq!(
+            PeriodicTable::from_nist_ascii(listing.as_bytes())
+                .unwrap_err()
+                .to_string(),
+            "the mass of `C[13]` is not a positive, finite number"
+        );
+        assert!(matches!(
+            PeriodicTable::from_nist_ascii("Atomic Number 6".as_bytes()),
+            Err(TableLoadError::Malformed(_))
+        ));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_json() {
+        let reader = fs::File::open("data/nist_mass.json").unwrap();
+        let table = PeriodicTable::from_nist_mass_json(reader).unwrap();
+        assert_eq!(table.elements.len(), PERIODIC_TABLE.elements.len());
+        for (symbol, element) in table.elements.iter() {
+            let builtin = &PERIODIC_TABLE[symbol.as_str()];
+            assert_eq!(
+                element.most_abundant_isotope, builtin.most_abundant_isotope,
+                "{symbol}"
+            );
+            assert_eq!(element.isotopes.len(), builtin.isotopes.len(), "{symbol}");
+            assert_eq!(element.element_number, builtin.element_number, "{symbol}");
+        }
+
+        let text = serde_json::to_string(&table).unwrap();
+        let reloaded = PeriodicTable::from_json(text.as_bytes()).unwrap();
+        assert_eq!(reloaded["Se"], table["Se"]);
+
+        // Elements saved before the atomic metadata was added still load
+        let mut legacy = serde_json::to_value(&table["C"]).unwrap();
+        let fields = legacy.as_object_mut().unwrap();
+        fields.remove("name");
+        fields.remove("standard_atomic_weight");
+        let element: Element = serde_json::from_value(legacy).unwrap();
+        assert_eq!(element, table["C"]);
+        // Element equality does not compare the atomic metadata, so check the defaults
+        assert_eq!(element.name, "");
+        assert_eq!(
+            element.standard_atomic_weight,
+            StandardAtomicWeight::Unknown
+        );
+
+        let missing = r#"{"C": {"12": [12.0, 0.0], "13": [13.003355, 0.0]}}"#;
+        assert!(matches!(
+            PeriodicTable::from_nist_mass_json(missing.as_bytes()),
+            Err(TableLoadError::MissingAbundance(_))
+        ));
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_toml() {
+        let mut table = PeriodicTable::new();
+        table.add(PERIODIC_TABLE["C"].clone());
+        table.add(PERIODIC_TABLE["H"].clone());
+        let text = toml::to_string(&table).unwrap();
+        let reloaded = PeriodicTable::from_toml(&text).unwrap();
+        assert_eq!(reloaded["C"], table["C"]);
+        assert!(
+            (reloaded["H"].isotopes[&2].abundance - table["H"].isotopes[&2].abundance).abs()
+                < 1e-12
+        );
+    }
+}